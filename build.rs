@@ -7,6 +7,6 @@ fn main() {
 		res.compile().expect("Could not compile windows resource!");
 	}
 	if std::env::var("SFILE_PASSWORD").is_err() {
-        println!("cargo:warning=SFILE_PASSWORD is not set! ZIP decryption will not work.");
+        println!("cargo:warning=SFILE_PASSWORD is not set! Encrypted sfiles need a password at runtime.");
     }
 }
@@ -0,0 +1,62 @@
+// Configuration directories ---------------------------------------------------------
+// Sherlog looks for configuration in several places. Later directories take
+// precedence over earlier ones, so users can override what ships with Sherlog:
+//
+// 1. `config` folder next to the executable (bundled with the installer)
+// 2. Per-user configuration folder:
+//    Windows: %APPDATA%\Sherlog
+//    Others:  $XDG_CONFIG_HOME/sherlog or ~/.config/sherlog
+// 3. Folder given by the environment variable SHERLOG_CONFIG_DIR
+// ------------------------------------------------------------------------------------
+
+use std::path::PathBuf;
+
+pub fn config_dirs() -> Vec<PathBuf> {
+	let mut dirs = Vec::new();
+
+	if let Ok(exe_path) = std::env::current_exe() {
+		if let Some(exe_dir) = exe_path.parent() {
+			dirs.push(exe_dir.join("config"));
+		}
+	}
+
+	if let Some(user_dir) = user_config_dir() {
+		dirs.push(user_dir);
+	}
+
+	if let Some(env_dir) = std::env::var_os("SHERLOG_CONFIG_DIR") {
+		dirs.push(PathBuf::from(env_dir));
+	}
+
+	dirs
+}
+
+// Returns the existing sub-directories `name` of all configuration directories,
+// ordered from lowest to highest precedence.
+pub fn config_sub_dirs(name: &str) -> Vec<PathBuf> {
+	config_dirs()
+		.into_iter()
+		.map(|dir| dir.join(name))
+		.filter(|dir| dir.is_dir())
+		.collect()
+}
+
+// Returns the existing files `name` of all configuration directories,
+// ordered from lowest to highest precedence.
+pub fn config_files(name: &str) -> Vec<PathBuf> {
+	config_dirs()
+		.into_iter()
+		.map(|dir| dir.join(name))
+		.filter(|path| path.is_file())
+		.collect()
+}
+
+fn user_config_dir() -> Option<PathBuf> {
+	if cfg!(windows) {
+		std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("Sherlog"))
+	} else if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+		Some(PathBuf::from(dir).join("sherlog"))
+	} else {
+		std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".config").join("sherlog"))
+	}
+}
@@ -0,0 +1,148 @@
+// Error code dictionary -------------------------------------------------------------
+// Describes the error codes of glog and RDS logs. The dictionary is read from
+// `error_codes.toml` in all Sherlog configuration directories (see
+// `config::config_dirs`), definitions in later directories override earlier ones:
+//
+// [[error_code]]
+// code = 1042
+// source = "Hwa"          # optional, only for entries of this log source
+// title = "Motor overcurrent"
+// description = "The motor driver reported a current above its limit."
+// action = "Check the motor cable for shorts."   # optional
+//
+// `source` matches any part of the log source path, e.g. "Hwa" matches the entries
+// of "/device.sfile/Controller/Hwa". Definitions with a source take precedence over
+// the ones without.
+// ------------------------------------------------------------------------------------
+
+use serde::Deserialize;
+
+use std::collections::HashMap;
+
+const DICTIONARY_FILE: &str = "error_codes.toml";
+
+#[derive(Deserialize, Clone)]
+pub struct ErrorCodeInfo {
+	pub code: u32,
+	pub source: Option<String>,
+	pub title: String,
+	#[serde(default)]
+	pub description: String,
+	pub action: Option<String>,
+}
+
+impl ErrorCodeInfo {
+	pub fn tooltip(&self) -> String {
+		let mut tooltip = format!("{}: {}", self.code, self.title);
+		if !self.description.is_empty() {
+			tooltip += "\n\n";
+			tooltip += &self.description;
+		}
+		if let Some(action) = &self.action {
+			tooltip += "\n\nRecommended action: ";
+			tooltip += action;
+		}
+		tooltip
+	}
+}
+
+#[derive(Deserialize)]
+struct DictionaryFile {
+	#[serde(default)]
+	error_code: Vec<ErrorCodeInfo>,
+}
+
+#[derive(Default)]
+pub struct ErrorCodeDictionary {
+	infos: HashMap<u32, Vec<ErrorCodeInfo>>,
+}
+
+impl ErrorCodeDictionary {
+	// Loads the dictionaries of all configuration directories. Files that fail to
+	// load are skipped with a warning.
+	pub fn load() -> ErrorCodeDictionary {
+		let mut dictionary = ErrorCodeDictionary::default();
+		for path in crate::config::config_files(DICTIONARY_FILE) {
+			match std::fs::read_to_string(&path)
+				.map_err(|e| e.to_string())
+				.and_then(|s| dictionary.add_definitions(&s).map_err(|e| e.to_string()))
+			{
+				Ok(()) => log::info!("Loaded error codes from {}", path.display()),
+				Err(e) => log::warn!("Ignoring {}: {}", path.display(), e),
+			}
+		}
+		dictionary
+	}
+
+	// Adds the definitions of a dictionary file, replacing the ones for the same
+	// code and source
+	pub fn add_definitions(&mut self, definitions: &str) -> Result<(), toml::de::Error> {
+		let file: DictionaryFile = toml::from_str(definitions)?;
+		for info in file.error_code {
+			let infos = self.infos.entry(info.code).or_default();
+			infos.retain(|existing| existing.source != info.source);
+			infos.push(info);
+		}
+		Ok(())
+	}
+
+	// `source_name` is the full name of the log source, as in `LogStoreLinear::log_sources`
+	pub fn lookup(&self, code: u32, source_name: &str) -> Option<&ErrorCodeInfo> {
+		let infos = self.infos.get(&code)?;
+		infos
+			.iter()
+			.find(|info| {
+				info.source
+					.as_ref()
+					.is_some_and(|source| source_name.split('/').any(|part| part == source))
+			})
+			.or_else(|| infos.iter().find(|info| info.source.is_none()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_error_code_dictionary() {
+		let mut dictionary = ErrorCodeDictionary::default();
+		dictionary
+			.add_definitions(
+				r#"
+				[[error_code]]
+				code = 1042
+				title = "Overcurrent"
+
+				[[error_code]]
+				code = 1042
+				source = "Hwa"
+				title = "Motor overcurrent"
+				description = "The motor driver reported a current above its limit."
+				action = "Check the motor cable."
+				"#,
+			)
+			.unwrap();
+		dictionary
+			.add_definitions("[[error_code]]\ncode = 0x10\ntitle = \"Old\"\n[[error_code]]\ncode = 16\ntitle = \"New\"")
+			.unwrap();
+
+		let info = dictionary
+			.lookup(1042, "/device.sfile/Controller/Hwa")
+			.unwrap();
+		assert_eq!(info.title, "Motor overcurrent");
+		assert_eq!(
+			info.tooltip(),
+			"1042: Motor overcurrent\n\nThe motor driver reported a current above its limit.\n\nRecommended action: Check the motor cable."
+		);
+		assert_eq!(
+			dictionary.lookup(1042, "/device.sfile/Hwa2").unwrap().title,
+			"Overcurrent"
+		);
+		assert_eq!(dictionary.lookup(16, "/rds.log").unwrap().title, "New");
+		assert!(dictionary.lookup(7, "/rds.log").is_none());
+		assert!(dictionary
+			.add_definitions("[[error_code]]\ntitle = \"No code\"")
+			.is_err());
+	}
+}
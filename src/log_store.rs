@@ -4,6 +4,8 @@ use chrono::prelude::*;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use crate::error_codes::ErrorCodeDictionary;
+use crate::error_codes::ErrorCodeInfo;
 use crate::model;
 
 use crate::model_internal::LogEntryExt;
@@ -32,6 +34,17 @@ pub struct ScrollBarVert {
 	pub scroll_perc: f64,
 }
 
+// Devices have their own clocks, so the time shift applies per sfile (archive)
+pub struct SensorShift {
+	pub archive_name: String,
+
+	// Current time shift value (accumulated shift applied to sensor/controller logs)
+	pub sensor_shift: chrono::Duration,
+
+	// IDs of log sources that should be shifted (groups with `time_shift` in the sfile rules)
+	pub log_sources_to_shift: Vec<u32>,
+}
+
 pub struct LogStoreLinear {
 	pub store: Vec<LogEntryExt>,
 	pub entry_count: usize,  //entry_count <= store.len(), number of active items
@@ -45,6 +58,7 @@ pub struct LogStoreLinear {
 	pub show_info: bool,
 	pub show_dbg: bool,
 	pub show_trace: bool,
+	pub hidden_source_severities: HashSet<model::SourceSeverity>,
 
 	pub selected_single: HashSet<usize>,
 	pub excluded_single: HashSet<usize>,
@@ -74,13 +88,16 @@ pub struct LogStoreLinear {
 	// This does not affect stored UTC timestamps or time-diff calculations.
 	pub tz_offset: chrono::Duration,
 
-	// Current time shift value (accumulated shift applied to sensor/controller logs)
-    pub sensor_shift: chrono::Duration,
-
-    // IDs of log sources that should be shifted (e.g., Sensor, Controller, Probe, Connect Box)
-    pub log_sources_to_shift: Vec<u32>,
+	// Time shifts of sensor/controller logs, one per opened sfile
+	pub sensor_shifts: Vec<SensorShift>,
 
 	pub sort_mode: SortMode,
+
+	// Only entries with this error code are shown, if set
+	pub error_code_filter: Option<u32>,
+	// Error codes get their own column if any entry has one
+	pub error_code_column: bool,
+	pub error_codes: ErrorCodeDictionary,
 }
 
 impl LogStoreLinear {
@@ -196,13 +213,17 @@ impl LogStoreLinear {
 				Utc,
 			),
 			severity: model::LogLevel::Error,
+			source_severity: None,
 			session_id: Some(0),
+			error_code: None,
 			message: "Foo".to_string(),
 			source_id: 0,
 			visible: crate::model_internal::VISIBLE_ON,
 			entry_id: 0,
 			prev_offset: 0,
 			next_offset: 0,
+			diagnostic_id: None,
+			custom_fields: Box::new([]),
 		};
 
 		{
@@ -348,12 +369,90 @@ impl LogStoreLinear {
 		viewport_offset_old != self.viewport_offset
 	}
 
+	pub fn is_severity_shown(&self, severity: model::LogLevel) -> bool {
+		match severity {
+			model::LogLevel::Critical => self.show_crit,
+			model::LogLevel::Error => self.show_err,
+			model::LogLevel::Warning => self.show_warn,
+			model::LogLevel::Info => self.show_info,
+			model::LogLevel::Debug => self.show_dbg,
+			model::LogLevel::Trace => self.show_trace,
+		}
+	}
+
+	pub fn set_severity_shown(&mut self, severity: model::LogLevel, shown: bool) {
+		match severity {
+			model::LogLevel::Critical => self.show_crit = shown,
+			model::LogLevel::Error => self.show_err = shown,
+			model::LogLevel::Warning => self.show_warn = shown,
+			model::LogLevel::Info => self.show_info = shown,
+			model::LogLevel::Debug => self.show_dbg = shown,
+			model::LogLevel::Trace => self.show_trace = shown,
+		}
+	}
+
+	// Appends entries read in follow mode, hidden according to the current source and
+	// severity filters. The caller has to sort the store and re-apply the search filter
+	// afterwards, which also links the new entries into the prev/next offset chain.
+	pub fn append_entries(&mut self, entries: Vec<LogEntryExt>, is_source_hidden: &dyn Fn(u32) -> bool) {
+		self.store.reserve(entries.len());
+		for mut entry in entries {
+			entry.visible = crate::model_internal::VISIBLE_ON;
+			if is_source_hidden(entry.source_id) {
+				entry.visible |= crate::model_internal::VISIBLE_OFF_SOURCE;
+			}
+			//Entries with a severity as logged only follow their "Logged as" toggle
+			match entry.source_severity {
+				Some(source_severity) => {
+					if self.hidden_source_severities.contains(&source_severity) {
+						entry.visible |= crate::model_internal::VISIBLE_OFF_SOURCE_SEVERITY;
+					}
+				}
+				None => {
+					if !self.is_severity_shown(entry.severity) {
+						entry.visible |= crate::model_internal::VISIBLE_OFF_SEVERITY;
+					}
+				}
+			}
+			if self.error_code_filter.is_some() && entry.error_code != self.error_code_filter {
+				entry.visible |= crate::model_internal::VISIBLE_OFF_ERROR_CODE;
+			}
+			self.error_code_column |= entry.error_code.is_some();
+			for shift in &self.sensor_shifts {
+				if shift.log_sources_to_shift.contains(&entry.source_id) {
+					entry.timestamp += shift.sensor_shift;
+				}
+			}
+			self.store.push(entry);
+		}
+	}
+
+	// Distinct error codes of all entries (also hidden ones) with their number of entries,
+	// sorted by code
+	pub fn error_code_counts(&self) -> Vec<(u32, usize)> {
+		let mut counts = HashMap::<u32, usize>::new();
+		for error_code in self.store.iter().filter_map(|entry| entry.error_code) {
+			*counts.entry(error_code).or_default() += 1;
+		}
+		let mut counts: Vec<(u32, usize)> = counts.into_iter().collect();
+		counts.sort_unstable();
+		counts
+	}
+
+	pub fn error_code_info(&self, entry: &LogEntryExt) -> Option<&ErrorCodeInfo> {
+		let error_code = entry.error_code?;
+		let source_name = self.log_sources.get(&entry.source_id)?;
+		self.error_codes.lookup(error_code, source_name)
+	}
+
 	pub fn shift_store_times(
 		&mut self,
+		shift_idx: usize,
 		time_shift: chrono::Duration) 
 	{
+		let log_sources_to_shift = &self.sensor_shifts[shift_idx].log_sources_to_shift;
 		for entry in self.store.iter_mut() {
-			if self.log_sources_to_shift.contains(&entry.source_id) {
+			if log_sources_to_shift.contains(&entry.source_id) {
 				entry.timestamp = entry.timestamp + time_shift;
 			}
 		}
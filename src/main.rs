@@ -20,26 +20,34 @@ use std::time::Instant;
 
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::fmt::Write;
 
 #[allow(unused_imports)]
 use regex::Regex;
 
+mod config;
+mod error_codes;
 mod log_store;
 mod model;
 mod model_internal;
 mod parse;
 mod ui_formatting;
 mod ui_actions;
+mod view_state;
 
 use log_store::LogStoreLinear;
 use log_store::SortMode;
 use log_store::ScrollBarVert;
+use log_store::SensorShift;
 
+use model_internal::DiagnosticExt;
 use model_internal::LogEntryExt;
 use model_internal::LogSourceContentsExt;
 use model_internal::LogSourceExt;
 
+use view_state::ViewState;
+
 use crate::ui_formatting::get_timezones;
 
 
@@ -59,6 +67,77 @@ enum LogSourcesColumns {
 	ChildCount = 4,
 }
 
+enum DiagnosticsColumns {
+	Source = 0,
+	Location = 1,
+	Kind = 2,
+	Snippet = 3,
+	DiagnosticId = 4, //-1 if the diagnostic does not belong to an entry
+	File = 5,         //Shown as tooltip
+}
+
+fn append_diagnostics(list_store: &ListStore, diagnostics: &[DiagnosticExt]) {
+	for diagnostic_ext in diagnostics {
+		let location = match diagnostic_ext.diagnostic.location {
+			model::DiagnosticLocation::Entry(idx) => format!("Entry {}", idx),
+			model::DiagnosticLocation::ByteOffset(offset) => format!("Byte {}", offset),
+		};
+		let diagnostic_id = diagnostic_ext.diagnostic_id.map_or(-1, i64::from);
+		list_store.insert_with_values(
+			None,
+			&[
+				(DiagnosticsColumns::Source as u32, &diagnostic_ext.source_name),
+				(DiagnosticsColumns::Location as u32, &location),
+				(
+					DiagnosticsColumns::Kind as u32,
+					&diagnostic_ext.diagnostic.kind.to_string(),
+				),
+				(
+					DiagnosticsColumns::Snippet as u32,
+					&diagnostic_ext.diagnostic.snippet,
+				),
+				(DiagnosticsColumns::DiagnosticId as u32, &diagnostic_id),
+				(
+					DiagnosticsColumns::File as u32,
+					&diagnostic_ext.diagnostic.source_name,
+				),
+			],
+		);
+	}
+}
+
+enum ErrorCodesColumns {
+	Text = 0,
+	Count = 1,
+	ErrorCode = 2, //-1 for the row that shows all entries
+}
+
+fn fill_error_codes(list_store: &ListStore, store: &LogStoreLinear) {
+	list_store.clear();
+	list_store.insert_with_values(
+		None,
+		&[
+			(ErrorCodesColumns::Text as u32, &"All"),
+			(ErrorCodesColumns::Count as u32, &(store.store.len() as u64)),
+			(ErrorCodesColumns::ErrorCode as u32, &-1i64),
+		],
+	);
+	for (error_code, count) in store.error_code_counts() {
+		list_store.insert_with_values(
+			None,
+			&[
+				(ErrorCodesColumns::Text as u32, &error_code.to_string()),
+				(ErrorCodesColumns::Count as u32, &(count as u64)),
+				(ErrorCodesColumns::ErrorCode as u32, &i64::from(error_code)),
+			],
+		);
+	}
+}
+
+fn diagnostics_title(count: usize) -> String {
+	format!("Diagnostics ({})", count)
+}
+
 fn toggle_row(
 	tree_store: &gtk::TreeStore,
 	store: &mut LogStoreLinear,
@@ -380,7 +459,7 @@ fn draw(
 		ctx.move_to(store.border_left + 45.0, font_offset_y);
 		ctx.show_text(&date_str).unwrap();
 
-		let short_sev = ui_formatting::short_severity(&entry.severity);
+		let short_sev = ui_formatting::short_entry_severity(entry);
 
 		ctx.move_to(store.border_left + 235.0, font_offset_y);
 		ctx.show_text(&short_sev).unwrap();
@@ -409,7 +488,30 @@ fn draw(
 			}
 		}
 
-		ctx.move_to(store.border_left + 265.0, font_offset_y);
+		if entry.diagnostic_id.is_some() {
+			//Gutter marker for entries with parse diagnostics, left of the anchor arrow
+			ctx.save().unwrap();
+			ctx.set_source_rgb(1.0, 0.55, 0.0); //Orange
+			ctx.arc(
+				store.border_left - 25.0,
+				store.border_top + store.line_spacing * (i as f64 + 0.5),
+				3.0,
+				0.0,
+				2.0 * std::f64::consts::PI,
+			);
+			ctx.fill().unwrap();
+			ctx.restore().unwrap();
+		}
+
+		let mut message_x = store.border_left + 265.0;
+		if store.error_code_column {
+			if let Some(error_code) = entry.error_code {
+				ctx.move_to(message_x, font_offset_y);
+				ctx.show_text(&error_code.to_string()).unwrap();
+			}
+			message_x += 90.0;
+		}
+		ctx.move_to(message_x, font_offset_y);
 
 		/*let font_face = ctx.get_font_face();
 		let new_font_face = cairo::FontFace::toy_create("cairo :monospace", font_face.toy_get_slant(), font_face.toy_get_weight());
@@ -417,6 +519,13 @@ fn draw(
 
 		ctx.show_text(&entry.message).unwrap();
 
+		if let Some(error_code_info) = store.error_code_info(entry) {
+			//Title of the error code after the message, details are in the tooltip
+			ctx.rel_move_to(store.font_size, 0.0);
+			ctx.set_source_rgb(0.2, 0.4, 0.7); //Muted blue
+			ctx.show_text(&format!("[{}]", error_code_info.title)).unwrap();
+		}
+
 		/*if let Some(source_name) = store.log_sources.get(&entry.source_id) {
 			ctx.move_to(store.border_left + 210.0, font_offset_y + store.font_size);
 			ctx.set_font_size(f64::round(store.font_size * 0.7));
@@ -661,6 +770,25 @@ fn handle_evt_motion(
 				//TODO: This is not perfect... Needs hover to update, even if log store becomes empty:
 				timediff_entry.set_text("+0D 00:00:00.000");
 			}
+			//Error code description and custom fields of the entry
+			let tooltip = current_hover
+				.and_then(|line| store.rel_to_abs_offset(line))
+				.and_then(|offset| {
+					let entry = &store.store[offset];
+					let parts: Vec<String> = store
+						.error_code_info(entry)
+						.map(|error_code_info| error_code_info.tooltip())
+						.into_iter()
+						.chain(entry.custom_fields_tooltip())
+						.collect();
+					if parts.is_empty() {
+						None
+					} else {
+						Some(parts.join("\n\n"))
+					}
+				});
+			drawing_area.set_tooltip_text(tooltip.as_deref());
+
 			//log::info!("Hover change: {:?}, {:?}", current_hover, store.hover_line);
 			store.hover_line = current_hover;
 			drawing_area.queue_draw();
@@ -675,28 +803,179 @@ const PREFERRED_FONTS: [&str; 2] = [
 	"DejaVu Sans Mono", // Generally available on Linux
 ];
 
+//How often the file is checked for new entries in follow mode
+const FOLLOW_POLL_INTERVAL_MS: u64 = 500;
+//How often the progress dialog is updated while parsing
+const PROGRESS_POLL_INTERVAL_MS: u64 = 100;
+
+type ParseResult =
+	Result<(model::LogSource, Option<parse::follow::Follower>), parse::io::LogParseError>;
+
 //--------------------------------------------------------------------------------------------------
 //--------------------------------------------------------------------------------------------------
 //--------------------------------------------------------------------------------------------------
 
-fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
-	log::info!("File paths: {:?}", file_paths);
-	let file_path = if !file_paths.is_empty() {
-		if file_paths.len() > 1 {
-			log::warn!("Multiple files opened, ignoring all but the first one.");
-		}
-		Some(&file_paths[0])
-	} else {
-		None
+// Parses the files on a worker thread while a progress dialog is shown, then opens a
+// window with the result. `replaced_window` is closed once the new window is open,
+// it stays open if parsing is cancelled.
+fn build_ui(
+	application: &gtk::Application,
+	file_paths: &[std::path::PathBuf],
+	view_state: Option<ViewState>,
+	replaced_window: Option<gtk::ApplicationWindow>,
+) {
+	//Loaded once for all archives that are opened
+	let sfile_rules = Arc::new(parse::sfile_rules::SfileRules::load());
+	if file_paths.is_empty() {
+		build_window(application, file_paths, None, &sfile_rules, view_state);
+		return;
+	}
+
+	let dialog = gtk::Window::new(gtk::WindowType::Toplevel);
+	//Keeps the application running while no other window is open
+	dialog.set_application(Some(application));
+	dialog.set_title("Opening logs - Sherlog");
+	dialog.set_transient_for(replaced_window.as_ref());
+	dialog.set_modal(replaced_window.is_some());
+	dialog.set_position(gtk::WindowPosition::Center);
+	dialog.set_default_size(400, -1);
+	dialog.set_border_width(10);
+	let current_label = gtk::Label::new(None);
+	current_label.set_xalign(0.0);
+	current_label.set_ellipsize(gtk::pango::EllipsizeMode::Middle);
+	let progress_bar = gtk::ProgressBar::new();
+	progress_bar.set_show_text(true);
+	let cancel_button = gtk::Button::with_label("Cancel");
+	cancel_button.set_halign(gtk::Align::End);
+	let dialog_box = gtk::Box::new(Orientation::Vertical, 10);
+	dialog_box.pack_start(&current_label, false, false, 0);
+	dialog_box.pack_start(&progress_bar, false, false, 0);
+	dialog_box.pack_start(&cancel_button, false, false, 0);
+	dialog.add(&dialog_box);
+	dialog.show_all();
+
+	let progress = Arc::new(parse::progress::Progress::new());
+	let finished = Rc::new(std::cell::Cell::new(false));
+	{
+		let progress = progress.clone();
+		cancel_button.connect_clicked(move |button| {
+			progress.cancel();
+			button.set_sensitive(false);
+		});
+	}
+	{
+		let progress = progress.clone();
+		let finished = finished.clone();
+		dialog.connect_delete_event(move |_, _| {
+			if finished.get() {
+				return gtk::Inhibit(false);
+			}
+			//Closed by the user, the dialog goes away once the parser has stopped
+			progress.cancel();
+			gtk::Inhibit(true)
+		});
+	}
+
+	let (sender, receiver) = std::sync::mpsc::channel::<ParseResult>();
+	{
+		let progress = progress.clone();
+		let file_paths = file_paths.to_vec();
+		let sfile_rules = sfile_rules.clone();
+		std::thread::spawn(move || {
+			log::info!("File paths: {:?}", file_paths);
+			let now = Instant::now();
+			let result = parse::from_paths_follow(&file_paths, &sfile_rules, &progress);
+			let elapsed = now.elapsed();
+			log::info!(
+				"Time to parse files: {}ms",
+				elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64
+			);
+			//Only fails if the application was closed meanwhile
+			let _ = sender.send(result);
+		});
+	}
+
+	let application = application.clone();
+	let file_paths = file_paths.to_vec();
+	let mut view_state = view_state;
+	glib::timeout_add_local(
+		std::time::Duration::from_millis(PROGRESS_POLL_INTERVAL_MS),
+		move || {
+			let result = match receiver.try_recv() {
+				Ok(result) => result,
+				Err(std::sync::mpsc::TryRecvError::Empty) => {
+					progress_bar.set_fraction(progress.fraction());
+					current_label.set_text(&progress.current());
+					return glib::Continue(true);
+				}
+				Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+					log::error!("Parser thread stopped without a result");
+					Err(parse::io::LogParseError::IoError(std::io::Error::other(
+						"The parser stopped unexpectedly",
+					)))
+				}
+			};
+			if let Err(parse::io::LogParseError::InvalidPassword(path)) = &result {
+				if let Some(password) = password_dialog(&dialog, path) {
+					parse::sfile::add_password(&password);
+					//Parses again, the new password is tried along with the known ones
+					build_ui(
+						&application,
+						&file_paths,
+						view_state.take(),
+						replaced_window.clone(),
+					);
+					finished.set(true);
+					dialog.close();
+					return glib::Continue(false);
+				}
+			}
+			match result {
+				Err(parse::io::LogParseError::Cancelled) => {
+					log::info!("Parsing cancelled");
+					if replaced_window.is_none() {
+						build_window(&application, &[], None, &sfile_rules, None);
+					}
+				}
+				result => {
+					build_window(
+						&application,
+						&file_paths,
+						Some(result),
+						&sfile_rules,
+						view_state.take(),
+					);
+					if let Some(replaced_window) = &replaced_window {
+						replaced_window.close();
+					}
+				}
+			}
+			finished.set(true);
+			dialog.close();
+			glib::Continue(false)
+		},
+	);
+}
+
+// Opens a window with the parsed files. Without files, the user is asked for some.
+fn build_window(
+	application: &gtk::Application,
+	file_paths: &[std::path::PathBuf],
+	parse_result: Option<ParseResult>,
+	sfile_rules: &parse::sfile_rules::SfileRules,
+	view_state: Option<ViewState>,
+) {
+	let window_title = match file_paths {
+		[] => std::borrow::Cow::Borrowed("(No file)"),
+		[p] => p.file_name().unwrap_or(p.as_os_str()).to_string_lossy(),
+		_ => std::borrow::Cow::Owned(format!("{} files", file_paths.len())),
 	};
 
 	let window = gtk::ApplicationWindow::new(application);
 	//window.set_icon_from_file("../images/sherlog_icon.png");
 	window.set_title(&format!(
 		"{} - Sherlog v{}",
-		file_path
-			.map(|p| p.file_name().unwrap_or(p.as_os_str()).to_string_lossy())
-			.unwrap_or(std::borrow::Cow::Borrowed("(No file)")),
+		window_title,
 		env!("CARGO_PKG_VERSION")
 	));
 	window.set_border_width(10);
@@ -704,9 +983,10 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	window.set_default_size(800, 600);
 	window.maximize();
 
-	if file_path.is_none() {
-		if let Some(path) = open_file_dialog(&window) {
-			build_ui(application, &[path]);
+	if file_paths.is_empty() {
+		let paths = open_file_dialog(&window, gtk::FileChooserAction::Open);
+		if !paths.is_empty() {
+			build_ui(application, &paths, None, None);
 			window.close();
 			return;
 		}
@@ -788,30 +1068,37 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	let log_source_ex = model::LogSource {
 		name: "example".to_string(),
 		children: { model::LogSourceContents::Entries(log_entries) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex2_1 = model::LogSource {
 		name: "example2_1".to_string(),
 		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex2 = model::LogSource {
 		name: "example2".to_string(),
 		children: { model::LogSourceContents::Sources(vec![log_source_ex2_1]) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex3 = model::LogSource {
 		name: "example3".to_string(),
 		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex4_1 = model::LogSource {
 		name: "example4_1".to_string(),
 		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex4_2 = model::LogSource {
 		name: "example4_2".to_string(),
 		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
 	};
 	let log_source_ex4 = model::LogSource {
 		name: "example4".to_string(),
 		children: { model::LogSourceContents::Sources(vec![log_source_ex4_1, log_source_ex4_2]) },
+		diagnostics: Vec::new(),
 	};
 
 	let log_source_root = model::LogSource {
@@ -824,23 +1111,20 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 				log_source_ex4,
 			])
 		},
+		diagnostics: Vec::new(),
 	};
 
 	//---------------------------------------------------------------------------------------
 
 	let mut dialog_vec: Vec<gtk::MessageDialog> = Vec::<gtk::MessageDialog>::new();
 
-	let log_source_root = if let Some(file_path) = file_path {
-		let now = Instant::now();
-		let root = parse::from_file(file_path);
-		let elapsed = now.elapsed();
-		log::info!(
-			"Time to parse file: {}ms",
-			elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64
-		);
-
-		match root {
-			Ok(root) => root,
+	let mut follower = None;
+	let log_source_root = if let Some(parse_result) = parse_result {
+		match parse_result {
+			Ok((root, root_follower)) => {
+				follower = root_follower;
+				root
+			}
 			Err(err) => {
 				let error_str = format!("Error: {}", err);
 				dialog_vec.push(gtk::MessageDialog::new(
@@ -872,6 +1156,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		show_info: true,
 		show_dbg: true,
 		show_trace: true,
+		hidden_source_severities: std::collections::HashSet::new(),
 
 		selected_single: std::collections::HashSet::new(),
 		excluded_single: std::collections::HashSet::new(),
@@ -915,9 +1200,11 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 			scroll_perc: 0.0, //calculate dynamically
 		},
 		tz_offset: chrono::Duration::zero(),
-		sensor_shift: chrono::Duration::zero(),
-		log_sources_to_shift: Vec::new(),
+		sensor_shifts: Vec::new(),
 		sort_mode: SortMode::Timestamp,
+		error_code_filter: None,
+		error_code_column: false,
+		error_codes: error_codes::ErrorCodeDictionary::load(),
 	};
 
 	let store_rc = Rc::new(RefCell::new(store));
@@ -928,6 +1215,90 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 
 	let mut log_source_root_ext = LogSourceExt::from_source(log_source_root);
 
+	// Diagnostics panel below the log view ----------------------------------------------
+
+	let mut diagnostics = Vec::new();
+	log_source_root_ext.take_diagnostics("", &mut diagnostics);
+	let diagnostics_store = ListStore::new(&[
+		String::static_type(),
+		String::static_type(),
+		String::static_type(),
+		String::static_type(),
+		glib::Type::I64,
+		String::static_type(),
+	]);
+	append_diagnostics(&diagnostics_store, &diagnostics);
+	let diagnostics_expander = gtk::Expander::new(Some(&diagnostics_title(diagnostics.len())));
+	let diagnostics_rc = Rc::new(RefCell::new(diagnostics));
+	{
+		let diagnostics_tree_view = gtk::TreeView::with_model(&diagnostics_store);
+		diagnostics_tree_view.set_headers_visible(true);
+		diagnostics_tree_view.set_tooltip_column(DiagnosticsColumns::File as i32);
+		for (title, column_id) in [
+			("Log source", DiagnosticsColumns::Source),
+			("Location", DiagnosticsColumns::Location),
+			("Kind", DiagnosticsColumns::Kind),
+			("Snippet", DiagnosticsColumns::Snippet),
+		] {
+			let column = gtk::TreeViewColumn::new();
+			column.set_title(title);
+			column.set_resizable(true);
+			let renderer_text = CellRendererText::new();
+			gtk::prelude::TreeViewColumnExt::pack_start(&column, &renderer_text, true);
+			gtk::prelude::TreeViewColumnExt::add_attribute(
+				&column,
+				&renderer_text,
+				"text",
+				column_id as i32,
+			);
+			diagnostics_tree_view.append_column(&column);
+		}
+
+		//Activating a row jumps to the entry of the diagnostic
+		let drawing_area_clone = drawing_area.clone();
+		let store_rc_clone = store_rc.clone();
+		let window_clone = window.clone();
+		diagnostics_tree_view.connect_row_activated(move |tree_view, path, _column| {
+			let model = match tree_view.model() {
+				Some(model) => model,
+				None => return,
+			};
+			if let Some(iter) = model.iter(path) {
+				let diagnostic_id = model
+					.value(&iter, DiagnosticsColumns::DiagnosticId as i32)
+					.get::<i64>()
+					.unwrap();
+				if diagnostic_id >= 0 {
+					let visible = ui_actions::jump_to_diagnostic(
+						&mut store_rc_clone.borrow_mut(),
+						diagnostic_id as u32,
+						&drawing_area_clone,
+					);
+					if !visible {
+						let dialog = gtk::MessageDialog::new(
+							Some(&window_clone),
+							gtk::DialogFlags::MODAL,
+							gtk::MessageType::Info,
+							gtk::ButtonsType::Ok,
+							"The entry of this diagnostic is hidden by the current filters. \
+							It is selected and anchored, and shows up once the filters allow it.",
+						);
+						dialog.run();
+						dialog.emit_close();
+					}
+				}
+			}
+		});
+
+		let scrolled_window_diagnostics =
+			gtk::ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+		scrolled_window_diagnostics
+			.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+		scrolled_window_diagnostics.set_min_content_height(150);
+		scrolled_window_diagnostics.add(&diagnostics_tree_view);
+		diagnostics_expander.add(&scrolled_window_diagnostics);
+	}
+
 	// left pane
 	let left_store = TreeStore::new(&[
 		glib::Type::BOOL,
@@ -1088,13 +1459,28 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		let window_clone = window.clone();
 		let app_clone = application.clone();
 		open_file_menu_item.connect_activate(move |_| {
-			if let Some(path) = open_file_dialog(&window_clone) {
-				build_ui(&app_clone, &[path]);
-				window_clone.close();
+			let paths = open_file_dialog(&window_clone, gtk::FileChooserAction::Open);
+			if !paths.is_empty() {
+				build_ui(&app_clone, &paths, None, Some(window_clone.clone()));
 			}
 		});
 	}
 	menu_bar.append(&open_file_menu_item);
+	let open_folder_menu_item = gtk::MenuItem::with_label("Open Folder...");
+	{
+		let window_clone = window.clone();
+		let app_clone = application.clone();
+		open_folder_menu_item.connect_activate(move |_| {
+			let paths = open_file_dialog(&window_clone, gtk::FileChooserAction::SelectFolder);
+			if !paths.is_empty() {
+				build_ui(&app_clone, &paths, None, Some(window_clone.clone()));
+			}
+		});
+	}
+	menu_bar.append(&open_folder_menu_item);
+	let reload_menu_item = gtk::MenuItem::with_label("Reload (F5)");
+	reload_menu_item.set_sensitive(!file_paths.is_empty());
+	menu_bar.append(&reload_menu_item);
 	root_box.pack_start(&menu_bar, false, false, 0);
 
 	let scrolled_window_left =
@@ -1113,8 +1499,10 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		drawing_area: &gtk::DrawingArea,
 	) {
 		log::info!("Active: {} ({:?})", w.is_active(), severity);
+		store.set_severity_shown(severity, w.is_active());
+		//Entries with a severity as logged only follow their "Logged as" toggle
 		store.filter_store(
-			&|entry: &LogEntryExt| entry.severity == severity,
+			&|entry: &LogEntryExt| entry.severity == severity && entry.source_severity.is_none(),
 			w.is_active(),
 			crate::model_internal::VISIBLE_OFF_SEVERITY,
 		);
@@ -1122,6 +1510,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	}
 
 	split_pane_left.pack_start(&scrolled_window_left, true, true, 0);
+	let mut severity_buttons = Vec::<(model::LogLevel, gtk::CheckButton)>::new();
 	{
 		let severity_box = gtk::Box::new(Orientation::Horizontal, 6);
 		severity_box.set_margin_end(10);
@@ -1141,6 +1530,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Critical, check_btn));
 		let check_btn = gtk::CheckButton::with_label("Error");
 		check_btn.set_active(true);
 
@@ -1156,6 +1546,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Error, check_btn));
 		let check_btn = gtk::CheckButton::with_label("Warn");
 		check_btn.set_active(true);
 
@@ -1171,6 +1562,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Warning, check_btn));
 		let check_btn = gtk::CheckButton::with_label("Info");
 		check_btn.set_active(true);
 
@@ -1186,6 +1578,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Info, check_btn));
 		let check_btn = gtk::CheckButton::with_label("Dbg");
 		check_btn.set_active(true);
 
@@ -1201,6 +1594,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Debug, check_btn));
 		let check_btn = gtk::CheckButton::with_label("Trace");
 		check_btn.set_active(true);
 
@@ -1216,9 +1610,129 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 
 		severity_box.pack_start(&check_btn, false, false, 0);
+		severity_buttons.push((model::LogLevel::Trace, check_btn));
 		split_pane_left.pack_start(&severity_box, false, false, 0);
 	}
 
+	fn source_severity_toggle(
+		w: &gtk::CheckButton,
+		store: &mut LogStoreLinear,
+		source_severity: model::SourceSeverity,
+		drawing_area: &gtk::DrawingArea,
+	) {
+		log::info!("Active: {} ({:?})", w.is_active(), source_severity);
+		if w.is_active() {
+			store.hidden_source_severities.remove(&source_severity);
+		} else {
+			store.hidden_source_severities.insert(source_severity);
+		}
+		store.filter_store(
+			&|entry: &LogEntryExt| entry.source_severity == Some(source_severity),
+			w.is_active(),
+			crate::model_internal::VISIBLE_OFF_SOURCE_SEVERITY,
+		);
+		drawing_area.queue_draw();
+	}
+
+	//Severities as logged, only shown once the store is known to have any (see below)
+	let mut source_severity_buttons = Vec::<(model::SourceSeverity, gtk::CheckButton)>::new();
+	let source_severity_box = gtk::Box::new(Orientation::Horizontal, 6);
+	{
+		source_severity_box.set_margin_end(10);
+		source_severity_box.set_no_show_all(true);
+		let label = gtk::Label::new(Some("Logged as:"));
+		label.show();
+		source_severity_box.pack_start(&label, false, false, 0);
+		for source_severity in [
+			model::SourceSeverity::Hardware,
+			model::SourceSeverity::Unspecified,
+			model::SourceSeverity::AppStart,
+			model::SourceSeverity::AppStop,
+			model::SourceSeverity::Exception,
+		] {
+			let check_btn = gtk::CheckButton::with_label(&source_severity.to_string());
+			check_btn.set_active(true);
+			check_btn.show();
+
+			let store_rc_clone = store_rc.clone();
+			let drawing_area_clone = drawing_area.clone();
+			check_btn.connect_clicked(move |w| {
+				source_severity_toggle(
+					w,
+					&mut store_rc_clone.borrow_mut(),
+					source_severity,
+					&drawing_area_clone,
+				);
+			});
+
+			source_severity_box.pack_start(&check_btn, false, false, 0);
+			source_severity_buttons.push((source_severity, check_btn));
+		}
+		split_pane_left.pack_start(&source_severity_box, false, false, 0);
+	}
+
+	//Error codes with their number of entries, only shown if the store has any (see below)
+	let error_codes_store = ListStore::new(&[
+		String::static_type(),
+		glib::Type::U64,
+		glib::Type::I64,
+	]);
+	let error_codes_expander = gtk::Expander::new(Some("Error codes"));
+	{
+		let error_codes_tree_view = gtk::TreeView::with_model(&error_codes_store);
+		error_codes_tree_view.set_headers_visible(true);
+		for (title, column_id) in [
+			("Error code", ErrorCodesColumns::Text),
+			("Entries", ErrorCodesColumns::Count),
+		] {
+			let column = gtk::TreeViewColumn::new();
+			column.set_title(title);
+			let renderer_text = CellRendererText::new();
+			gtk::prelude::CellRendererExt::set_alignment(&renderer_text, 0.0, 0.0);
+			gtk::prelude::TreeViewColumnExt::pack_start(&column, &renderer_text, false);
+			gtk::prelude::TreeViewColumnExt::add_attribute(
+				&column,
+				&renderer_text,
+				"text",
+				column_id as i32,
+			);
+			error_codes_tree_view.append_column(&column);
+		}
+		error_codes_tree_view.set_property("activate-on-single-click", true);
+
+		let drawing_area_clone = drawing_area.clone();
+		let store_rc_clone = store_rc.clone();
+		error_codes_tree_view.connect_row_activated(move |tree_view, path, _column| {
+			let model = match tree_view.model() {
+				Some(model) => model,
+				None => return,
+			};
+			if let Some(iter) = model.iter(path) {
+				let error_code = model
+					.value(&iter, ErrorCodesColumns::ErrorCode as i32)
+					.get::<i64>()
+					.unwrap();
+				ui_actions::error_code_filter_changed(
+					&mut store_rc_clone.borrow_mut(),
+					u32::try_from(error_code).ok(),
+					&drawing_area_clone,
+				);
+			}
+		});
+
+		let scrolled_window_error_codes =
+			gtk::ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+		scrolled_window_error_codes.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+		scrolled_window_error_codes.set_min_content_height(120);
+		scrolled_window_error_codes.add(&error_codes_tree_view);
+		error_codes_expander.add(&scrolled_window_error_codes);
+		error_codes_expander.set_margin_end(10);
+		error_codes_expander.set_no_show_all(true);
+		error_codes_expander.set_expanded(true);
+		scrolled_window_error_codes.show_all();
+		split_pane_left.pack_start(&error_codes_expander, false, false, 0);
+	}
+
 	let search_entry = gtk::SearchEntry::new();
 	search_entry.set_margin_end(10);
 	let case_sensitive_search = gtk::CheckButton::with_label("Case sensitive");
@@ -1284,41 +1798,78 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 
 	split_pane.pack1(&split_pane_left, false, false);
 
-	// Initialize log sources to shift
-    log_source_root_ext.collect_descendant_ids_of_filtered_roots(
-        &|src| matches!(src.name.as_str(), "Controller" | "Sensor" | "Probe" | "Connect Box"),
-        &mut store_rc.borrow_mut().log_sources_to_shift
-    );
-
-	
+	// Initialize log sources to shift, separately for every sfile
+	let mut shift_groups = Vec::new();
+	log_source_root_ext.collect_descendant_ids_per_parent(
+		&|src| {
+			sfile_rules
+				.groups
+				.iter()
+				.any(|group| group.time_shift && group.name == src.name)
+		},
+		&mut shift_groups,
+	);
+	if shift_groups.is_empty() {
+		shift_groups.push((log_source_root_ext.name.clone(), Vec::new()));
+	}
+	let multiple_archives = shift_groups.len() > 1;
+	store_rc.borrow_mut().sensor_shifts = shift_groups
+		.into_iter()
+		.map(|(archive_name, log_sources_to_shift)| SensorShift {
+			archive_name,
+			sensor_shift: chrono::Duration::zero(),
+			log_sources_to_shift,
+		})
+		.collect();
 
-	let timeshift_entry = gtk::Entry::new();
-	timeshift_entry.set_editable(true);
-	timeshift_entry.set_alignment(1.0); //1.0 is right-aligned
-	timeshift_entry.set_text("+0D 00:00:00.000");
-	timeshift_entry.set_tooltip_text(Some("Shifts the timestamps of Sensor and Controller log entries."));
-	let store_rc_clone = store_rc.clone();
-	let drawing_area_clone = drawing_area.clone();
+	let archive_names: Vec<String> = store_rc
+		.borrow()
+		.sensor_shifts
+		.iter()
+		.map(|shift| shift.archive_name.clone())
+		.collect();
+	let mut timeshift_entries = Vec::<(String, gtk::Entry)>::new();
+	for (shift_idx, archive_name) in archive_names.into_iter().enumerate() {
+		let timeshift_entry = gtk::Entry::new();
+		timeshift_entry.set_editable(true);
+		timeshift_entry.set_alignment(1.0); //1.0 is right-aligned
+		timeshift_entry.set_text("+0D 00:00:00.000");
+		timeshift_entry.set_tooltip_text(Some(&format!(
+			"Shifts the timestamps of Sensor and Controller log entries of {}.",
+			archive_name
+		)));
+		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
 
-	timeshift_entry.connect_activate({
-        let store_rc_clone = store_rc_clone.clone();
-        let drawing_area_clone = drawing_area_clone.clone();
-        move |entry| {
-            ui_actions::timeshift_changed(
-                entry,
-                &mut store_rc_clone.borrow_mut(),
-                &drawing_area_clone,
-            );
-        }
-    });
-	let timeshift_label = gtk::Label::new(Some("Sensor time shift:"));
-	timeshift_label.set_xalign(1.0); // right align label text
-	timeshift_label.set_size_request(120, -1);
-	let timeshift_box = gtk::Box::new(Orientation::Horizontal, 4);
-	timeshift_box.pack_start(&timeshift_label, false, false, 0);
-	timeshift_box.pack_start(&timeshift_entry, true, true, 0);
-	timeshift_box.set_margin_end(10);
-	split_pane_left.pack_start(&timeshift_box, false, false, 0);
+		timeshift_entry.connect_activate({
+			let store_rc_clone = store_rc_clone.clone();
+			let drawing_area_clone = drawing_area_clone.clone();
+			move |entry| {
+				ui_actions::timeshift_changed(
+					entry,
+					&mut store_rc_clone.borrow_mut(),
+					shift_idx,
+					&drawing_area_clone,
+				);
+			}
+		});
+		let timeshift_label = if multiple_archives {
+			let label = gtk::Label::new(Some(&format!("{}:", archive_name)));
+			label.set_ellipsize(gtk::pango::EllipsizeMode::Middle);
+			label.set_tooltip_text(Some(&format!("Sensor time shift of {}", archive_name)));
+			label
+		} else {
+			gtk::Label::new(Some("Sensor time shift:"))
+		};
+		timeshift_label.set_xalign(1.0); // right align label text
+		timeshift_label.set_size_request(120, -1);
+		let timeshift_box = gtk::Box::new(Orientation::Horizontal, 4);
+		timeshift_box.pack_start(&timeshift_label, false, false, 0);
+		timeshift_box.pack_start(&timeshift_entry, true, true, 0);
+		timeshift_box.set_margin_end(10);
+		split_pane_left.pack_start(&timeshift_box, false, false, 0);
+		timeshift_entries.push((archive_name, timeshift_entry));
+	}
 	
 	let sort_box = gtk::Box::new(Orientation::Horizontal, 4);
 	let sort_label = gtk::Label::new(Some("Sort by:"));
@@ -1415,6 +1966,21 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		String::new(),
 	);
 
+	source_severity_box.set_visible(
+		store_rc
+			.borrow()
+			.store
+			.iter()
+			.any(|entry| entry.source_severity.is_some()),
+	);
+
+	{
+		let mut store = store_rc.borrow_mut();
+		store.error_code_column = store.store.iter().any(|entry| entry.error_code.is_some());
+		fill_error_codes(&error_codes_store, &store);
+		error_codes_expander.set_visible(store.error_code_column);
+	}
+
 	store_rc.borrow_mut().sort_entries();
 	store_rc.borrow_mut().filter_store(
 		&|_entry: &LogEntryExt| true,
@@ -1429,6 +1995,196 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 	);
 	log::info!("after build_log_store");
 
+	// Follow mode: parse entries appended to the file ----------------------------------
+
+	fn hidden_source_ids(tree_store: &TreeStore) -> std::collections::HashSet<u32> {
+		let mut ids = std::collections::HashSet::new();
+		tree_store.foreach(|model, _path, iter| {
+			let active = model
+				.value(iter, LogSourcesColumns::Active as i32)
+				.get::<bool>()
+				.unwrap();
+			let inconsistent = model
+				.value(iter, LogSourcesColumns::Inconsistent as i32)
+				.get::<bool>()
+				.unwrap();
+			if !active && !inconsistent {
+				ids.insert(
+					model
+						.value(iter, LogSourcesColumns::Id as i32)
+						.get::<u32>()
+						.unwrap(),
+				);
+			}
+			false //Continue iterating
+		});
+		ids
+	}
+
+	if let Some(mut follower) = follower {
+		let follow_check = gtk::CheckButton::with_label("Follow file");
+		follow_check.set_active(true);
+		follow_check.set_tooltip_text(Some(&format!(
+			"Shows entries appended to {} while it is open.",
+			follower.name()
+		)));
+		let autoscroll_check = gtk::CheckButton::with_label("Auto-scroll to end");
+		autoscroll_check.set_active(true);
+		let follow_box = gtk::Box::new(Orientation::Horizontal, 4);
+		follow_box.pack_end(&autoscroll_check, false, false, 0);
+		follow_box.pack_end(&follow_check, false, false, 0);
+		follow_box.set_margin_end(10);
+		split_pane_left.pack_start(&follow_box, false, false, 0);
+
+		//Reverse lookup of `build_log_sources`: full log source name to ID
+		let source_ids: std::collections::HashMap<String, u32> = store_rc
+			.borrow()
+			.log_sources
+			.iter()
+			.map(|(id, name)| (name.clone(), *id))
+			.collect();
+		let root_id = log_source_root_ext.id;
+
+		let window_closed = Rc::new(std::cell::Cell::new(false));
+		{
+			let window_closed = window_closed.clone();
+			window.connect_destroy(move |_| window_closed.set(true));
+		}
+
+		let store_rc_clone = store_rc.clone();
+		let drawing_area_clone = drawing_area.clone();
+		let left_store_clone = left_store.clone();
+		let search_entry_clone = search_entry.clone();
+		let case_sensitive_search_clone = case_sensitive_search.clone();
+		let search_enable_clone = search_enable.clone();
+		let diagnostics_store_clone = diagnostics_store.clone();
+		let diagnostics_expander_clone = diagnostics_expander.clone();
+		let diagnostics_rc_clone = diagnostics_rc.clone();
+		let error_codes_store_clone = error_codes_store.clone();
+		let error_codes_expander_clone = error_codes_expander.clone();
+		glib::timeout_add_local(
+			std::time::Duration::from_millis(FOLLOW_POLL_INTERVAL_MS),
+			move || {
+				if window_closed.get() {
+					return glib::Continue(false);
+				}
+				if !follow_check.is_active() {
+					return glib::Continue(true);
+				}
+				let log_source = match follower.poll() {
+					Ok(Some(log_source)) => log_source,
+					Ok(None) => return glib::Continue(true),
+					Err(err) => {
+						//TODO: Notify of follow errors?
+						log::warn!("Follow mode: cannot read {}: {}", follower.name(), err);
+						return glib::Continue(true);
+					}
+				};
+
+				let mut log_source_ext = LogSourceExt::from_source(log_source);
+				{
+					//Appended, so the IDs of the new entries do not clash with the old ones
+					let mut diagnostics = diagnostics_rc_clone.borrow_mut();
+					let old_len = diagnostics.len();
+					log_source_ext.take_diagnostics("", &mut diagnostics);
+					if diagnostics.len() > old_len {
+						append_diagnostics(&diagnostics_store_clone, &diagnostics[old_len..]);
+						diagnostics_expander_clone
+							.set_label(Some(&diagnostics_title(diagnostics.len())));
+					}
+				}
+
+				let mut entries = Vec::new();
+				log_source_ext.take_entries_by_path(
+					"",
+					&source_ids,
+					root_id,
+					&mut entries,
+				);
+				if entries.is_empty() {
+					return glib::Continue(true);
+				}
+				log::info!("Follow mode: {} new entries", entries.len());
+
+				let hidden_sources = hidden_source_ids(&left_store_clone);
+				let mut store = store_rc_clone.borrow_mut();
+				store.append_entries(entries, &|id| hidden_sources.contains(&id));
+				if store.error_code_column {
+					fill_error_codes(&error_codes_store_clone, &store);
+					error_codes_expander_clone.set_visible(true);
+				}
+				ui_actions::resort_store_preserve_state(&mut store);
+				//Applies the search filter to the new entries
+				ui_actions::search_changed(
+					&search_entry_clone,
+					&case_sensitive_search_clone,
+					&search_enable_clone,
+					&mut store,
+					&drawing_area_clone,
+				);
+				if autoscroll_check.is_active() {
+					let (entry_count, visible_lines) = (store.entry_count, store.visible_lines);
+					store.scroll(entry_count as i64, visible_lines);
+				}
+				drawing_area_clone.queue_draw();
+				glib::Continue(true)
+			},
+		);
+	}
+
+	// Reload: parse the files again and reapply the view state ----------------------
+
+	fn capture_view_state(
+		store: &LogStoreLinear,
+		tree_store: &TreeStore,
+		search_entry: &gtk::SearchEntry,
+		case_sensitive_search: &gtk::CheckButton,
+		search_enable: &gtk::CheckButton,
+	) -> ViewState {
+		let mut view_state = ViewState::from_store(store);
+		view_state.hidden_sources = hidden_source_ids(tree_store)
+			.iter()
+			.filter_map(|id| store.log_sources.get(id).cloned())
+			.collect();
+		view_state.search_text = search_entry.text().to_string();
+		view_state.search_case_sensitive = case_sensitive_search.is_active();
+		view_state.search_enabled = search_enable.is_active();
+		view_state
+	}
+
+	let reload: Rc<dyn Fn()> = {
+		let window_clone = window.clone();
+		let app_clone = application.clone();
+		let file_paths = file_paths.to_vec();
+		let store_rc_clone = store_rc.clone();
+		let left_store_clone = left_store.clone();
+		let search_entry_clone = search_entry.clone();
+		let case_sensitive_search_clone = case_sensitive_search.clone();
+		let search_enable_clone = search_enable.clone();
+		Rc::new(move || {
+			if file_paths.is_empty() {
+				return;
+			}
+			let view_state = capture_view_state(
+				&store_rc_clone.borrow(),
+				&left_store_clone,
+				&search_entry_clone,
+				&case_sensitive_search_clone,
+				&search_enable_clone,
+			);
+			build_ui(
+				&app_clone,
+				&file_paths,
+				Some(view_state),
+				Some(window_clone.clone()),
+			);
+		})
+	};
+	{
+		let reload = reload.clone();
+		reload_menu_item.connect_activate(move |_| reload());
+	}
+
 	//-------------------------------------------------------------------------------
 
 	let event_mask = EventMask::POINTER_MOTION_MASK
@@ -1484,7 +2240,10 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		)
 	});
 
-	split_pane.pack2(&drawing_area, true, false);
+	let log_view_box = gtk::Box::new(Orientation::Vertical, 0);
+	log_view_box.pack_start(&drawing_area, true, true, 0);
+	log_view_box.pack_start(&diagnostics_expander, false, false, 0);
+	split_pane.pack2(&log_view_box, true, false);
 
 	//https://gtk-rs.org/docs/gdk/enums/key/index.html
 	//log::info!("CODES: {} {} {} {}", gdk::keys::constants::Control_L, gdk::keys::constants::Control_R, gdk::keys::constants::Shift_L, gdk::keys::constants::Shift_R);
@@ -1498,6 +2257,10 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 				event_key.keyval(),
 				event_key.hardware_keycode()
 			);
+			if event_key.keyval() == gdk::keys::constants::F5 {
+				reload();
+				return gtk::Inhibit(true);
+			}
 			if event_key.keyval() == gdk::keys::constants::Control_L
 				|| event_key.keyval() == gdk::keys::constants::Control_R
 			{
@@ -1537,7 +2300,7 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 							// Use the same display timezone offset as on screen.
 							(entry.timestamp + store_rc_clone.borrow().tz_offset)
 								.format("%d-%m-%y %T%.6f"),
-							ui_formatting::short_severity(&entry.severity),
+							ui_formatting::short_entry_severity(entry),
 							entry.message
 						).unwrap();
 					}
@@ -1570,12 +2333,90 @@ fn build_ui(application: &gtk::Application, file_paths: &[std::path::PathBuf]) {
 		});
 	}
 
+	// Reapply the view state of the window that was reloaded
+	if let Some(view_state) = view_state {
+		if view_state.sort_mode == SortMode::SessionId {
+			sort_combo.set_active(Some(1)); //Emits changed, which sorts the store
+		}
+		for (shift_idx, (archive_name, timeshift_entry)) in timeshift_entries.iter().enumerate() {
+			if let Some(sensor_shift) = view_state.sensor_shifts.get(archive_name) {
+				if !sensor_shift.is_zero() {
+					timeshift_entry.set_text(&ui_formatting::format_duration(*sensor_shift));
+					ui_actions::timeshift_changed(
+						timeshift_entry,
+						&mut store_rc.borrow_mut(),
+						shift_idx,
+						&drawing_area,
+					);
+				}
+			}
+		}
+		for (severity, check_btn) in &severity_buttons {
+			if view_state.shown_severities.contains(&(*severity, false)) {
+				check_btn.set_active(false); //Emits clicked, which filters the store
+			}
+		}
+		for (source_severity, check_btn) in &source_severity_buttons {
+			if view_state.hidden_source_severities.contains(source_severity) {
+				check_btn.set_active(false); //Emits clicked, which filters the store
+			}
+		}
+		ui_actions::error_code_filter_changed(
+			&mut store_rc.borrow_mut(),
+			view_state.error_code_filter,
+			&drawing_area,
+		);
+
+		let mut paths_to_hide = Vec::new();
+		{
+			let store = store_rc.borrow();
+			left_store.foreach(|model, path, iter| {
+				let id = model
+					.value(iter, LogSourcesColumns::Id as i32)
+					.get::<u32>()
+					.unwrap();
+				if store
+					.log_sources
+					.get(&id)
+					.is_some_and(|name| view_state.hidden_sources.contains(name))
+				{
+					paths_to_hide.push(path.clone());
+				}
+				false //Continue iterating
+			});
+		}
+		for path in paths_to_hide {
+			//Children of a hidden parent were already hidden with it, skip them
+			let active = left_store
+				.value(&left_store.iter(&path).unwrap(), LogSourcesColumns::Active as i32)
+				.get::<bool>()
+				.unwrap();
+			if active {
+				toggle_row(&left_store, &mut store_rc.borrow_mut(), &drawing_area, path);
+			}
+		}
+
+		case_sensitive_search.set_active(view_state.search_case_sensitive);
+		search_enable.set_active(view_state.search_enabled);
+		search_entry.set_text(&view_state.search_text);
+		//search-changed is emitted with a delay, filter now to restore the anchor on the final view
+		ui_actions::search_changed(
+			&search_entry,
+			&case_sensitive_search,
+			&search_enable,
+			&mut store_rc.borrow_mut(),
+			&drawing_area,
+		);
+		view_state.restore_entries(&mut store_rc.borrow_mut());
+		drawing_area.queue_draw();
+	}
+
 	root_box.pack_start(&split_pane, true, true, 0);
 	window.set_focus_on_map(false);
 	window.add(&root_box);
 	window.show_all();
 
-	if file_path.is_some() {
+	if !file_paths.is_empty() {
 		window.set_urgency_hint(true);
 
 		// Re-trigger blinking 5 seconds after load if the window is still not focused.
@@ -1621,23 +2462,64 @@ fn set_wait_cursor(window: &impl glib::IsA<gtk::Window>) {
 	}
 }
 
-fn open_file_dialog(window: &gtk::ApplicationWindow) -> Option<std::path::PathBuf> {
-	let dialog = gtk::FileChooserDialog::new(
-		Some("Open log file"),
-		Some(window),
-		gtk::FileChooserAction::Open,
-	);
+fn open_file_dialog(
+	window: &gtk::ApplicationWindow,
+	action: gtk::FileChooserAction,
+) -> Vec<std::path::PathBuf> {
+	let title = if action == gtk::FileChooserAction::SelectFolder {
+		"Open log folder"
+	} else {
+		"Open log files"
+	};
+	let dialog = gtk::FileChooserDialog::new(Some(title), Some(window), action);
+	dialog.set_select_multiple(true);
 	dialog.add_button("Cancel", gtk::ResponseType::Cancel);
 	dialog.add_button("Open", gtk::ResponseType::Accept);
 	let response = dialog.run();
-	let path = if response == gtk::ResponseType::Accept {
+	let paths = if response == gtk::ResponseType::Accept {
 		set_wait_cursor(&dialog);
-		dialog.filename()
+		dialog.filenames()
 	} else {
-		None
+		Vec::new()
 	};
 	dialog.close();
-	path
+	paths
+}
+
+// Asks for the password of an encrypted sfile. Returns None if the user cancels.
+fn password_dialog(parent: &gtk::Window, path: &std::path::Path) -> Option<String> {
+	let dialog = gtk::Dialog::with_buttons(
+		Some("Password required"),
+		Some(parent),
+		gtk::DialogFlags::MODAL,
+		&[
+			("Cancel", gtk::ResponseType::Cancel),
+			("Open", gtk::ResponseType::Accept),
+		],
+	);
+	dialog.set_default_response(gtk::ResponseType::Accept);
+	dialog.set_border_width(10);
+	let label = gtk::Label::new(Some(&format!(
+		"None of the known passwords decrypts {}.\nEnter its password:",
+		path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()
+	)));
+	label.set_xalign(0.0);
+	let password_entry = gtk::Entry::new();
+	password_entry.set_visibility(false);
+	password_entry.set_activates_default(true);
+	let content_area = dialog.content_area();
+	content_area.set_spacing(10);
+	content_area.pack_start(&label, false, false, 0);
+	content_area.pack_start(&password_entry, false, false, 0);
+	dialog.show_all();
+	let response = dialog.run();
+	let password = password_entry.text().to_string();
+	dialog.close();
+	if response == gtk::ResponseType::Accept && !password.is_empty() {
+		Some(password)
+	} else {
+		None
+	}
 }
 
 fn configure_gsettings_schema_dir() {
@@ -1728,13 +2610,31 @@ fn main() {
 		Some("This is a test argument"),
 	);
 
+	application.add_main_option(
+		"sfile-password",
+		glib::Char::from(b'p'),
+		glib::OptionFlags::IN_MAIN,
+		glib::OptionArg::StringArray,
+		"Password of encrypted sfiles, may be given several times",
+		Some("PASSWORD"),
+	);
+
+	application.connect_handle_local_options(|_app, options| {
+		if let Ok(Some(passwords)) = options.lookup::<Vec<String>>("sfile-password") {
+			for password in passwords {
+				parse::sfile::add_password(&password);
+			}
+		}
+		-1 //Continue with the default handling of the command line
+	});
+
 	// https://gtk-rs.org/docs/gio/prelude/trait.ApplicationExtManual.html
 	application.connect_open(move |app, gio_files, _| {
-		build_ui(app, &gio_files_to_paths(gio_files));
+		build_ui(app, &gio_files_to_paths(gio_files), None, None);
 	});
 
 	application.connect_activate(|app| {
-		build_ui(app, &Vec::new());
+		build_ui(app, &Vec::new(), None, None);
 	});
 
 	// https://gtk-rs.org/docs/gio/prelude/trait.ApplicationExtManual.html#tymethod.run
@@ -1753,19 +2653,3 @@ log::info!("{}", dt.to_rfc3339_opts(SecondsFormat::Millis, false));
 let dt : DateTime::<Utc> = DateTime::<FixedOffset>::parse_from_rfc3339("1996-12-19T16:39:57-08:00").expect("Parse error!").with_timezone(&Utc);
 log::info!("{}", dt.to_rfc3339_opts(SecondsFormat::Millis, false));
 */
-
-/*
-enum Severity
-{
-  OFF = SLOG2_SHUTDOWN - 1,    ///filter setting only: no log passes.
-  SHUTDOWN = SLOG2_SHUTDOWN,   ///sporadic abnormal event, whole system corrupted, e.g.  (most severe)
-  CRITICAL = SLOG2_CRITICAL,   ///sporadic abnormal event, functionality corrupted, e.g.
-  ERROR = SLOG2_ERROR,         ///sporadic abnormal event, functionality immediately affected, e.g.
-  WARNING = SLOG2_WARNING,     ///sporadic abnormal event, functionality not immediately affected, e.g.
-  NOTICE = SLOG2_NOTICE,       ///sporadic data, business content, e.g.
-  INFO = SLOG2_INFO,           ///sporadic data, generic content, e.g.
-  DEBUG_NORMAL = SLOG2_DEBUG1, ///low bandwidth debug data, business content, e.g.
-  DEBUG_DETAIL = SLOG2_DEBUG2, ///high bandwidth debug data, generic content, e.g. all communication accesses (least severe)
-  DEFAULT,                     ///filter setting only: set the initial severity.
-};
-*/
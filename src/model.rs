@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use std::fmt;
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum LogLevel {
 	Critical,
 	Error,
@@ -32,6 +32,48 @@ impl fmt::Display for LogLevel {
 	}
 }
 
+// Severity as logged, for the levels of a format that `LogLevel` cannot tell apart.
+// The entry's `severity` holds the normalized level.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum SourceSeverity {
+	AppStart,    //xlog, normalized to Info
+	AppStop,     //xlog, normalized to Info
+	Exception,   //xlog, normalized to Error
+	Hardware,    //glog, hardware fault, normalized to Critical
+	Unspecified, //glog severity "None", normalized to Debug
+}
+
+impl fmt::Display for SourceSeverity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				SourceSeverity::AppStart => "AppStart",
+				SourceSeverity::AppStop => "AppStop",
+				SourceSeverity::Exception => "Exception",
+				SourceSeverity::Hardware => "Hardware",
+				SourceSeverity::Unspecified => "None",
+			}
+		)
+	}
+}
+
+impl LogLevel {
+	// Parses common severity names (case insensitive), e.g. "WARN" or "fatal"
+	pub fn from_name(name: &str) -> Option<LogLevel> {
+		match name.to_lowercase().as_str() {
+			"critical" | "crit" | "fatal" => Some(LogLevel::Critical),
+			"error" | "err" => Some(LogLevel::Error),
+			"warning" | "warn" => Some(LogLevel::Warning),
+			"info" | "information" | "notice" => Some(LogLevel::Info),
+			"debug" => Some(LogLevel::Debug),
+			"trace" | "verbose" => Some(LogLevel::Trace),
+			_ => None,
+		}
+	}
+}
+
 #[allow(dead_code)]
 pub enum CustomField {
 	Int64(i64),
@@ -47,9 +89,28 @@ pub enum CustomField {
 	String(String),
 }
 
+impl fmt::Display for CustomField {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CustomField::Int64(value) => write!(f, "{}", value),
+			CustomField::Int32(value) => write!(f, "{}", value),
+			CustomField::Int16(value) => write!(f, "{}", value),
+			CustomField::Int8(value) => write!(f, "{}", value),
+			CustomField::UInt64(value) => write!(f, "{}", value),
+			CustomField::UInt32(value) => write!(f, "{}", value),
+			CustomField::UInt16(value) => write!(f, "{}", value),
+			CustomField::UInt8(value) => write!(f, "{}", value),
+			CustomField::Float32(value) => write!(f, "{}", value),
+			CustomField::Float64(value) => write!(f, "{}", value),
+			CustomField::String(value) => write!(f, "{}", value),
+		}
+	}
+}
+
 pub struct LogEntry {
 	pub timestamp: chrono::DateTime<Utc>,
 	pub severity: LogLevel,
+	pub source_severity: Option<SourceSeverity>, //Set if the log has a more detailed severity
 	pub message: String,
 	pub custom_fields: HashMap<std::borrow::Cow<'static, str>, CustomField>,
 }
@@ -62,6 +123,58 @@ pub enum LogSourceContents {
 pub struct LogSource {
 	pub name: String,
 	pub children: LogSourceContents,
+	pub diagnostics: Vec<Diagnostic>, //Problems found while parsing this log source
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DiagnosticKind {
+	MalformedTimestamp,
+	InvalidSeverity,
+	MalformedField, //e.g. session ID or log sub-source
+	MalformedUtf8,
+	UnrecognizedKind,
+	UnrecognizedLine,
+	InvalidBytes,
+	CutOff,
+	ReadError,
+	UnparsableFile,
+	DetectedFormat, //Not a problem, tells which parser was picked by the file contents
+}
+
+impl fmt::Display for DiagnosticKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				DiagnosticKind::MalformedTimestamp => "Malformed timestamp",
+				DiagnosticKind::InvalidSeverity => "Invalid severity",
+				DiagnosticKind::MalformedField => "Malformed field",
+				DiagnosticKind::MalformedUtf8 => "Malformed UTF-8",
+				DiagnosticKind::UnrecognizedKind => "Unrecognized kind",
+				DiagnosticKind::UnrecognizedLine => "Unrecognized line",
+				DiagnosticKind::InvalidBytes => "Invalid bytes",
+				DiagnosticKind::CutOff => "Cut off",
+				DiagnosticKind::ReadError => "Read error",
+				DiagnosticKind::UnparsableFile => "Unparsable file",
+				DiagnosticKind::DetectedFormat => "Detected format",
+			}
+		)
+	}
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DiagnosticLocation {
+	Entry(usize),    //Index into the entries of the log source
+	ByteOffset(u64), //Offset into the file, for problems outside of any entry
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub source_name: String, //File or archive member that was parsed
+	pub location: DiagnosticLocation,
+	pub kind: DiagnosticKind,
+	pub snippet: String, //Raw input, shortened
 }
 
 impl Default for LogEntry {
@@ -72,6 +185,7 @@ impl Default for LogEntry {
 				Utc,
 			),
 			severity: LogLevel::Error,
+			source_severity: None,
 			message: "".to_string(),
 			custom_fields: HashMap::new(),
 		}
@@ -8,12 +8,16 @@ pub const VISIBLE_ON: u8 = 0x0;
 pub const VISIBLE_OFF_SOURCE: u8 = 0x1;
 pub const VISIBLE_OFF_SEVERITY: u8 = 0x2;
 pub const VISIBLE_OFF_FILTER: u8 = 0x4;
+pub const VISIBLE_OFF_SOURCE_SEVERITY: u8 = 0x8;
+pub const VISIBLE_OFF_ERROR_CODE: u8 = 0x10;
 
 #[rustfmt::skip]
 pub struct LogEntryExt {
 	pub timestamp: chrono::DateTime<Utc>,
 	pub severity: model::LogLevel,
+	pub source_severity: Option<model::SourceSeverity>,
 	pub session_id: Option<u32>,
+	pub error_code: Option<u32>,
 	pub message: String,
 	pub source_id: u32,
 	pub visible: u8,
@@ -22,12 +26,69 @@ pub struct LogEntryExt {
 	pub next_offset: u32, //offset of previous active element in LogStoreLinear vec
 	//For 1 million objects, 3 uint32 require 20ms more to sort.
 	//Therefore, stick to uint32 and not usize which doubles this amount
+	pub diagnostic_id: Option<u32>, //index of the first diagnostic of the entry, see `take_diagnostics`
+	pub custom_fields: Box<[(std::borrow::Cow<'static, str>, model::CustomField)]>, //sorted by name, without the ones above
 }
 
 impl LogEntryExt {
+	pub fn from_entry(entry: model::LogEntry) -> LogEntryExt {
+		let session_id = entry.custom_fields.get("SessionId").and_then(|custom_field| {
+				// If the "SessionId" field exists in the HashMap, attempt to extract the value and convert it to `Option<u32>`
+				match custom_field {
+					model::CustomField::UInt32(value) => Some(*value),
+					_ => None, // Return None for other variants or if the key doesn't exist
+				}
+		});
+
+		let error_code = match entry.custom_fields.get("ErrorCode") {
+			Some(model::CustomField::UInt32(value)) => Some(*value),
+			_ => None,
+		};
+
+		//Shown in the tooltip, e.g. the exception of a client log entry
+		let mut custom_fields: Vec<_> = entry
+			.custom_fields
+			.into_iter()
+			.filter(|(name, _)| {
+				!((name == "SessionId" && session_id.is_some())
+					|| (name == "ErrorCode" && error_code.is_some()))
+			})
+			.collect();
+		custom_fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		LogEntryExt {
+			timestamp: entry.timestamp,
+			severity: entry.severity,
+			source_severity: entry.source_severity,
+			session_id,
+			error_code,
+			message: remove_nul_bytes(entry.message),
+			source_id: 0,
+			visible: VISIBLE_ON,
+			entry_id: 0,
+			prev_offset: 0,
+			next_offset: 0,
+			diagnostic_id: None,
+			custom_fields: custom_fields.into_boxed_slice(),
+		}
+	}
+
 	pub fn is_visible(&self) -> bool {
 		self.visible == VISIBLE_ON
 	}
+
+	// One "name: value" line per custom field, None if there are none
+	pub fn custom_fields_tooltip(&self) -> Option<String> {
+		if self.custom_fields.is_empty() {
+			return None;
+		}
+		let lines: Vec<String> = self
+			.custom_fields
+			.iter()
+			.map(|(name, value)| format!("{}: {}", name, remove_nul_bytes(value.to_string())))
+			.collect();
+		Some(lines.join("\n"))
+	}
 }
 
 // Extended log source (not part of the API)
@@ -42,6 +103,14 @@ pub struct LogSourceExt {
 	pub id: u32,
 	pub child_cnt: u64,
 	pub children: LogSourceContentsExt,
+	pub diagnostics: Vec<model::Diagnostic>,
+}
+
+// Diagnostic taken out of the log source tree (not part of the API)
+pub struct DiagnosticExt {
+	pub source_name: String, //full name, as in `LogStoreLinear::log_sources`
+	pub diagnostic_id: Option<u32>, //of the entry the diagnostic belongs to
+	pub diagnostic: model::Diagnostic,
 }
 
 // Delete NUL bytes from string - GTK has trouble with them
@@ -66,27 +135,7 @@ impl LogSourceExt {
 			}
 			model::LogSourceContents::Entries(v) => LogSourceContentsExt::Entries(
 				v.into_iter()
-					.map(move |entry| {
-						let session_id = entry.custom_fields.get("SessionId").and_then(|custom_field| {
-								// If the "SessionId" field exists in the HashMap, attempt to extract the value and convert it to `Option<u32>`
-								match custom_field {
-									model::CustomField::UInt32(value) => Some(*value),
-									_ => None, // Return None for other variants or if the key doesn't exist
-								}
-						});
-
-						LogEntryExt {
-							timestamp: entry.timestamp,
-							severity: entry.severity,
-							session_id: session_id,
-							message: remove_nul_bytes(entry.message),
-							source_id: 0,
-							visible: VISIBLE_ON,
-							entry_id: 0,
-							prev_offset: 0,
-							next_offset: 0,
-						}
-					})
+					.map(LogEntryExt::from_entry)
 					.collect(),
 			),
 		};
@@ -95,6 +144,7 @@ impl LogSourceExt {
 			id: 0,
 			child_cnt: 0,
 			children,
+			diagnostics: log_source.diagnostics,
 		};
 		source_ext.generate_ids();
 		source_ext.calc_child_cnt();
@@ -150,6 +200,80 @@ impl LogSourceExt {
         }
     }
 
+    // Finds the log sources with direct children matching `filter` (e.g. the root of
+    // every sfile when several are opened) and collects the IDs of the matching
+    // subtrees per found log source, together with its name.
+    pub fn collect_descendant_ids_per_parent<F>(&self, filter: &F, groups: &mut Vec<(String, Vec<u32>)>)
+    where
+        F: Fn(&LogSourceExt) -> bool,
+    {
+        if let LogSourceContentsExt::Sources(sources) = &self.children {
+            if sources.iter().any(filter) {
+                let mut ids = Vec::new();
+                self.collect_descendant_ids_of_filtered_roots(filter, &mut ids);
+                groups.push((self.name.clone(), ids));
+            } else {
+                for source in sources {
+                    source.collect_descendant_ids_per_parent(filter, groups);
+                }
+            }
+        }
+    }
+
+    // Moves all entries out of this tree (e.g. entries read in follow mode) and assigns
+    // them the ID of the log source with the same path in `source_ids`, as built by
+    // `build_log_sources`. Log sources that are unknown there cannot get a new ID, as
+    // IDs must stay contiguous per subtree. Their entries go to the closest known parent.
+    pub fn take_entries_by_path(
+        &mut self,
+        prefix: &str,
+        source_ids: &std::collections::HashMap<String, u32>,
+        parent_id: u32,
+        entries: &mut Vec<LogEntryExt>,
+    ) {
+        let current_name = String::new() + prefix + "/" + &self.name;
+        let id = source_ids.get(&current_name).copied().unwrap_or(parent_id);
+        match &mut self.children {
+            LogSourceContentsExt::Sources(sources) => {
+                for source in sources {
+                    source.take_entries_by_path(&current_name, source_ids, id, entries);
+                }
+            }
+            LogSourceContentsExt::Entries(v) => {
+                for mut entry in v.drain(..) {
+                    entry.source_id = id;
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    // Moves the diagnostics of this tree to the end of `diagnostics`. Entries that have
+    // diagnostics get the index of their first one in `diagnostics` as `diagnostic_id`.
+    pub fn take_diagnostics(&mut self, prefix: &str, diagnostics: &mut Vec<DiagnosticExt>) {
+        let current_name = String::new() + prefix + "/" + &self.name;
+        for diagnostic in self.diagnostics.drain(..) {
+            let mut diagnostic_id = None;
+            if let (model::DiagnosticLocation::Entry(idx), LogSourceContentsExt::Entries(v)) =
+                (diagnostic.location, &mut self.children)
+            {
+                if let Some(entry) = v.get_mut(idx) {
+                    diagnostic_id = Some(*entry.diagnostic_id.get_or_insert(diagnostics.len() as u32));
+                }
+            }
+            diagnostics.push(DiagnosticExt {
+                source_name: current_name.clone(),
+                diagnostic_id,
+                diagnostic,
+            });
+        }
+        if let LogSourceContentsExt::Sources(sources) = &mut self.children {
+            for source in sources {
+                source.take_diagnostics(&current_name, diagnostics);
+            }
+        }
+    }
+
     fn collect_all_ids(&self, ids: &mut Vec<u32>) {
         ids.push(self.id);
         match &self.children {
@@ -0,0 +1,162 @@
+use std::io::Read;
+
+// Compressed log files ---------------------------------------------------------------
+// Single log files are often handed around compressed, e.g. debug.txt.gz or app.log.xz.
+// They are recognized by the magic bytes at their start, regardless of their name, and
+// are decompressed while they are parsed. The parsers only see the decompressed bytes.
+// ------------------------------------------------------------------------------------
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Compression {
+	Gzip,
+	Xz,
+	Zstd,
+	Bzip2,
+}
+
+impl std::fmt::Display for Compression {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				Compression::Gzip => "gzip",
+				Compression::Xz => "xz",
+				Compression::Zstd => "zstd",
+				Compression::Bzip2 => "bzip2",
+			}
+		)
+	}
+}
+
+const MAGIC_BYTES: [(Compression, &[u8]); 4] = [
+	(Compression::Gzip, b"\x1f\x8b"),
+	(Compression::Xz, b"\xfd7zXZ\x00"),
+	(Compression::Zstd, b"\x28\xb5\x2f\xfd"),
+	(Compression::Bzip2, b"BZh"),
+];
+
+pub fn detect_magic(bytes: &[u8]) -> Option<Compression> {
+	let compression = MAGIC_BYTES
+		.iter()
+		.find(|(_, magic)| bytes.starts_with(magic))
+		.map(|(compression, _)| *compression)?;
+	//"BZh" is followed by the block size, so text starting with "BZh" is no bzip2 file
+	if compression == Compression::Bzip2 && !matches!(bytes.get(3), Some(b'1'..=b'9')) {
+		return None;
+	}
+	Some(compression)
+}
+
+pub fn detect(path: &std::path::Path) -> Result<Option<Compression>, std::io::Error> {
+	let mut bytes = Vec::with_capacity(6);
+	std::fs::File::open(path)?.take(6).read_to_end(&mut bytes)?;
+	Ok(detect_magic(&bytes))
+}
+
+// Reader of the decompressed contents of `reader`. All formats decode concatenated
+// streams, as files are often appended to by log rotation (`cat a.gz b.gz`).
+pub fn decoder<'a>(
+	compression: Compression,
+	reader: impl Read + 'a,
+) -> Result<Box<dyn Read + 'a>, std::io::Error> {
+	Ok(match compression {
+		Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+		Compression::Xz => Box::new(lzma_rust2::XzReader::new(reader, true)),
+		Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+		Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+	})
+}
+
+// Path the file had before it was compressed, e.g. debug.txt for debug.txt.gz.
+// Used to recognize the format by its extension.
+pub fn inner_path(path: &std::path::Path) -> std::path::PathBuf {
+	match path.extension() {
+		Some(extension)
+			if ["gz", "xz", "zst", "bz2"]
+				.contains(&extension.to_string_lossy().to_lowercase().as_str()) =>
+		{
+			path.with_extension("")
+		}
+		_ => path.to_path_buf(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::Write;
+
+	// Compresses `text` as two streams, as if two files were concatenated
+	fn compress(compression: Compression, text: &str) -> Vec<u8> {
+		let (first, second) = text.split_at(text.len() / 2);
+		let mut compressed = Vec::new();
+		for part in [first, second] {
+			let mut stream = match compression {
+				Compression::Gzip => {
+					let mut encoder =
+						flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+					encoder.write_all(part.as_bytes()).unwrap();
+					encoder.finish().unwrap()
+				}
+				Compression::Xz => {
+					let mut encoder =
+						lzma_rust2::XzWriter::new(Vec::new(), lzma_rust2::XzOptions::default())
+							.unwrap();
+					encoder.write_all(part.as_bytes()).unwrap();
+					encoder.finish().unwrap()
+				}
+				Compression::Zstd => zstd::stream::encode_all(part.as_bytes(), 0).unwrap(),
+				Compression::Bzip2 => {
+					let mut encoder =
+						bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+					encoder.write_all(part.as_bytes()).unwrap();
+					encoder.finish().unwrap()
+				}
+			};
+			compressed.append(&mut stream);
+		}
+		compressed
+	}
+
+	#[test]
+	fn test_decompress() {
+		let text = "2023-01-01 10:00:00 first\n2023-01-01 10:00:01 second\n".repeat(100);
+		for compression in [
+			Compression::Gzip,
+			Compression::Xz,
+			Compression::Zstd,
+			Compression::Bzip2,
+		] {
+			let compressed = compress(compression, &text);
+			assert_eq!(detect_magic(&compressed), Some(compression));
+
+			let mut decompressed = String::new();
+			decoder(compression, compressed.as_slice())
+				.unwrap()
+				.read_to_string(&mut decompressed)
+				.unwrap();
+			assert_eq!(decompressed, text, "{}", compression);
+		}
+		assert_eq!(detect_magic(b"2023-01-01"), None);
+		assert_eq!(detect_magic(b"BZh9"), Some(Compression::Bzip2));
+		assert_eq!(detect_magic(b"BZh is starting"), None);
+	}
+
+	#[test]
+	fn test_inner_path() {
+		assert_eq!(
+			inner_path(std::path::Path::new("debug.txt.gz")),
+			std::path::Path::new("debug.txt")
+		);
+		assert_eq!(
+			inner_path(std::path::Path::new("app.log.ZST")),
+			std::path::Path::new("app.log")
+		);
+		assert_eq!(
+			inner_path(std::path::Path::new("app.LOG")),
+			std::path::Path::new("app.LOG")
+		);
+	}
+}
@@ -0,0 +1,565 @@
+use super::super::model;
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+
+// User-defined text log formats -----------------------------------------------------
+// Format definitions are TOML files in the `formats` sub-directory of any Sherlog
+// configuration directory (see `config::config_dirs`). Example:
+//
+// name = "Motion service"
+// file_glob = "motion_*.log"
+// line_regex = '^(?P<timestamp>\S+ \S+) \[(?P<severity>\w+)\] (?P<source>[\w.]+) <(?P<thread>\d+)> (?P<message>.*)$'
+// timestamp_format = "%Y-%m-%d %H:%M:%S%.f"
+// source_separator = "."     # optional, splits the source into a hierarchy
+// continuation = "append"    # "append" (default) or "ignore"
+// continuation_regex = '^\s' # optional, only append lines that match
+//
+// [severity_map]             # optional, raw severity -> critical/error/warning/info/debug/trace
+// E = "error"
+// W = "warning"
+//
+// [field_types]              # optional, type of extra captures (default: string)
+// thread = "u32"
+//
+// The line regex needs a `message` capture. The captures `timestamp`, `severity`
+// and `source` are optional, all other named captures become custom fields.
+// Lines that do not match the line regex continue the message of the previous entry.
+// ------------------------------------------------------------------------------------
+
+const RESERVED_CAPTURES: [&str; 4] = ["timestamp", "severity", "source", "message"];
+
+#[derive(Deserialize)]
+struct FormatDefinition {
+	name: String,
+	file_glob: String,
+	line_regex: String,
+	#[serde(default)]
+	timestamp_format: String,
+	#[serde(default)]
+	severity_map: HashMap<String, String>,
+	#[serde(default)]
+	continuation: ContinuationRule,
+	continuation_regex: Option<String>,
+	source_separator: Option<String>,
+	#[serde(default)]
+	field_types: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ContinuationRule {
+	#[default]
+	Append,
+	Ignore,
+}
+
+#[derive(Copy, Clone)]
+enum FieldType {
+	Int64,
+	Int32,
+	Int16,
+	Int8,
+	UInt64,
+	UInt32,
+	UInt16,
+	UInt8,
+	Float32,
+	Float64,
+	String,
+}
+
+#[derive(Clone)]
+pub struct CustomFormat {
+	pub name: String,
+	file_glob: glob::Pattern,
+	line_re: Regex,
+	timestamp_format: String,
+	severity_map: HashMap<String, model::LogLevel>,
+	continuation: ContinuationRule,
+	continuation_re: Option<Regex>,
+	source_separator: Option<String>,
+	field_types: HashMap<String, FieldType>,
+}
+
+#[derive(Debug)]
+pub enum FormatDefinitionError {
+	IoError(std::io::Error),
+	TomlError(toml::de::Error),
+	RegexError(regex::Error),
+	GlobError(glob::PatternError),
+	MissingMessageCapture,
+	InvalidSeverity(String),
+	InvalidFieldType(String),
+}
+
+impl std::fmt::Display for FormatDefinitionError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			FormatDefinitionError::IoError(err) => write!(f, "{}", err),
+			FormatDefinitionError::TomlError(err) => write!(f, "Invalid definition: {}", err),
+			FormatDefinitionError::RegexError(err) => write!(f, "Invalid regex: {}", err),
+			FormatDefinitionError::GlobError(err) => write!(f, "Invalid file glob: {}", err),
+			FormatDefinitionError::MissingMessageCapture => {
+				write!(f, "Line regex has no named capture 'message'")
+			}
+			FormatDefinitionError::InvalidSeverity(sev) => {
+				write!(f, "Invalid severity in severity map: {}", sev)
+			}
+			FormatDefinitionError::InvalidFieldType(field_type) => {
+				write!(f, "Invalid field type: {}", field_type)
+			}
+		}
+	}
+}
+
+impl CustomFormat {
+	pub fn from_definition(definition: &str) -> Result<CustomFormat, FormatDefinitionError> {
+		let def: FormatDefinition =
+			toml::from_str(definition).map_err(FormatDefinitionError::TomlError)?;
+
+		let line_re = Regex::new(&def.line_regex).map_err(FormatDefinitionError::RegexError)?;
+		if !line_re.capture_names().any(|name| name == Some("message")) {
+			return Err(FormatDefinitionError::MissingMessageCapture);
+		}
+		let continuation_re = if let Some(continuation_regex) = &def.continuation_regex {
+			Some(Regex::new(continuation_regex).map_err(FormatDefinitionError::RegexError)?)
+		} else {
+			None
+		};
+
+		let mut severity_map = HashMap::new();
+		for (raw, level) in def.severity_map {
+			let level = model::LogLevel::from_name(&level)
+				.ok_or(FormatDefinitionError::InvalidSeverity(level))?;
+			severity_map.insert(raw, level);
+		}
+
+		let mut field_types = HashMap::new();
+		for (field, field_type) in def.field_types {
+			let field_type = parse_field_type(&field_type)
+				.ok_or(FormatDefinitionError::InvalidFieldType(field_type))?;
+			field_types.insert(field, field_type);
+		}
+
+		Ok(CustomFormat {
+			name: def.name,
+			file_glob: glob::Pattern::new(&def.file_glob)
+				.map_err(FormatDefinitionError::GlobError)?,
+			line_re,
+			timestamp_format: def.timestamp_format,
+			severity_map,
+			continuation: def.continuation,
+			continuation_re,
+			source_separator: def.source_separator.filter(|sep| !sep.is_empty()),
+			field_types,
+		})
+	}
+
+	pub fn from_definition_file(
+		path: &std::path::Path,
+	) -> Result<CustomFormat, FormatDefinitionError> {
+		let definition = std::fs::read_to_string(path).map_err(FormatDefinitionError::IoError)?;
+		CustomFormat::from_definition(&definition)
+	}
+
+	pub fn matches_file(&self, path: &std::path::Path) -> bool {
+		let options = glob::MatchOptions {
+			case_sensitive: false,
+			..Default::default()
+		};
+		path.file_name().is_some_and(|file_name| {
+			self.file_glob
+				.matches_with(&file_name.to_string_lossy(), options)
+		})
+	}
+
+	pub fn to_log_entries(
+		&self,
+		reader: impl std::io::Read,
+		mut root: model::LogSource,
+	) -> model::LogSource {
+		let mut builder = SourceTreeBuilder::new(&root.name);
+		let mut parser = CustomFormatParser::new(self.clone());
+		parser.parse(reader, &mut root, &mut builder);
+		parser.flush_entry(&mut builder);
+		builder.build(root)
+	}
+
+	pub fn parse_file(
+		&self,
+		path: &std::path::PathBuf,
+		progress: &Progress,
+	) -> Result<model::LogSource, std::io::Error> {
+		let file = progress.reader(std::fs::File::open(path)?);
+		let root = model::LogSource {
+			name: path.file_name().unwrap().to_string_lossy().to_string(),
+			children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+			diagnostics: Vec::new(),
+		};
+		Ok(self.to_log_entries(file, root))
+	}
+
+	fn to_log_entry(
+		&self,
+		caps: &regex::Captures,
+		diagnostics: &mut EntryDiagnostics,
+	) -> (model::LogEntry, Vec<String>) {
+		let mut log_entry = model::LogEntry {
+			severity: model::LogLevel::Info,
+			message: caps.name("message").map_or("", |m| m.as_str()).to_string(),
+			..Default::default()
+		};
+
+		if let Some(timestamp) = caps.name("timestamp") {
+			if let Some(timestamp) = self.parse_timestamp(timestamp.as_str()) {
+				log_entry.timestamp = timestamp;
+			} else {
+				diagnostics.report(DiagnosticKind::MalformedTimestamp, timestamp.as_str());
+			}
+		}
+
+		if let Some(severity) = caps.name("severity") {
+			let severity = severity.as_str();
+			if let Some(level) = self
+				.severity_map
+				.get(severity)
+				.copied()
+				.or_else(|| model::LogLevel::from_name(severity))
+			{
+				log_entry.severity = level;
+			} else {
+				diagnostics.report(DiagnosticKind::InvalidSeverity, severity);
+			}
+		}
+
+		for name in self.line_re.capture_names().flatten() {
+			if RESERVED_CAPTURES.contains(&name) {
+				continue;
+			}
+			if let Some(value) = caps.name(name).map(|m| m.as_str()) {
+				if value.is_empty() {
+					continue;
+				}
+				let field_type = self
+					.field_types
+					.get(name)
+					.copied()
+					.unwrap_or(FieldType::String);
+				let field = to_custom_field(field_type, value).unwrap_or_else(|| {
+					diagnostics.report(DiagnosticKind::MalformedField, value);
+					model::CustomField::String(value.to_string())
+				});
+				log_entry
+					.custom_fields
+					.insert(std::borrow::Cow::Owned(name.to_string()), field);
+			}
+		}
+
+		let source = caps.name("source").map_or("", |m| m.as_str().trim());
+		let path = if source.is_empty() {
+			Vec::new()
+		} else if let Some(separator) = &self.source_separator {
+			source
+				.split(separator.as_str())
+				.filter(|name| !name.is_empty())
+				.map(|name| name.to_string())
+				.collect()
+		} else {
+			vec![source.to_string()]
+		};
+
+		(log_entry, path)
+	}
+
+	fn parse_timestamp(&self, value: &str) -> Option<chrono::DateTime<Utc>> {
+		if self.timestamp_format.contains("%z")
+			|| self.timestamp_format.contains("%:z")
+			|| self.timestamp_format.contains("%#z")
+		{
+			DateTime::parse_from_str(value, &self.timestamp_format)
+				.ok()
+				.map(|dt| dt.with_timezone(&Utc))
+		} else {
+			//No offset in the log, assume UTC
+			NaiveDateTime::parse_from_str(value, &self.timestamp_format)
+				.ok()
+				.map(|ndt| DateTime::<Utc>::from_utc(ndt, Utc))
+		}
+	}
+}
+
+// State of the parser between the lines of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the last entry may still get continuation
+// lines from the next chunk.
+pub struct CustomFormatParser {
+	format: CustomFormat,
+	current: Option<(model::LogEntry, Vec<String>)>,
+	entry_diagnostics: EntryDiagnostics, //Of `current`
+	offset: u64,                         //Of the next line
+}
+
+impl CustomFormatParser {
+	pub fn new(format: CustomFormat) -> CustomFormatParser {
+		CustomFormatParser {
+			format,
+			current: None,
+			entry_diagnostics: EntryDiagnostics::default(),
+			offset: 0,
+		}
+	}
+
+	// Parses the lines of `reader` into `builder`. The last entry is kept, as the next
+	// lines may still continue it (see `flush_entry`).
+	pub fn parse(
+		&mut self,
+		reader: impl std::io::Read,
+		root: &mut model::LogSource,
+		builder: &mut SourceTreeBuilder,
+	) {
+		let mut bufreader = BufReader::new(reader);
+		let mut buf = Vec::<u8>::with_capacity(512);
+
+		loop {
+			let line_offset = self.offset;
+			buf.clear();
+			match bufreader.read_until(b'\n', &mut buf) {
+				Ok(0) => break,
+				Ok(bytes_read) => self.offset += bytes_read as u64,
+				Err(e) => {
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::ReadError,
+						&e.to_string(),
+					);
+					break;
+				}
+			}
+			let mut line_end = buf.len();
+			if buf[..line_end].last() == Some(&b'\n') {
+				line_end -= 1;
+			}
+			if buf[..line_end].last() == Some(&b'\r') {
+				line_end -= 1;
+			}
+			let line = String::from_utf8_lossy(&buf[..line_end]);
+			let malformed_utf8 = matches!(line, std::borrow::Cow::Owned(_));
+
+			if let Some(caps) = self.format.line_re.captures(&line) {
+				self.flush_entry(builder);
+				if malformed_utf8 {
+					self.entry_diagnostics
+						.report(DiagnosticKind::MalformedUtf8, &line);
+				}
+				self.current = Some(self.format.to_log_entry(&caps, &mut self.entry_diagnostics));
+			} else if let Some((log_entry, _)) = &mut self.current {
+				if self.format.continuation == ContinuationRule::Append
+					&& self
+						.format
+						.continuation_re
+						.as_ref()
+						.is_none_or(|re| re.is_match(&line))
+				{
+					//Multi-line message continuation
+					if malformed_utf8 {
+						self.entry_diagnostics
+							.report(DiagnosticKind::MalformedUtf8, &line);
+					}
+					log_entry.message.push('\n');
+					log_entry.message.push_str(&line);
+				} else if self.format.continuation == ContinuationRule::Append
+					&& !line.trim().is_empty()
+				{
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::UnrecognizedLine,
+						&line,
+					);
+				}
+			} else if !line.trim().is_empty() {
+				let kind = if malformed_utf8 {
+					DiagnosticKind::MalformedUtf8
+				} else {
+					DiagnosticKind::UnrecognizedLine
+				};
+				diagnostics::report_at(root, line_offset, kind, &line);
+			}
+		}
+	}
+
+	// Pushes the last entry, it does not get any more continuation lines
+	pub fn flush_entry(&mut self, builder: &mut SourceTreeBuilder) {
+		if let Some((log_entry, path)) = self.current.take() {
+			builder.push(&path, log_entry, &mut self.entry_diagnostics);
+		}
+	}
+}
+
+// Loads all format definitions from the configuration directories.
+// Definitions that fail to load are skipped with a warning. If several
+// definitions share a name, the one with the highest precedence wins.
+pub fn load_formats() -> Vec<CustomFormat> {
+	let mut formats = Vec::<CustomFormat>::new();
+	for dir in crate::config::config_sub_dirs("formats") {
+		for format in load_formats_from_dir(&dir) {
+			formats.retain(|existing| existing.name != format.name);
+			formats.push(format);
+		}
+	}
+	formats
+}
+
+pub fn load_formats_from_dir(dir: &std::path::Path) -> Vec<CustomFormat> {
+	let mut paths = match std::fs::read_dir(dir) {
+		Ok(read_dir) => read_dir
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| {
+				path.extension()
+					.is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+			})
+			.collect::<Vec<_>>(),
+		Err(e) => {
+			log::warn!("Cannot read format directory {}: {}", dir.display(), e);
+			return Vec::new();
+		}
+	};
+	paths.sort();
+
+	let mut formats = Vec::new();
+	for path in paths {
+		match CustomFormat::from_definition_file(&path) {
+			Ok(format) => {
+				log::info!("Loaded log format '{}' from {}", format.name, path.display());
+				formats.push(format);
+			}
+			Err(e) => log::warn!("Skipping log format {}: {}", path.display(), e),
+		}
+	}
+	formats
+}
+
+#[rustfmt::skip]
+fn parse_field_type(value: &str) -> Option<FieldType> {
+	match value {
+		"i64"    => Some(FieldType::Int64),
+		"i32"    => Some(FieldType::Int32),
+		"i16"    => Some(FieldType::Int16),
+		"i8"     => Some(FieldType::Int8),
+		"u64"    => Some(FieldType::UInt64),
+		"u32"    => Some(FieldType::UInt32),
+		"u16"    => Some(FieldType::UInt16),
+		"u8"     => Some(FieldType::UInt8),
+		"f32"    => Some(FieldType::Float32),
+		"f64"    => Some(FieldType::Float64),
+		"string" => Some(FieldType::String),
+		_ => None,
+	}
+}
+
+fn to_custom_field(field_type: FieldType, value: &str) -> Option<model::CustomField> {
+	let value = value.trim();
+	match field_type {
+		FieldType::Int64 => value.parse().ok().map(model::CustomField::Int64),
+		FieldType::Int32 => value.parse().ok().map(model::CustomField::Int32),
+		FieldType::Int16 => value.parse().ok().map(model::CustomField::Int16),
+		FieldType::Int8 => value.parse().ok().map(model::CustomField::Int8),
+		FieldType::UInt64 => value.parse().ok().map(model::CustomField::UInt64),
+		FieldType::UInt32 => value.parse().ok().map(model::CustomField::UInt32),
+		FieldType::UInt16 => value.parse().ok().map(model::CustomField::UInt16),
+		FieldType::UInt8 => value.parse().ok().map(model::CustomField::UInt8),
+		FieldType::Float32 => value.parse().ok().map(model::CustomField::Float32),
+		FieldType::Float64 => value.parse().ok().map(model::CustomField::Float64),
+		FieldType::String => Some(model::CustomField::String(value.to_string())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DEFINITION: &str = r#"
+name = "Motion service"
+file_glob = "motion_*.log"
+line_regex = '^(?P<timestamp>\S+ \S+) \[(?P<severity>\w+)\] (?P<source>[\w.]+) <(?P<thread>\d+)> (?P<message>.*)$'
+timestamp_format = "%Y-%m-%d %H:%M:%S%.f"
+source_separator = "."
+
+[severity_map]
+E = "error"
+W = "warning"
+
+[field_types]
+thread = "u32"
+"#;
+
+	#[test]
+	fn test_custom_format() {
+		let format = CustomFormat::from_definition(DEFINITION).expect("Invalid definition");
+		assert!(format.matches_file(std::path::Path::new("logs/Motion_1.log")));
+		assert!(!format.matches_file(std::path::Path::new("logs/other.log")));
+
+		let log = concat!(
+			"2021-03-09 08:07:25.123 [E] axis.x <12> Limit reached\n",
+			"  continued\n",
+			"2021-03-09 08:07:25.124 [W] axis <12> Slow\n",
+			"2021-03-09 08:07:25.125 [INFO] axis.y <13> Homed\n",
+		);
+		let root = model::LogSource {
+			name: "motion_1.log".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = format.to_log_entries(log.as_bytes(), root);
+
+		let axis = match &root.children {
+			model::LogSourceContents::Sources(v) => {
+				assert_eq!(v.len(), 1);
+				&v[0]
+			}
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(axis.name, "axis");
+		let names: Vec<&str> = match &axis.children {
+			model::LogSourceContents::Sources(v) => v.iter().map(|s| s.name.as_str()).collect(),
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(names, vec!["axis", "x", "y"]);
+
+		let x = match &axis.children {
+			model::LogSourceContents::Sources(v) => &v[1],
+			_ => unreachable!(),
+		};
+		let entries = match &x.children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].severity, model::LogLevel::Error);
+		assert_eq!(entries[0].message, "Limit reached\n  continued");
+		match entries[0].custom_fields.get("thread") {
+			Some(model::CustomField::UInt32(thread)) => assert_eq!(*thread, 12),
+			_ => panic!("Expected thread field"),
+		}
+	}
+
+	#[test]
+	fn test_invalid_definition() {
+		let definition = "name = \"x\"\nfile_glob = \"*\"\nline_regex = '^(?P<text>.*)$'\n";
+		assert!(matches!(
+			CustomFormat::from_definition(definition),
+			Err(FormatDefinitionError::MissingMessageCapture)
+		));
+	}
+}
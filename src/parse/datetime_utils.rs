@@ -1,7 +1,10 @@
 extern crate chrono;
 
 use chrono::prelude::DateTime;
+use chrono::prelude::Datelike;
+use chrono::prelude::NaiveDate;
 use chrono::prelude::NaiveDateTime;
+use chrono::prelude::NaiveTime;
 use chrono::prelude::Utc;
 
 use std::convert::TryFrom;
@@ -122,6 +125,87 @@ pub fn from_timestamp_ms(timestamp_ms: u64) -> Option<chrono::DateTime<Utc>> {
 	}
 }
 
+// Timestamps without year -----------------------------------------------------------
+// BSD syslog and QNX slog2info write timestamps like "Oct  1 08:00:01.123" that lack
+// the year. `YearRollback` assigns the years by walking backwards from the last entry
+// of a file, which is assumed to be logged in `last_year` (see `modified_year`).
+// ------------------------------------------------------------------------------------
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct YearlessDateTime {
+	pub month: u32,
+	pub day: u32,
+	pub time: NaiveTime,
+}
+
+pub fn parse_yearless(timestamp: &str) -> Option<YearlessDateTime> {
+	let mut parts = timestamp.split_whitespace();
+	let month = match parts.next()? {
+		"Jan" => 1,
+		"Feb" => 2,
+		"Mar" => 3,
+		"Apr" => 4,
+		"May" => 5,
+		"Jun" => 6,
+		"Jul" => 7,
+		"Aug" => 8,
+		"Sep" => 9,
+		"Oct" => 10,
+		"Nov" => 11,
+		"Dec" => 12,
+		_ => return None,
+	};
+	let day = parts.next()?.parse::<u32>().ok()?;
+	let time = NaiveTime::parse_from_str(parts.next()?, "%H:%M:%S%.f").ok()?;
+	if parts.next().is_some() {
+		return None;
+	}
+	Some(YearlessDateTime { month, day, time })
+}
+
+pub struct YearRollback {
+	year: i32,
+	next_month: Option<u32>,
+}
+
+impl YearRollback {
+	pub fn new(last_year: i32) -> YearRollback {
+		YearRollback {
+			year: last_year,
+			next_month: None,
+		}
+	}
+
+	// Must be called for the entries in reverse file order.
+	// Returns None if the date does not exist in the inferred year.
+	pub fn resolve(&mut self, yearless: YearlessDateTime) -> Option<chrono::DateTime<Utc>> {
+		//A month that is much larger than the month of the following entry means we
+		//crossed a new year going back in time. Small decreases are tolerated, as
+		//entries are not always strictly ordered.
+		if let Some(next_month) = self.next_month {
+			if yearless.month >= next_month + 6 {
+				self.year -= 1;
+			}
+		}
+		self.next_month = Some(yearless.month);
+		NaiveDate::from_ymd_opt(self.year, yearless.month, yearless.day)
+			.map(|date| DateTime::<Utc>::from_utc(date.and_time(yearless.time), Utc))
+	}
+
+	pub fn year(&self) -> i32 {
+		self.year
+	}
+}
+
+// Year of the file modification time, or the current year if it is unknown
+pub fn modified_year(file: &std::fs::File) -> i32 {
+	file.metadata()
+		.and_then(|metadata| metadata.modified())
+		.map(DateTime::<Utc>::from)
+		.unwrap_or_else(|_| Utc::now())
+		.year()
+}
+
 #[cfg(test)]
 mod tests {
 	// Test in-file as we cannot test on a binary crate level:
@@ -153,6 +237,28 @@ mod tests {
 		validate_gcom_datetime(637287826990872490, "2020-06-26T15:38:19.087249000Z");
 	}
 
+	#[test]
+	fn test_year_rollback() {
+		let mut rollback = YearRollback::new(2021);
+		let jan = parse_yearless("Jan  1 00:00:01").expect("Parsing failed");
+		let dec = parse_yearless("Dec 31 23:59:58.250").expect("Parsing failed");
+		assert_eq!(
+			rollback
+				.resolve(jan)
+				.unwrap()
+				.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+			"2021-01-01T00:00:01Z"
+		);
+		assert_eq!(
+			rollback
+				.resolve(dec)
+				.unwrap()
+				.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			"2020-12-31T23:59:58.250Z"
+		);
+		assert_eq!(parse_yearless("Foo  1 00:00:01"), None);
+	}
+
 	#[test]
 	fn test_offset_addition() {
 		let dt1 = from_100ns(637287826990872490).expect("Conversion to Rust DateTime failed"); //2020-06-26T15:38:19.087249000Z
@@ -0,0 +1,314 @@
+use regex::Regex;
+
+use std::io::Read;
+
+// Content sniffing ------------------------------------------------------------------
+// Used when the file extension does not tell us which parser to use. We read the
+// first few KB of the file and let every known format score how well the sample
+// matches its syntax. The best scoring format wins if it is confident enough.
+// ------------------------------------------------------------------------------------
+
+pub const SAMPLE_SIZE: u64 = 8 * 1024;
+
+//Below this confidence, we rather report an unrecognized file than guess
+const MIN_CONFIDENCE: f32 = 0.5;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LogFormat {
+	Sfile,
+	Tar,
+	Glog,
+	Xlog,
+	Rds,
+	ScanLib,
+	Robot,
+	RobotXml,
+	JsonLines,
+	Syslog,
+	Slog2,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Detection {
+	pub format: LogFormat,
+	pub confidence: f32, //0.0 (no match at all) to 1.0 (every line matches)
+}
+
+pub fn detect(reader: impl std::io::Read) -> Result<Option<Detection>, std::io::Error> {
+	let mut sample = Vec::with_capacity(SAMPLE_SIZE as usize);
+	let bytes_read = reader.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+	Ok(detect_sample(&sample, bytes_read as u64 == SAMPLE_SIZE))
+}
+
+pub fn detect_sample(sample: &[u8], truncated: bool) -> Option<Detection> {
+	let scores = score_sample(sample, truncated);
+	let best = scores
+		.into_iter()
+		.max_by(|a, b| a.confidence.total_cmp(&b.confidence))?;
+	if best.confidence >= MIN_CONFIDENCE {
+		Some(best)
+	} else {
+		log::info!(
+			"No confident format match, best guess was {:?} ({:.0}%)",
+			best.format,
+			best.confidence * 100.0
+		);
+		None
+	}
+}
+
+fn score_sample(sample: &[u8], truncated: bool) -> Vec<Detection> {
+	if sample.starts_with(b"PK\x03\x04") {
+		//ZIP local file header magic. All sfiles are ZIP archives.
+		return vec![Detection {
+			format: LogFormat::Sfile,
+			confidence: 1.0,
+		}];
+	}
+	if sample.len() >= 512 && sample[257..262] == *b"ustar" {
+		//POSIX tar header magic, older tar files are only recognized by their extension
+		return vec![Detection {
+			format: LogFormat::Tar,
+			confidence: 1.0,
+		}];
+	}
+
+	let text = String::from_utf8_lossy(sample);
+	let mut lines: Vec<&str> = text.lines().collect();
+	if truncated && lines.len() > 1 {
+		//Last line was most likely cut off by the sample size
+		lines.pop();
+	}
+
+	vec![
+		Detection {
+			format: LogFormat::Glog,
+			confidence: score_glog(&lines),
+		},
+		Detection {
+			format: LogFormat::Xlog,
+			confidence: score_xlog(&lines),
+		},
+		Detection {
+			format: LogFormat::Rds,
+			confidence: score_rds(&lines),
+		},
+		Detection {
+			format: LogFormat::ScanLib,
+			confidence: score_scanlib(&lines),
+		},
+		Detection {
+			format: LogFormat::Robot,
+			confidence: score_robot(&lines),
+		},
+		Detection {
+			format: LogFormat::RobotXml,
+			confidence: score_robot_xml(&text),
+		},
+		Detection {
+			format: LogFormat::JsonLines,
+			confidence: score_jsonl(&lines),
+		},
+		Detection {
+			format: LogFormat::Syslog,
+			confidence: score_syslog(&lines),
+		},
+		Detection {
+			format: LogFormat::Slog2,
+			confidence: score_slog2(&lines),
+		},
+	]
+}
+
+//Fraction of relevant lines that match, lines rejected by `is_relevant` are not counted at all
+fn line_ratio(
+	lines: &[&str],
+	is_relevant: impl Fn(&str) -> bool,
+	is_match: impl Fn(&str) -> bool,
+) -> f32 {
+	let mut relevant = 0;
+	let mut matched = 0;
+	for line in lines.iter().filter(|line| is_relevant(line)) {
+		relevant += 1;
+		if is_match(line) {
+			matched += 1;
+		}
+	}
+	if relevant == 0 {
+		0.0
+	} else {
+		matched as f32 / relevant as f32
+	}
+}
+
+fn is_not_blank(line: &str) -> bool {
+	!line.trim().is_empty()
+}
+
+//Example: [tq|1568208334469]:[s|4]:[i|12]:[m|Hwa: Started.]
+fn score_glog(lines: &[&str]) -> f32 {
+	let re = Regex::new(r"^\[(tq|s|i|m|e|n|t)\|").unwrap();
+	line_ratio(lines, is_not_blank, |line| {
+		re.is_match(line) && line.trim_end().ends_with(']')
+	})
+}
+
+//Example: <T>˩637055156092730381˫<L>˩Info˫<M>˩LoggerService: Started.
+fn score_xlog(lines: &[&str]) -> f32 {
+	line_ratio(lines, is_not_blank, |line| {
+		line.starts_with("<T>˩") && line.contains("˫<L>˩")
+	})
+}
+
+//Example: 2020-12-01 15:46:19.122|INFO|RDSAgent|Started.
+//Messages may span multiple lines, so we only count lines that look like
+//they begin a record and compare that to all lines that start with a digit.
+fn score_rds(lines: &[&str]) -> f32 {
+	let re = Regex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(\.\d+)?\|").unwrap();
+	let ratio = line_ratio(lines, is_not_blank, |line| re.is_match(line));
+	let record_ratio = line_ratio(
+		lines,
+		|line| line.starts_with(|c: char| c.is_ascii_digit()),
+		|line| re.is_match(line),
+	);
+	//Continuation lines lower the plain ratio, so average both views of the sample
+	if ratio == 0.0 {
+		0.0
+	} else {
+		(ratio + record_ratio) / 2.0
+	}
+}
+
+//Example: 2020-12-01 15:46:19.122013 (warning) <0x00000001> [] : Foo
+//Multi-line messages are handled the same way as in RDS logs.
+fn score_scanlib(lines: &[&str]) -> f32 {
+	let re =
+		Regex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(\.\d+)? \(\w+\) <[^>]*> \[").unwrap();
+	let ratio = line_ratio(lines, is_not_blank, |line| re.is_match(line));
+	let record_ratio = line_ratio(
+		lines,
+		|line| line.starts_with(|c: char| c.is_ascii_digit()),
+		|line| re.is_match(line),
+	);
+	if ratio == 0.0 {
+		0.0
+	} else {
+		(ratio + record_ratio) / 2.0
+	}
+}
+
+//Example: 2025-12-18 22:50:36.585690 - INFO - Selecting tracker 10.62.33.92
+fn score_robot(lines: &[&str]) -> f32 {
+	let re = Regex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{6} - \w+ - ").unwrap();
+	let separator_re = Regex::new(r"^[=\-~]+$").unwrap();
+	line_ratio(
+		lines,
+		|line| is_not_blank(line) && !separator_re.is_match(line),
+		|line| re.is_match(line),
+	)
+}
+
+//Example: <?xml version="1.0" encoding="UTF-8"?>
+//          <robot generator="Robot 6.1 (Python 3.11.4 on linux)" generated="20230101 12:00:00.000">
+//The root element decides, there is nothing line based to count
+fn score_robot_xml(text: &str) -> f32 {
+	let re = Regex::new(r"^\u{feff}?\s*(?:<\?xml[^>]*\?>\s*)?(?s:<!--.*?-->\s*)*<robot[\s>]").unwrap();
+	if re.is_match(text) {
+		1.0
+	} else {
+		0.0
+	}
+}
+
+//Example: {"ts":"2023-05-04T10:00:00.123Z","level":"warn","msg":"Slow login"}
+fn score_jsonl(lines: &[&str]) -> f32 {
+	line_ratio(lines, is_not_blank, |line| {
+		let line = line.trim();
+		line.starts_with('{')
+			&& line.ends_with('}')
+			&& serde_json::from_str::<serde_json::Value>(line).is_ok()
+	})
+}
+
+//Example: <34>Oct 11 22:14:15 mymachine su: 'su root' failed
+//Example: <165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 - Started
+fn score_syslog(lines: &[&str]) -> f32 {
+	let re = Regex::new(
+		r"^(?:<\d{1,3}>)?(?:[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} |\d{4}-\d{2}-\d{2}T\S+ \S+ |1 \S+ \S+ \S+ \S+ \S+ )",
+	)
+	.unwrap();
+	line_ratio(lines, is_not_blank, |line| re.is_match(line))
+}
+
+//Example: Jan 01 00:00:04.637             syslogd.16392         slog      0  syslogd starting
+fn score_slog2(lines: &[&str]) -> f32 {
+	let re = Regex::new(
+		r"^[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}\.\d+\s+\S+\.\d+\s+\S+\s+(?:\S+\s+)?\d+(?:\s|$)",
+	)
+	.unwrap();
+	line_ratio(lines, is_not_blank, |line| re.is_match(line))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn detect_str(sample: &str) -> Option<Detection> {
+		detect_sample(sample.as_bytes(), false)
+	}
+
+	#[test]
+	fn test_detect_formats() {
+		let glog = "[tq|1568208334469]:[s|4]:[i|12]:[m|Hwa: Started.]\n\
+			[tq|1568208334470]:[s|2]:[i|12]:[m|Hwa: Failed.]\n";
+		assert_eq!(detect_str(glog).unwrap().format, LogFormat::Glog);
+
+		let xlog = "<T>˩637055156092730381˫<L>˩Info˫<M>˩LoggerService: Started.˫<A>˩App\n";
+		assert_eq!(detect_str(xlog).unwrap().format, LogFormat::Xlog);
+
+		let rds = "2020-12-01 15:46:19.122|INFO|RDSAgent|Started.\n\
+			2020-12-01 15:46:19.123|ERROR|RDSAgent|Multi\nline\n";
+		assert_eq!(detect_str(rds).unwrap().format, LogFormat::Rds);
+
+		let scanlib = "2020-12-01 15:46:19.122013 (warning) <0x00000001> [] : Foo\n";
+		assert_eq!(detect_str(scanlib).unwrap().format, LogFormat::ScanLib);
+
+		let robot = "==========\n\
+			2025-12-18 22:50:36.585690 - INFO - Selecting tracker 10.62.33.92\n\
+			2025-12-18 22:50:36.585691 - DEBUG - Connected\n";
+		assert_eq!(detect_str(robot).unwrap().format, LogFormat::Robot);
+
+		let robot_xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+			<robot generator=\"Robot 6.1\">\n<suite name=\"Device\">\n";
+		assert_eq!(detect_str(robot_xml).unwrap().format, LogFormat::RobotXml);
+
+		let jsonl = "{\"ts\":1683194400,\"msg\":\"Up\"}\n{\"ts\":1683194401,\"msg\":\"Down\"}\n";
+		assert_eq!(detect_str(jsonl).unwrap().format, LogFormat::JsonLines);
+
+		let syslog = "Oct  1 08:00:01 gateway CRON[1234]: (root) CMD (run-parts)\n\
+			<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 - Started\n";
+		assert_eq!(detect_str(syslog).unwrap().format, LogFormat::Syslog);
+
+		let slog2 =
+			"Jan 01 00:00:04.637             syslogd.16392         slog      0  syslogd starting\n";
+		assert_eq!(detect_str(slog2).unwrap().format, LogFormat::Slog2);
+
+		let sfile = detect_sample(b"PK\x03\x04\x14\x00", false).unwrap();
+		assert_eq!(sfile.format, LogFormat::Sfile);
+		assert_eq!(sfile.confidence, 1.0);
+
+		let mut tar = vec![0u8; 512];
+		tar[..14].copy_from_slice(b"contr_Hwa.glog");
+		tar[257..263].copy_from_slice(b"ustar\0");
+		assert_eq!(detect_sample(&tar, false).unwrap().format, LogFormat::Tar);
+	}
+
+	#[test]
+	fn test_detect_unknown() {
+		assert_eq!(
+			detect_str("Lorem ipsum dolor sit amet\nconsectetur\n"),
+			None
+		);
+		assert_eq!(detect_str(""), None);
+	}
+}
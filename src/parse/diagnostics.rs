@@ -0,0 +1,110 @@
+use super::super::model;
+
+// Parse diagnostics -------------------------------------------------------------------
+// Parsers report problems with their input here, so they can be shown in the GUI
+// instead of only on the console. A problem found while an entry is parsed belongs
+// to that entry, but its index is only known once the entry is pushed to its log
+// source (parsers that split a file into sub-sources decide this at the end of the
+// entry). Therefore, such problems are kept in `EntryDiagnostics` until then.
+// ------------------------------------------------------------------------------------
+
+const SNIPPET_MAX_CHARS: usize = 200;
+
+fn to_snippet(raw: &str) -> String {
+	match raw.char_indices().nth(SNIPPET_MAX_CHARS) {
+		Some((end, _)) => format!("{}…", &raw[..end]),
+		None => raw.to_string(),
+	}
+}
+
+// Problems of the entry that is currently parsed
+#[derive(Default)]
+pub struct EntryDiagnostics {
+	reported: Vec<(model::DiagnosticKind, String)>,
+}
+
+impl EntryDiagnostics {
+	pub fn report(&mut self, kind: model::DiagnosticKind, raw: &str) {
+		log::warn!("{}: {}", kind, raw);
+		self.reported.push((kind, to_snippet(raw)));
+	}
+
+	// Moves the reported problems to the diagnostics of the log source the entry was
+	// pushed to, at `entry_index`
+	pub fn attach(
+		&mut self,
+		source_name: &str,
+		diagnostics: &mut Vec<model::Diagnostic>,
+		entry_index: usize,
+	) {
+		diagnostics.extend(
+			self.reported
+				.drain(..)
+				.map(|(kind, snippet)| model::Diagnostic {
+					source_name: source_name.to_string(),
+					location: model::DiagnosticLocation::Entry(entry_index),
+					kind,
+					snippet,
+				}),
+		);
+	}
+}
+
+// Reports a problem that does not belong to any entry, e.g. a line before the first entry
+pub fn report_at(
+	log_source: &mut model::LogSource,
+	byte_offset: u64,
+	kind: model::DiagnosticKind,
+	raw: &str,
+) {
+	log::warn!(
+		"{} at byte {} of {}: {}",
+		kind,
+		byte_offset,
+		log_source.name,
+		raw
+	);
+	let diagnostic = model::Diagnostic {
+		source_name: log_source.name.clone(),
+		location: model::DiagnosticLocation::ByteOffset(byte_offset),
+		kind,
+		snippet: to_snippet(raw),
+	};
+	log_source.diagnostics.push(diagnostic);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_entry_diagnostics() {
+		let mut log_source = model::LogSource {
+			name: "test.log".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let mut entry_diagnostics = EntryDiagnostics::default();
+		entry_diagnostics.report(model::DiagnosticKind::MalformedTimestamp, "yesterday");
+		entry_diagnostics.report(model::DiagnosticKind::InvalidSeverity, &"x".repeat(300));
+		entry_diagnostics.attach("test.log", &mut log_source.diagnostics, 3);
+		entry_diagnostics.attach("test.log", &mut log_source.diagnostics, 4);
+		report_at(&mut log_source, 42, model::DiagnosticKind::CutOff, "[m|");
+
+		assert_eq!(log_source.diagnostics.len(), 3);
+		assert_eq!(
+			log_source.diagnostics[0].location,
+			model::DiagnosticLocation::Entry(3)
+		);
+		assert_eq!(log_source.diagnostics[0].snippet, "yesterday");
+		assert_eq!(
+			log_source.diagnostics[1].snippet.chars().count(),
+			SNIPPET_MAX_CHARS + 1
+		);
+		assert_eq!(
+			log_source.diagnostics[2].location,
+			model::DiagnosticLocation::ByteOffset(42)
+		);
+		assert_eq!(log_source.diagnostics[2].source_name, "test.log");
+	}
+}
@@ -0,0 +1,409 @@
+use super::super::model;
+use super::custom_format;
+use super::datetime_utils;
+use super::detect;
+use super::glog;
+use super::io::FileFormat;
+use super::jsonl_log;
+use super::progress::Progress;
+use super::rds_log;
+use super::robot_log;
+use super::scanlib_log;
+use super::slog2_log;
+use super::source_tree::SourceTreeBuilder;
+use super::syslog;
+use super::xlog;
+
+use std::io::Read;
+use std::io::Seek;
+
+// Follow mode -----------------------------------------------------------------------
+// Keeps a text log file open and parses the bytes that are appended to it, like
+// `tail -f`. The parser of the file's format is kept from the initial parse and only
+// fed the new bytes, so it goes on where it stopped, e.g. with the layout decided
+// from the first records of a slog2 file or with the open suites of a robot log.
+//
+// Writers usually append an entry with a single write, but a multi-line entry may
+// still be torn apart if we parse while it is being written. Therefore, appended data
+// is held back as long as the file keeps growing and its complete lines are parsed
+// once it was quiet for one poll. If the file never goes quiet, they are parsed as
+// soon as MAX_PENDING bytes are waiting.
+//
+// Continuation lines (e.g. a stack trace) may also come after a quiet poll. So the
+// parsers keep the last entry open until the line that starts the next entry arrives,
+// or until the file was quiet for RELEASE_QUIET_POLLS polls.
+// ------------------------------------------------------------------------------------
+
+const MAX_PENDING: usize = 256 * 1024;
+const RELEASE_QUIET_POLLS: u32 = 4;
+
+pub struct Follower {
+	file: std::fs::File,
+	name: String,
+	position: u64,    //Bytes of the file read so far
+	pending: Vec<u8>, //Bytes read, but not parsed yet
+	quiet_polls: u32, //Polls since the file last grew
+	parser: ChunkParser,
+}
+
+enum ChunkParser {
+	Custom(Box<custom_format::CustomFormatParser>),
+	Glog(Box<glog::GlogParser>, glog::SensorCorrections),
+	Xlog, //Every line is an entry of its own, there is nothing to keep
+	Rds(Box<rds_log::RdsParser>),
+	ScanLib(Box<scanlib_log::ScanLibParser>),
+	Robot(Box<robot_log::RobotParser>),
+	JsonLines(Box<jsonl_log::JsonLinesParser>),
+	Syslog(Box<syslog::SyslogParser>),
+	Slog2(Box<slog2_log::Slog2Parser>),
+}
+
+impl Follower {
+	// Opens the file and parses its current contents.
+	// Returns Ok(None) if the format cannot be followed (e.g. sfiles, which are archives).
+	pub fn new(
+		path: &std::path::PathBuf,
+		format: FileFormat,
+		progress: &Progress,
+	) -> Result<Option<(Follower, model::LogSource)>, std::io::Error> {
+		let name = path.file_name().unwrap().to_string_lossy().to_string();
+		let parser = match format {
+			FileFormat::Custom(format) => ChunkParser::Custom(Box::new(
+				custom_format::CustomFormatParser::new(format.clone()),
+			)),
+			FileFormat::Builtin(log_format) => match log_format {
+				detect::LogFormat::Sfile | detect::LogFormat::Tar | detect::LogFormat::RobotXml => {
+					return Ok(None)
+				}
+				detect::LogFormat::Glog => ChunkParser::Glog(
+					Box::new(glog::GlogParser::new(&name)),
+					glog::SensorCorrections::default(),
+				),
+				detect::LogFormat::Xlog => ChunkParser::Xlog,
+				detect::LogFormat::Rds => ChunkParser::Rds(Box::default()),
+				detect::LogFormat::ScanLib => ChunkParser::ScanLib(Box::default()),
+				detect::LogFormat::Robot => ChunkParser::Robot(Box::default()),
+				detect::LogFormat::JsonLines => ChunkParser::JsonLines(Box::new(
+					jsonl_log::JsonLinesParser::new(jsonl_log::FieldMapping::load()),
+				)),
+				detect::LogFormat::Syslog => ChunkParser::Syslog(Box::default()),
+				detect::LogFormat::Slog2 => ChunkParser::Slog2(Box::default()),
+			},
+		};
+		let mut follower = Follower {
+			file: std::fs::File::open(path)?,
+			name,
+			position: 0,
+			pending: Vec::new(),
+			quiet_polls: 0,
+			parser,
+		};
+		//Parse the current contents straight from the file, it may be large
+		let len = follower.file.metadata()?.len();
+		let file = follower.file.try_clone()?;
+		let log_source = follower.parse(progress.reader(file.take(len)), false)?;
+		follower.position = len;
+		Ok(Some((follower, log_source)))
+	}
+
+	// Reads the data appended since the last call. Returns the newly parsed entries,
+	// with the same log source tree layout as the initial parse, or Ok(None) if
+	// there is nothing to parse yet.
+	pub fn poll(&mut self) -> Result<Option<model::LogSource>, std::io::Error> {
+		let bytes_read = self.read_appended()?;
+		if bytes_read == 0 {
+			self.quiet_polls += 1;
+		} else {
+			self.quiet_polls = 0;
+		}
+		//The parsers keep the last entry open themselves, only complete lines are parsed
+		let end = if bytes_read == 0 || self.pending.len() >= MAX_PENDING {
+			self.pending
+				.iter()
+				.rposition(|b| *b == b'\n')
+				.map_or(0, |newline| newline + 1)
+		} else {
+			0
+		};
+		//Nothing more to come for the last entry
+		let release = self.quiet_polls == RELEASE_QUIET_POLLS;
+		if end == 0 && !release {
+			return Ok(None);
+		}
+		let chunk: Vec<u8> = self.pending.drain(..end).collect();
+		let log_source = self.parse(chunk.as_slice(), release)?;
+		if count_entries(&log_source) == 0 && log_source.diagnostics.is_empty() {
+			return Ok(None);
+		}
+		Ok(Some(log_source))
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn read_appended(&mut self) -> Result<usize, std::io::Error> {
+		let len = self.file.metadata()?.len();
+		if len < self.position {
+			//TODO: Notify of truncated file?
+			log::warn!("{} was truncated, following it from the start", self.name);
+			self.position = 0;
+			self.pending.clear();
+		}
+		self.file.seek(std::io::SeekFrom::Start(self.position))?;
+		let bytes_read = (&mut self.file)
+			.take(len - self.position)
+			.read_to_end(&mut self.pending)?;
+		self.position += bytes_read as u64;
+		Ok(bytes_read)
+	}
+
+	fn root(&self) -> model::LogSource {
+		model::LogSource {
+			name: self.name.clone(),
+			children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+			diagnostics: Vec::new(),
+		}
+	}
+
+	// `finish` ends the last entry, nothing is appended to it any more
+	fn parse(
+		&mut self,
+		reader: impl std::io::Read,
+		finish: bool,
+	) -> Result<model::LogSource, std::io::Error> {
+		let mut root = self.root();
+		let mut builder = SourceTreeBuilder::new(&root.name);
+		match &mut self.parser {
+			ChunkParser::Custom(parser) => {
+				parser.parse(reader, &mut root, &mut builder);
+				if finish {
+					parser.flush_entry(&mut builder);
+				}
+			}
+			ChunkParser::Glog(parser, corrections) => {
+				parser.parse(reader);
+				if finish {
+					parser.flush_entry();
+				}
+				let mut log_source = parser.take_log_source(root);
+				corrections.adjust(&mut log_source);
+				return Ok(log_source);
+			}
+			ChunkParser::Xlog => return Ok(xlog::to_log_entries(reader, root)),
+			ChunkParser::Rds(parser) => {
+				parser.parse(reader, &mut builder);
+				if finish {
+					parser.flush_entry(&mut builder);
+				}
+			}
+			ChunkParser::ScanLib(parser) => {
+				parser.parse(reader, &mut root, &mut builder);
+				if finish {
+					parser.flush_entry(&mut builder);
+				}
+			}
+			ChunkParser::Robot(parser) => {
+				parser.parse(reader, &mut root, &mut builder)?;
+				if finish {
+					parser.flush_entry(&mut builder);
+				}
+			}
+			ChunkParser::JsonLines(parser) => parser.parse(reader, &mut root, &mut builder),
+			ChunkParser::Syslog(parser) => {
+				parser.parse(reader, &mut root);
+				if finish {
+					parser.flush_entry();
+				}
+				parser.push_records(&mut builder, datetime_utils::modified_year(&self.file));
+			}
+			ChunkParser::Slog2(parser) => {
+				parser.parse(reader, &mut root);
+				if finish {
+					parser.flush_entry();
+				}
+				parser.push_records(&mut builder, datetime_utils::modified_year(&self.file));
+			}
+		}
+		Ok(builder.build(root))
+	}
+}
+
+fn count_entries(log_source: &model::LogSource) -> usize {
+	match &log_source.children {
+		model::LogSourceContents::Sources(v) => v.iter().map(count_entries).sum(),
+		model::LogSourceContents::Entries(v) => v.len(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::Write;
+
+	fn temp_path(name: &str) -> std::path::PathBuf {
+		std::env::temp_dir().join(format!("sherlog_follow_{}_{}", std::process::id(), name))
+	}
+
+	fn quiet_polls(follower: &mut Follower, polls: u32) -> Vec<model::LogSource> {
+		(0..polls)
+			.filter_map(|_| follower.poll().unwrap())
+			.collect()
+	}
+
+	fn messages(log_source: &model::LogSource, messages: &mut Vec<String>) {
+		match &log_source.children {
+			model::LogSourceContents::Sources(v) => {
+				v.iter().for_each(|s| self::messages(s, messages))
+			}
+			model::LogSourceContents::Entries(v) => {
+				messages.extend(v.iter().map(|entry| entry.message.clone()))
+			}
+		}
+	}
+
+	#[test]
+	fn test_follow_appended_entries() {
+		let path = temp_path("appended.jsonl");
+		let mut file = std::fs::File::create(&path).unwrap();
+		writeln!(file, r#"{{"msg":"first"}}"#).unwrap();
+
+		let (mut follower, log_source) = Follower::new(
+			&path,
+			FileFormat::Builtin(detect::LogFormat::JsonLines),
+			&Progress::new(),
+		)
+		.unwrap()
+		.unwrap();
+		assert_eq!(count_entries(&log_source), 1);
+		assert!(follower.poll().unwrap().is_none());
+
+		//Held back while the file is growing, parsed once it is quiet
+		writeln!(file, r#"{{"msg":"second"}}"#).unwrap();
+		writeln!(file, r#"{{"msg":"third"}}"#).unwrap();
+		assert!(follower.poll().unwrap().is_none());
+		let log_source = follower.poll().unwrap().unwrap();
+		assert_eq!(count_entries(&log_source), 2);
+		assert_eq!(log_source.name, follower.name());
+		//Every line is an entry of its own, none is held back
+		assert!(quiet_polls(&mut follower, RELEASE_QUIET_POLLS).is_empty());
+
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_follow_continuation_lines() {
+		let path = temp_path("continuation.txt");
+		let mut file = std::fs::File::create(&path).unwrap();
+		writeln!(file, "Jan 01 00:00:04.637  app.1  slog  0  started").unwrap();
+		let (mut follower, _) = Follower::new(
+			&path,
+			FileFormat::Builtin(detect::LogFormat::Slog2),
+			&Progress::new(),
+		)
+		.unwrap()
+		.unwrap();
+
+		//Stack trace written after a quiet poll. The last entry is held back until the
+		//next one starts, or the file is quiet for long enough.
+		writeln!(file, "Jan 01 00:00:05.000  app.1  slog  0  exception").unwrap();
+		let mut parsed = Vec::new();
+		quiet_polls(&mut follower, 2)
+			.iter()
+			.for_each(|s| messages(s, &mut parsed));
+		assert_eq!(parsed, vec!["started"]);
+		writeln!(file, "  at main()").unwrap();
+		writeln!(file, "Jan 01 00:00:06.000  app.1  slog  0  next").unwrap();
+		let log_sources = quiet_polls(&mut follower, 2);
+		let mut parsed = Vec::new();
+		log_sources.iter().for_each(|s| messages(s, &mut parsed));
+		assert_eq!(parsed, vec!["exception\n  at main()"]);
+		assert!(log_sources.iter().all(|s| s.diagnostics.is_empty()));
+
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_follow_slog2_layout() {
+		let path = temp_path("slog2info.txt");
+		let mut file = std::fs::File::create(&path).unwrap();
+		writeln!(file, "Jan 01 00:00:04.637  app.1  slog  0  started").unwrap();
+		let (mut follower, _) = Follower::new(
+			&path,
+			FileFormat::Builtin(detect::LogFormat::Slog2),
+			&Progress::new(),
+		)
+		.unwrap()
+		.unwrap();
+
+		//Looks like a verbose record with severity 0 and code 12 on its own
+		writeln!(file, "Jan 01 00:00:05.000  app.1  slog  0  12 bytes").unwrap();
+		let mut parsed = Vec::new();
+		quiet_polls(&mut follower, RELEASE_QUIET_POLLS + 1)
+			.iter()
+			.for_each(|s| messages(s, &mut parsed));
+		assert_eq!(parsed, vec!["started", "12 bytes"]);
+
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_follow_robot_test_case() {
+		let path = temp_path("debug.txt");
+		let mut file = std::fs::File::create(&path).unwrap();
+		writeln!(
+			file,
+			"2025-12-18 22:50:36.000000 - INFO - + START SUITE: Device [ ]"
+		)
+		.unwrap();
+		writeln!(
+			file,
+			"2025-12-18 22:50:36.100000 - INFO - +- START TEST: Login [ ]"
+		)
+		.unwrap();
+		let (mut follower, _) = Follower::new(
+			&path,
+			FileFormat::Builtin(detect::LogFormat::Robot),
+			&Progress::new(),
+		)
+		.unwrap()
+		.unwrap();
+
+		writeln!(file, "2025-12-18 22:50:36.200000 - INFO - Logging in").unwrap();
+		quiet_polls(&mut follower, 2);
+		writeln!(file, "  as admin").unwrap();
+		writeln!(
+			file,
+			"2025-12-18 22:50:36.300000 - INFO - +- END TEST: Login (0.2s)"
+		)
+		.unwrap();
+		let log_source = quiet_polls(&mut follower, 2).pop().unwrap();
+		let device = match &log_source.children {
+			model::LogSourceContents::Sources(v) => &v[0],
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(device.name, "Device");
+		let login = match &device.children {
+			model::LogSourceContents::Sources(v) => &v[0],
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(login.name, "Login");
+		let mut parsed = Vec::new();
+		messages(login, &mut parsed);
+		assert_eq!(
+			parsed,
+			vec![
+				"+- START TEST: Login [ ]",
+				"Logging in\n  as admin",
+				"Test 'Login' PASS (0.200 s)"
+			]
+		);
+
+		drop(file);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
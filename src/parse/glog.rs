@@ -1,47 +1,49 @@
 use super::super::model;
 
 use super::datetime_utils;
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
 
 use std::collections::HashMap;
+use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Read;
 use std::mem;
+use model::DiagnosticKind;
 use chrono::{DateTime, NaiveDateTime, Utc};
 
 // GLOG parser ----------------------------------------------------------------------
 
-pub fn to_log_entries(reader: impl std::io::Read, root: model::LogSource) -> model::LogSource {
-	let mut parser = GlogParser::new(root);
+const READ_BUFFER_SIZE: usize = 64 * 1024;
 
-	let mut bufreader = BufReader::new(reader);
-	let mut buffer = [0; 1];
-	loop {
-		if let Ok(bytes_read) = bufreader.read(&mut buffer) {
-			if bytes_read == 0 {
-				//log::info!("Len srcs {}, entrs {}", parser.log_sources.len(), parser.log_entries.len());
-				break parser.finalize();
-			} else {
-				parser.read_byte(buffer[0]);
-			}
-		} else {
-			break parser.finalize();
-		}
-	}
+pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) -> model::LogSource {
+	let mut parser = GlogParser::new(&root.name);
+	parser.parse(reader);
+	parser.finish(&mut root);
+	parser.take_log_source(root)
 }
 
-struct GlogParser {
+// State of the parser between the bytes of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the last entry may still be completed by
+// the next chunk.
+pub struct GlogParser {
 	state: GlogParserState,
 	buf: Vec<u8>,
 	log_entry: model::LogEntry,
 	sub_source: Option<i32>,
 	log_entries: Vec<model::LogEntry>,
-	log_sources: HashMap<String, model::LogSource>,
+	log_sources: HashMap<i32, SubSource>, //By QNX log source ID
+	source_names: HashMap<i32, SubSourceName>, //Of the IDs taken before, kept for appended data
 	invalid_bytes: usize,
-	root: model::LogSource,
+	first_invalid_byte: Option<u64>,
+	bytes_read: u64, //Up to the slice given to `read_bytes`
+	entry_diagnostics: EntryDiagnostics,
+	log_entries_diagnostics: Vec<model::Diagnostic>, //Of `log_entries`
+	source_name: String,                             //File that is parsed, for diagnostics
 }
 
 impl GlogParser {
-	fn new(root: model::LogSource) -> GlogParser {
+	pub fn new(source_name: &str) -> GlogParser {
 		GlogParser {
 			state: GlogParserState::PreSection,
 			buf: Vec::with_capacity(512),
@@ -50,9 +52,70 @@ impl GlogParser {
 			},
 			sub_source: None,
 			log_entries: Vec::<model::LogEntry>::new(),
-			log_sources: HashMap::<String, model::LogSource>::new(),
+			log_sources: HashMap::<i32, SubSource>::new(),
+			source_names: HashMap::<i32, SubSourceName>::new(),
 			invalid_bytes: 0,
-			root,
+			first_invalid_byte: None,
+			bytes_read: 0,
+			entry_diagnostics: EntryDiagnostics::default(),
+			log_entries_diagnostics: Vec::new(),
+			source_name: source_name.to_string(),
+		}
+	}
+
+	// Parses the bytes of `reader`. The last entry is kept, as the next bytes may still
+	// belong to it (see `flush_entry`).
+	pub fn parse(&mut self, reader: impl std::io::Read) {
+		let mut bufreader = BufReader::with_capacity(READ_BUFFER_SIZE, reader);
+		while let Ok(buffer) = bufreader.fill_buf() {
+			if buffer.is_empty() {
+				break;
+			}
+			let bytes_read = buffer.len();
+			self.read_bytes(buffer);
+			self.bytes_read += bytes_read as u64;
+			bufreader.consume(bytes_read);
+		}
+	}
+
+	// Same as calling `read_byte` for every byte. The contents of kinds and values are
+	// skipped with memchr, only the bytes around the delimiters go through `read_byte`.
+	fn read_bytes(&mut self, mut bytes: &[u8]) {
+		let slice_len = bytes.len();
+		while !bytes.is_empty() {
+			let delimiter = match self.state {
+				GlogParserState::PreSection => {
+					let offset = memchr::memchr(b'[', bytes).unwrap_or(bytes.len());
+					let is_invalid = |chr: &&u8| **chr != b'\r' && **chr != b'\n';
+					if self.first_invalid_byte.is_none() {
+						if let Some(position) = bytes[..offset].iter().position(|chr| is_invalid(&chr)) {
+							self.first_invalid_byte = Some(
+								self.bytes_read + (slice_len - bytes.len() + position) as u64,
+							);
+						}
+					}
+					self.invalid_bytes += bytes[..offset].iter().filter(is_invalid).count();
+					offset
+				}
+				GlogParserState::SectionKind => {
+					let offset = memchr::memchr(b'|', bytes).unwrap_or(bytes.len());
+					self.buf.extend_from_slice(&bytes[..offset]);
+					offset
+				}
+				GlogParserState::SectionValue(_) => {
+					let offset = memchr::memchr(b']', bytes).unwrap_or(bytes.len());
+					self.buf.extend_from_slice(&bytes[..offset]);
+					offset
+				}
+				//A few bytes after ']' decide whether the value ends
+				_ => 0,
+			};
+			if let Some(chr) = bytes.get(delimiter) {
+				self.read_byte(*chr);
+				bytes = &bytes[delimiter + 1..];
+			} else {
+				bytes = &[];
+			}
 		}
 	}
 
@@ -82,16 +145,15 @@ impl GlogParser {
 							"n" => GlogSectionKind::SessionId,      //sensor only
 							"t" => GlogSectionKind::Timestamp100ns, //sensor only
 							_ => {
-								//TODO: Notify of invalid kind?
-								log::warn!("UNRECOGNIZED kind: {}", &kind_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::UnrecognizedKind, kind_str);
 								GlogSectionKind::Unknown
 							}
 						}
 					} else {
-						//TODO: Notify of malformed UTF-8?
-						log::warn!(
-							"MALFORMED UTF-8 in kind string: {}",
-							&String::from_utf8_lossy(&self.buf)
+						self.entry_diagnostics.report(
+							DiagnosticKind::MalformedUtf8,
+							&String::from_utf8_lossy(&self.buf),
 						);
 						GlogSectionKind::Unknown
 					};
@@ -147,42 +209,40 @@ impl GlogParser {
 								{
 									self.log_entry.timestamp = datetime;
 								} else {
-									//TODO: Notify of invalid datetime?
-									log::warn!("MALFORMED Log ms timestamp: {}", ts_milli);
+									self.entry_diagnostics
+										.report(DiagnosticKind::MalformedTimestamp, &value_str);
 								}
 							} else {
-								//TODO: Notify of invalid timestamp?
-								log::warn!("MALFORMED Log ms timestamp value: {}", value_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedTimestamp, &value_str);
 							}
 						}
 						GlogSectionKind::Severity => {
 							if let Ok(glog_sev_u32) = value_str.parse::<u32>() {
 								if let Some(glog_sev) = GlogSeverity::from_u32(glog_sev_u32) {
 									self.log_entry.severity = normalize_glog_sev(glog_sev);
+									self.log_entry.source_severity = glog_source_sev(glog_sev);
 								} else {
-									//TODO: Notify of invalid severity?
-									log::warn!("INVALID Log severity: {}", value_str);
+									self.entry_diagnostics
+										.report(DiagnosticKind::InvalidSeverity, &value_str);
 								}
 							} else {
-								//TODO: Notify of malformed severity?
-								log::warn!("MALFORMED Log severity: {}", value_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::InvalidSeverity, &value_str);
 							}
 						}
 						GlogSectionKind::LogSource => {
 							if let Ok(parsed_sub_source) = value_str.parse::<i32>() {
 								self.sub_source = Some(parsed_sub_source);
-							//self.log_entry.custom_fields.insert(
-							//	std::borrow::Cow::Borrowed("LogSource"),
-							//	model::CustomField::Int32(parsed_sub_source),
-							//);
 							} else {
-								//TODO: Notify of malformed sub-source?
-								log::warn!("MALFORMED Log sub-source: {}", value_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedField, &value_str);
 							}
 						}
 						GlogSectionKind::Message => {
 							if let std::borrow::Cow::Owned(owned_str) = &value_str {
-								log::warn!("MALFORMED UTF-8 in Message: {}", owned_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedUtf8, owned_str);
 							}
 							self.log_entry.message = value_str.to_string();
 						}
@@ -191,16 +251,29 @@ impl GlogParser {
 								if let Some(datetime) = datetime_utils::from_100ns(gcom_datetime) {
 									self.log_entry.timestamp = datetime;
 								} else {
-									//TODO: Notify of invalid datetime?
-									log::warn!("MALFORMED Log 100ns datetime: {}", gcom_datetime);
+									self.entry_diagnostics
+										.report(DiagnosticKind::MalformedTimestamp, &value_str);
 								}
 							} else {
-								//TODO: Notify of invalid 100ns value?
-								log::warn!("MALFORMED Log 100ns value: {}", value_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedTimestamp, &value_str);
 							}
 						}
 						GlogSectionKind::ErrorCode => {
-							//TODO: Expose ErrorCode to user
+							//Decimal, but accept hex notation as well
+							let parsed_error_code = match value_str.strip_prefix("0x") {
+								Some(hex) => u32::from_str_radix(hex, 16),
+								None => value_str.parse::<u32>(),
+							};
+							if let Ok(parsed_error_code) = parsed_error_code {
+								self.log_entry.custom_fields.insert(
+									std::borrow::Cow::Borrowed("ErrorCode"),
+									model::CustomField::UInt32(parsed_error_code),
+								);
+							} else {
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedField, &value_str);
+							}
 						}
 						GlogSectionKind::SessionId => {
 							//TODO: Handle session ID, in particular sorting
@@ -211,14 +284,14 @@ impl GlogParser {
 									model::CustomField::UInt32(parsed_session_id),
 								);
 							} else {
-								//TODO: Notify of malformed session id?
-								log::warn!("MALFORMED Session ID: {}", value_str);
+								self.entry_diagnostics
+									.report(DiagnosticKind::MalformedField, &value_str);
 							}
 						}
 						GlogSectionKind::Unknown => (),
 					}
 					if entry_done {
-						let log_entry = mem::replace(
+						let mut log_entry = mem::replace(
 							&mut self.log_entry,
 							model::LogEntry {
 								..Default::default()
@@ -226,49 +299,27 @@ impl GlogParser {
 						);
 						if let Some(sub_source) = self.sub_source {
 							//Log entry specified a log sub-source
-
-							//Note: We don't actually use QNX log source IDs properly
-							//All we do is prepend source string name, plus colon and space
-
-							let offset = log_entry.message.find(": ");
-							let source_name = if let Some(offset) = offset {
-								let name_slice = &log_entry.message[0..offset];
-								if !name_slice.is_empty() {
-									Some(std::borrow::Cow::from(name_slice))
-								} else {
-									None
-								}
-							} else {
-								None
-							}
-							.unwrap_or_else(|| {
-								std::borrow::Cow::from(format!("Unknown ({})", sub_source))
-							});
-
-							let source_option = self.log_sources.get_mut(source_name.as_ref());
-							if let Some(source) = source_option {
-								//Log sub-source exists, push log entry
-								let children = &mut source.children;
-								match children {
-									model::LogSourceContents::Entries(v) => {
-										v.push(log_entry);
-									}
-									_ => unreachable!(), //We only insert LogSourceContents::Entries
-								}
-							} else {
-								//Log sub-source does not yet exist
-								self.log_sources.insert(
-									source_name.to_string(),
-									model::LogSource {
-										name: source_name.to_string(),
-										children: {
-											model::LogSourceContents::Entries(vec![log_entry])
-										},
-									},
-								);
+							log_entry.custom_fields.insert(
+								std::borrow::Cow::Borrowed("LogSourceId"),
+								model::CustomField::Int32(sub_source),
+							);
+							let source = self.log_sources.entry(sub_source).or_default();
+							if let Some(prefix) = source_name_prefix(&log_entry.message) {
+								*source.name_counts.entry(prefix.to_string()).or_default() += 1;
 							}
+							self.entry_diagnostics.attach(
+								&self.source_name,
+								&mut source.diagnostics,
+								source.entries.len(),
+							);
+							source.entries.push(log_entry);
 						} else {
 							//Log entry did not specify a log sub-source
+							self.entry_diagnostics.attach(
+								&self.source_name,
+								&mut self.log_entries_diagnostics,
+								self.log_entries.len(),
+							);
 							self.log_entries.push(log_entry);
 						}
 						self.sub_source = None;
@@ -284,29 +335,10 @@ impl GlogParser {
 		};
 	}
 
-	fn finalize(mut self) -> model::LogSource {
-		if self.invalid_bytes > 0 {
-			//TODO: Invalid bytes?
-			log::warn!("INVALID bytes encountered, count: {}", self.invalid_bytes);
-		}
+	// Ends the last entry, as if the log ended here, if its last section is complete
+	pub fn flush_entry(&mut self) {
 		match self.state {
-			GlogParserState::PreSection => {
-				//Log file empty
-			}
-			GlogParserState::SectionKind => {
-				//TODO: Notify of cut off kind?
-				log::warn!("CUT OFF last log message (kind)");
-			}
-			GlogParserState::SectionValue(_) => {
-				//TODO: Notify of cut off kind?
-				log::warn!("CUT OFF last log message (value)");
-			}
-			GlogParserState::SectionValuePost1(_) => {
-				//Finish parsing section
-				self.read_byte(b'\n');
-				self.read_byte(b'[');
-			}
-			GlogParserState::SectionValuePost2(_) => {
+			GlogParserState::SectionValuePost1(_) | GlogParserState::SectionValuePost2(_) => {
 				//Finish parsing section
 				self.read_byte(b'\n');
 				self.read_byte(b'[');
@@ -315,33 +347,169 @@ impl GlogParser {
 				//Finish parsing section
 				self.read_byte(b'[');
 			}
+			_ => return,
 		};
+		//The '[' above does not start a section
+		self.state = GlogParserState::PreSection;
+	}
+
+	// Ends the last entry at the end of the log. Reports it if it is cut off.
+	pub fn finish(&mut self, root: &mut model::LogSource) {
+		self.report_invalid_bytes(root);
+		match self.state {
+			GlogParserState::SectionKind | GlogParserState::SectionValue(_) => {
+				//Last log message cut off in kind or value
+				diagnostics::report_at(
+					root,
+					self.bytes_read,
+					DiagnosticKind::CutOff,
+					&String::from_utf8_lossy(&self.buf),
+				);
+			}
+			_ => self.flush_entry(),
+		}
+	}
+
+	fn report_invalid_bytes(&mut self, root: &mut model::LogSource) {
+		if let Some(first_invalid_byte) = self.first_invalid_byte.take() {
+			diagnostics::report_at(
+				root,
+				first_invalid_byte,
+				DiagnosticKind::InvalidBytes,
+				&format!("{} invalid bytes outside of sections", self.invalid_bytes),
+			);
+			self.invalid_bytes = 0;
+		}
+	}
+
+	// Moves the entries that were ended since the last call into `root`
+	pub fn take_log_source(&mut self, mut root: model::LogSource) -> model::LogSource {
+		self.report_invalid_bytes(&mut root);
+		let log_entries = mem::take(&mut self.log_entries);
+		let mut log_entries_diagnostics = mem::take(&mut self.log_entries_diagnostics);
 
-		if self.log_sources.is_empty() {
+		if self.log_sources.is_empty() && self.source_names.is_empty() {
 			//If no log message specified a source, we put the entries directly into the root
-			self.root.children = model::LogSourceContents::Entries(self.log_entries);
+			root.children = model::LogSourceContents::Entries(log_entries);
+			root.diagnostics.append(&mut log_entries_diagnostics);
 		} else {
 			let mut v = Vec::<model::LogSource>::with_capacity(
-				self.log_sources.len() + !self.log_entries.is_empty() as usize,
+				self.log_sources.len() + !log_entries.is_empty() as usize,
 			);
-			for (_, sub_source) in self.log_sources {
-				v.push(sub_source);
+			let mut ids: Vec<i32> = self.log_sources.keys().copied().collect();
+			ids.sort_unstable();
+			let mut names_taken: std::collections::HashSet<String> = self
+				.source_names
+				.values()
+				.map(|source_name| source_name.name.clone())
+				.collect();
+			//Appended data keeps the name, so that its entries end up in the same source
+			for id in ids {
+				let sub_source = self.log_sources.remove(&id).unwrap(); //Key from above
+				let source_name = self
+					.source_names
+					.entry(id)
+					.or_insert_with(|| sub_source.learn_name(id, &mut names_taken));
+				v.push(sub_source.into_log_source(source_name));
 			}
 
-			if !self.log_entries.is_empty() {
+			if !log_entries.is_empty() {
 				let sub_source = model::LogSource {
 					name: "Unknown (None)".to_string(),
-					children: { model::LogSourceContents::Entries(self.log_entries) },
+					children: { model::LogSourceContents::Entries(log_entries) },
+					diagnostics: log_entries_diagnostics,
 				};
 				v.push(sub_source);
 			}
 
 			//Case insensitive sort by log source name
 			v.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-			self.root.children = model::LogSourceContents::Sources(v);
+			root.children = model::LogSourceContents::Sources(v);
+		}
+
+		root
+	}
+}
+
+// QNX log sources are identified by a numeric ID. Their name is not part of the
+// glog format, but slogger writes it in front of the message, e.g. "Hwa: Started.".
+// The name of an ID is the prefix that most of its messages have when they are first
+// taken. Data appended later keeps that name.
+#[derive(Default)]
+struct SubSource {
+	entries: Vec<model::LogEntry>,
+	diagnostics: Vec<model::Diagnostic>,
+	name_counts: HashMap<String, usize>, //Message prefixes that may be the name
+}
+
+// Name of a QNX log source ID, decided from the entries that were taken first
+struct SubSourceName {
+	name: String,
+	prefix: Option<String>, //Stripped from the messages, e.g. "Hwa: "
+}
+
+impl SubSource {
+	fn learn_name(
+		&self,
+		id: i32,
+		names_taken: &mut std::collections::HashSet<String>,
+	) -> SubSourceName {
+		//A prefix of a few messages is rather part of the message, e.g. "Error: ..."
+		let learned_name = self
+			.name_counts
+			.iter()
+			.filter(|(_, count)| *count * 2 > self.entries.len())
+			.max_by(|(a_name, a_count), (b_name, b_count)| {
+				a_count.cmp(b_count).then_with(|| b_name.cmp(a_name))
+			})
+			.map(|(name, _)| name);
+
+		let source_name = if let Some(learned_name) = learned_name {
+			let name = if names_taken.contains(learned_name) {
+				//Several IDs with the same name
+				format!("{} ({})", learned_name, id)
+			} else {
+				learned_name.clone()
+			};
+			SubSourceName {
+				name,
+				prefix: Some(format!("{}: ", learned_name)),
+			}
+		} else {
+			SubSourceName {
+				name: format!("Unknown ({})", id),
+				prefix: None,
+			}
+		};
+		names_taken.insert(source_name.name.clone());
+		source_name
+	}
+
+	fn into_log_source(mut self, source_name: &SubSourceName) -> model::LogSource {
+		if let Some(prefix) = &source_name.prefix {
+			for entry in &mut self.entries {
+				if let Some(message) = entry.message.strip_prefix(prefix) {
+					entry.message = message.to_string();
+				}
+			}
 		}
 
-		self.root
+		model::LogSource {
+			name: source_name.name.clone(),
+			children: model::LogSourceContents::Entries(self.entries),
+			diagnostics: self.diagnostics,
+		}
+	}
+}
+
+// Returns "Hwa" for "Hwa: Started.". Only names without whitespace count, so that
+// messages like "Value out of range: 42" are not mistaken for a source name.
+fn source_name_prefix(message: &str) -> Option<&str> {
+	let name = &message[..message.find(": ")?];
+	if name.is_empty() || name.len() > 64 || name.contains(char::is_whitespace) {
+		None
+	} else {
+		Some(name)
 	}
 }
 
@@ -384,6 +552,16 @@ fn normalize_glog_sev(glog_sev: GlogSeverity) -> model::LogLevel {
 	}
 }
 
+// Keeps the severities that get lost in `normalize_glog_sev`
+fn glog_source_sev(glog_sev: GlogSeverity) -> Option<model::SourceSeverity> {
+	match glog_sev {
+		GlogSeverity::Hardware => Some(model::SourceSeverity::Hardware),
+		GlogSeverity::None => Some(model::SourceSeverity::Unspecified),
+		_ => None,
+	}
+}
+
+#[derive(Copy, Clone)]
 enum GlogSeverity {
 	Critical = 0,
 	Hardware = 1,
@@ -420,150 +598,352 @@ enum GlogSectionKind {
 }
 
 pub fn adjust_sensor_timestamps(source: &mut model::LogSource) {
-	match &mut source.children {
-		model::LogSourceContents::Sources(v) => {
-			for mut source in v {
-				adjust_sensor_timestamps(&mut source);
+	SensorCorrections::default().adjust(source);
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Correction {
+	session_id: u32,
+	delta: i64,
+}
+
+// The newest EtherCAT time correction of every log source. Follow mode keeps it between
+// the chunks that are appended to the file, for the entries that are logged after it.
+#[derive(Default)]
+pub struct SensorCorrections {
+	newest: HashMap<String, Correction>, //By log source name
+}
+
+impl SensorCorrections {
+	pub fn adjust(&mut self, source: &mut model::LogSource) {
+		match &mut source.children {
+			model::LogSourceContents::Sources(v) => {
+				for source in v {
+					self.adjust(source);
+				}
+			}
+			model::LogSourceContents::Entries(v) => {
+				log::info!("Adjust sensor timestamps: {:?}", source.name);
+				correct_older_entries(v);
+				let newest = self.newest.remove(&source.name);
+				if let Some(newest) = correct_newer_entries(v, newest) {
+					self.newest.insert(source.name.clone(), newest);
+				}
 			}
 		}
-		model::LogSourceContents::Entries(v) => {
-			log::info!("Adjust sensor timestamps: {:?}", source.name);
+	}
+}
 
-			#[derive(Debug, PartialEq)]
-			struct Correction {
-				session_id: u32,
-				delta: i64,
-			}
-			let mut active_correction: Option<Correction> = None;
-			// Reverse iterate, from the newest to the oldest entry
-			for entry in v.iter_mut().rev() {
-				if let Some(field) = entry.custom_fields.get("SessionId") {
-					if let model::CustomField::UInt32(session_id) = field {
-						// Some log entries say:
-						// Setting EtherCAT time [delta = 1562060032100954112 ns].
-						// Others say (omitting the dot):
-						// Setting EtherCAT time [delta = 1562060032100954112 ns]
-						// We need to handle both...
-						if entry.message.starts_with("Setting EtherCAT time [delta = ")
-							&& (entry.message.ends_with(" ns].") || entry.message.ends_with(" ns]"))
-						{
-							//log::info!(
-							//	"Found EtherCAT time correction entry: session: {}, msg: {}",
-							//	session_id,
-							//	entry.message
-							//);
-							let delta = entry.message.split(' ').nth(5).unwrap(); //we can unwrap here because we verified the format above
-							if let Ok(delta) = delta.parse::<i64>() {
-								let old_correction = mem::replace(
-									&mut active_correction,
-									Some(Correction {
-										session_id: *session_id,
-										delta,
-									}),
-								);
+// Some log entries say:
+// Setting EtherCAT time [delta = 1562060032100954112 ns].
+// Others say (omitting the dot):
+// Setting EtherCAT time [delta = 1562060032100954112 ns]
+// We need to handle both...
+// Returns the delta as written
+fn ethercat_time_delta(message: &str) -> Option<&str> {
+	if message.starts_with("Setting EtherCAT time [delta = ")
+		&& (message.ends_with(" ns].") || message.ends_with(" ns]"))
+	{
+		message.split(' ').nth(5)
+	} else {
+		None
+	}
+}
 
-								let old_session_id_opt =
-									if let Some(old_correction) = &old_correction {
-										Some(old_correction.session_id)
-									} else {
-										None
-									};
-
-								if old_correction == active_correction {
-									log::warn!(
-										"Overwriting EtherCAT Time with same content! {:?}",
-										active_correction
-									);
-								} else if old_session_id_opt == Some(*session_id) {
-									log::warn!(
-										"Overwriting EtherCAT Time! Old: {:?}, New: {:?}",
-										old_correction,
-										active_correction
-									);
-								} else {
-									// This is the happy path for reading timestamp corrections.
-									// Happens when:
-									// - The very first correction is read
-									// - A valid correction is read after the last one was invalidated by e.g. a session change
-									// - A valid correction replaces a previous valid correction due to session change
-									//log::info!(
-									//	"Read fresh timestamp correction: {:?}, session ID: {}",
-									//	active_correction,
-									//	session_id
-									//);
-								}
-							} else {
-								log::warn!("could not parse EtherCAT timestamp {}", delta);
-								active_correction = None;
-							}
-						} else {
-							if let Some(correction) = &active_correction {
-								if *session_id == correction.session_id {
-									// Timestamps before 01-01-2001 00:00:00.000000 are not realistic because the device did not exist back then.
-									// We can safely assume that these are relative timestamps that are not yet corrected with EtherCAT time.
-									// It is also reasonable to assume that a device receives its EtherCAT time within 2 years (or never).
-									if entry.timestamp
-										< DateTime::<Utc>::from_utc(
-											NaiveDateTime::from_timestamp_opt(978_300_000, 0)
-												.unwrap(),
-											Utc,
-										) {
-										//Divide delta by 100 to convert from 1ns to 100ns ticks, which is the default GCOM timespan measurement
-										if let Some(corrected_timestamp) =
-											datetime_utils::add_offset_100ns(
-												entry.timestamp,
-												correction.delta / 100,
-											) {
-											entry.timestamp = corrected_timestamp;
-										} else {
-											log::warn!(
-												"could not correct timestamp with offset: {}",
-												correction.delta
-											);
-										}
-									}
-								} else {
-									// We moved on to a different session. Scrap active timestamp correction.
-									active_correction = None;
-								}
-							} else {
-								// This either happens if we encounter an already corrected timestamp and haven't yet
-								// encountered the log entry that specifies the time delta.
-								// Or else, it happens if the bus never connected, so the device never got the EtherCAT offset.
-								// This second case is also a normal thing to occur over the lifetime of a device,
-								// but we have to think about how to sort these log lines as their timestamp remains around 1970.
-								//log::warn!("Could not find EtherCAT offset for {}!", entry.message);
-							}
-						}
+fn session_id(entry: &model::LogEntry) -> Option<u32> {
+	match entry.custom_fields.get("SessionId") {
+		Some(model::CustomField::UInt32(session_id)) => Some(*session_id),
+		Some(_) => panic!("Wrong type for session ID!"),
+		None => None,
+	}
+}
+
+fn apply_correction(entry: &mut model::LogEntry, correction: &Correction) {
+	// Timestamps before 01-01-2001 00:00:00.000000 are not realistic because the device did not exist back then.
+	// We can safely assume that these are relative timestamps that are not yet corrected with EtherCAT time.
+	// It is also reasonable to assume that a device receives its EtherCAT time within 2 years (or never).
+	if entry.timestamp
+		< DateTime::<Utc>::from_utc(
+			NaiveDateTime::from_timestamp_opt(978_300_000, 0).unwrap(),
+			Utc,
+		) {
+		//Divide delta by 100 to convert from 1ns to 100ns ticks, which is the default GCOM timespan measurement
+		if let Some(corrected_timestamp) =
+			datetime_utils::add_offset_100ns(entry.timestamp, correction.delta / 100)
+		{
+			entry.timestamp = corrected_timestamp;
+		} else {
+			log::warn!(
+				"could not correct timestamp with offset: {}",
+				correction.delta
+			);
+		}
+	}
+}
+
+// Corrects the entries of a session with the EtherCAT time that the session logs later
+fn correct_older_entries(v: &mut [model::LogEntry]) {
+	let mut active_correction: Option<Correction> = None;
+	// Reverse iterate, from the newest to the oldest entry
+	for entry in v.iter_mut().rev() {
+		if let Some(session_id) = session_id(entry) {
+			if let Some(delta) = ethercat_time_delta(&entry.message) {
+				//log::info!(
+				//	"Found EtherCAT time correction entry: session: {}, msg: {}",
+				//	session_id,
+				//	entry.message
+				//);
+				if let Ok(delta) = delta.parse::<i64>() {
+					let old_correction = mem::replace(
+						&mut active_correction,
+						Some(Correction { session_id, delta }),
+					);
+
+					let old_session_id_opt = if let Some(old_correction) = &old_correction {
+						Some(old_correction.session_id)
+					} else {
+						None
+					};
+
+					if old_correction == active_correction {
+						log::warn!(
+							"Overwriting EtherCAT Time with same content! {:?}",
+							active_correction
+						);
+					} else if old_session_id_opt == Some(session_id) {
+						log::warn!(
+							"Overwriting EtherCAT Time! Old: {:?}, New: {:?}",
+							old_correction,
+							active_correction
+						);
 					} else {
-						panic!("Wrong type for session ID!");
+						// This is the happy path for reading timestamp corrections.
+						// Happens when:
+						// - The very first correction is read
+						// - A valid correction is read after the last one was invalidated by e.g. a session change
+						// - A valid correction replaces a previous valid correction due to session change
+						//log::info!(
+						//	"Read fresh timestamp correction: {:?}, session ID: {}",
+						//	active_correction,
+						//	session_id
+						//);
 					}
 				} else {
-					// "sensorbase_BaseboardSpecialLogs_1_v.glog" lacks session ID, these logs are special
-					log::warn!(
-						"No session ID found for sensor log entry: {}",
-						entry.message
-					);
+					log::warn!("could not parse EtherCAT timestamp {}", delta);
 					active_correction = None;
 				}
+			} else {
+				if let Some(correction) = &active_correction {
+					if session_id == correction.session_id {
+						apply_correction(entry, correction);
+					} else {
+						// We moved on to a different session. Scrap active timestamp correction.
+						active_correction = None;
+					}
+				} else {
+					// This either happens if we encounter an already corrected timestamp and haven't yet
+					// encountered the log entry that specifies the time delta.
+					// Or else, it happens if the bus never connected, so the device never got the EtherCAT offset.
+					// This second case is also a normal thing to occur over the lifetime of a device,
+					// but we have to think about how to sort these log lines as their timestamp remains around 1970.
+					//log::warn!("Could not find EtherCAT offset for {}!", entry.message);
+				}
 			}
+		} else {
+			// "sensorbase_BaseboardSpecialLogs_1_v.glog" lacks session ID, these logs are special
+			log::warn!(
+				"No session ID found for sensor log entry: {}",
+				entry.message
+			);
+			active_correction = None;
 		}
 	}
 }
 
+// Corrects the entries of a session that are logged after its EtherCAT time, but still
+// have relative timestamps. Starts with `newest`, the correction in effect before `v`.
+// Returns the correction in effect after `v`.
+fn correct_newer_entries(
+	v: &mut [model::LogEntry],
+	newest: Option<Correction>,
+) -> Option<Correction> {
+	let mut active_correction = newest;
+	for entry in v.iter_mut() {
+		match (session_id(entry), ethercat_time_delta(&entry.message)) {
+			(Some(session_id), Some(delta)) => {
+				active_correction = delta
+					.parse::<i64>()
+					.ok()
+					.map(|delta| Correction { session_id, delta });
+			}
+			(Some(session_id), None) => match active_correction {
+				Some(correction) if correction.session_id == session_id => {
+					apply_correction(entry, &correction);
+				}
+				// A different session, or no correction yet
+				_ => active_correction = None,
+			},
+			(None, _) => active_correction = None,
+		}
+	}
+	active_correction
+}
+
 // from_file -------------------------------------------------------------------------
 // Reads a GLOG file from disk and returns the parsed log source
 // with all its log entries and sub-sources.
 // Adjusts sensor timestamps based on EtherCAT time corrections found in the log.
 // Returns std::io::Error on file read errors.
 // ------------------------------------------------------------------------------------
-pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io::Error> {
-    let file = std::fs::File::open(&path)?;
+pub fn from_file(
+    path: &std::path::PathBuf,
+    progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+    let file = progress.reader(std::fs::File::open(path)?);
     let root = model::LogSource {
         name: path.file_name().unwrap().to_string_lossy().to_string(),
         children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+        diagnostics: Vec::new(),
     };
     let mut log_source = to_log_entries(file, root);
     adjust_sensor_timestamps(&mut log_source);
     Ok(log_source)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_glog() {
+		let log = b"[tq|1568208334469]:[s|4]:[i|12]:[m|Hwa: Started.]\r\n\
+			[tq|1568208334470]:[s|2]:[i|12]:[e|1042]:[m|Hwa: a]]:b\r\nc]\n\
+			[tq|1568208334471]:[s|1]:[m|No source]\n\
+			[tq|1568208334472]:[s|4]:[i|13]:[m|Error: not a source name]\n\
+			[tq|1568208334473]:[s|4]:[i|13]:[m|Done]";
+		let root = model::LogSource {
+			name: "contr_Hwa.glog".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(&log[..], root);
+
+		let sources = match root.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(sources.len(), 3);
+		assert_eq!(sources[0].name, "Hwa");
+		//Only one of two messages has the prefix
+		assert_eq!(sources[1].name, "Unknown (13)");
+		assert_eq!(sources[2].name, "Unknown (None)");
+
+		let entries = match &sources[0].children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].message, "Started.");
+		assert!(matches!(
+			entries[0].custom_fields.get("LogSourceId"),
+			Some(model::CustomField::Int32(12))
+		));
+		//Brackets and line breaks inside a value do not end it
+		assert_eq!(entries[1].message, "a]]:b\r\nc");
+		assert_eq!(entries[1].severity, model::LogLevel::Error);
+		assert!(matches!(
+			entries[1].custom_fields.get("ErrorCode"),
+			Some(model::CustomField::UInt32(1042))
+		));
+	}
+
+	#[test]
+	fn test_source_names_of_appended_data() {
+		let new_root = || model::LogSource {
+			name: "contr_Hwa.glog".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let source_names = |root: &model::LogSource| match &root.children {
+			model::LogSourceContents::Sources(v) => {
+				v.iter().map(|s| s.name.clone()).collect::<Vec<_>>()
+			}
+			_ => panic!("Expected sub-sources"),
+		};
+		let mut parser = GlogParser::new("contr_Hwa.glog");
+		parser.parse(&b"[tq|1568208334469]:[s|4]:[i|12]:[m|Hwa: Started.]\n"[..]);
+		parser.flush_entry();
+		let root = parser.take_log_source(new_root());
+		assert_eq!(source_names(&root), ["Hwa"]);
+
+		//Fewer than half of the messages have the prefix, and one has no source
+		parser.parse(
+			&b"[tq|1568208334470]:[s|4]:[i|12]:[m|Hwa: Running.]\n\
+			[tq|1568208334471]:[s|4]:[i|12]:[m|a]\n\
+			[tq|1568208334472]:[s|4]:[i|12]:[m|b]\n\
+			[tq|1568208334473]:[s|4]:[m|No source]\n"[..],
+		);
+		parser.flush_entry();
+		let root = parser.take_log_source(new_root());
+		assert_eq!(source_names(&root), ["Hwa", "Unknown (None)"]);
+		let sources = match root.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => unreachable!(),
+		};
+		let messages = match &sources[0].children {
+			model::LogSourceContents::Entries(v) => {
+				v.iter().map(|e| e.message.as_str()).collect::<Vec<_>>()
+			}
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(messages, ["Running.", "a", "b"]);
+	}
+
+	#[test]
+	fn test_sensor_corrections_carry_over() {
+		let entry = |session_id: u32, seconds: i64, message: &str| {
+			let mut entry = model::LogEntry {
+				timestamp: DateTime::<Utc>::from_utc(
+					NaiveDateTime::from_timestamp_opt(seconds, 0).unwrap(),
+					Utc,
+				),
+				message: message.to_string(),
+				..Default::default()
+			};
+			entry.custom_fields.insert(
+				std::borrow::Cow::Borrowed("SessionId"),
+				model::CustomField::UInt32(session_id),
+			);
+			entry
+		};
+		let source = |entries| model::LogSource {
+			name: "sensor.glog".to_string(),
+			children: model::LogSourceContents::Entries(entries),
+			diagnostics: Vec::new(),
+		};
+		let timestamps = |source: &model::LogSource| match &source.children {
+			model::LogSourceContents::Entries(v) => v
+				.iter()
+				.map(|e| e.timestamp.timestamp())
+				.collect::<Vec<_>>(),
+			_ => panic!("Expected entries"),
+		};
+
+		//As appended to a followed file, the correction comes in the first chunk
+		let mut corrections = SensorCorrections::default();
+		let mut first = source(vec![
+			entry(1, 5, "Booting"),
+			entry(
+				1,
+				6,
+				"Setting EtherCAT time [delta = 1000000000000000000 ns].",
+			),
+		]);
+		corrections.adjust(&mut first);
+		assert_eq!(timestamps(&first), vec![1_000_000_005, 6]);
+
+		let mut second = source(vec![entry(1, 7, "Running"), entry(2, 8, "Rebooted")]);
+		corrections.adjust(&mut second);
+		assert_eq!(timestamps(&second), vec![1_000_000_007, 8]);
+	}
+}
@@ -1,7 +1,24 @@
 use super::super::model;
+use super::compression;
+use super::custom_format;
+use super::datetime_utils;
+use super::detect;
+use super::follow;
 use super::glog;
+use super::jsonl_log;
+use super::progress::Progress;
+use super::rds_log;
 use super::robot_log;
+use super::robot_xml;
+use super::scanlib_log;
 use super::sfile;
+use super::sfile_rules::SfileRules;
+use super::slog2_log;
+use super::syslog;
+use super::tar;
+use super::xlog;
+
+use std::io::Read;
 
 #[derive(Debug)]
 pub enum LogParseError {
@@ -9,6 +26,9 @@ pub enum LogParseError {
 	UnrecognizedFileExtension(std::ffi::OsString),
 	NoFileExtension,
 	UnrecognizedLogFile(std::path::PathBuf),
+	NoLogFilesFound(std::path::PathBuf),
+	InvalidPassword(std::path::PathBuf), //None of the known passwords decrypts the archive
+	Cancelled,
 }
 
 impl std::error::Error for LogParseError {
@@ -31,6 +51,13 @@ impl std::fmt::Display for LogParseError {
 			LogParseError::UnrecognizedLogFile(path) => {
                 write!(f, "File '{}' is not known log file. Parsing failed.", path.display())
             }
+			LogParseError::NoLogFilesFound(path) => {
+				write!(f, "No known log files found in '{}'", path.display())
+			}
+			LogParseError::InvalidPassword(path) => {
+				write!(f, "No known password decrypts '{}'", path.display())
+			}
+			LogParseError::Cancelled => write!(f, "Parsing was cancelled"),
 		}
 	}
 }
@@ -41,43 +68,407 @@ impl From<std::io::Error> for LogParseError {
 	}
 }
 
-pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, LogParseError> {
+// Parser chosen for a file, either by the user's format definitions or by Sherlog
+pub enum FileFormat<'a> {
+	Custom(&'a custom_format::CustomFormat),
+	Builtin(detect::LogFormat),
+}
+
+fn file_format<'a>(
+	path: &std::path::PathBuf,
+	custom_formats: &'a [custom_format::CustomFormat],
+) -> Result<(FileFormat<'a>, Option<detect::Detection>), LogParseError> {
+	format_of(path, custom_formats, || std::fs::File::open(path))
+}
+
+// `path` is only used for its name, the contents are read from what `open` returns.
+// That is the file itself, or the beginning of its decompressed contents.
+// The detection is returned if the format was recognized by the contents.
+fn format_of<'a, R: std::io::Read + std::io::Seek>(
+	path: &std::path::Path,
+	custom_formats: &'a [custom_format::CustomFormat],
+	open: impl Fn() -> std::io::Result<R>,
+) -> Result<(FileFormat<'a>, Option<detect::Detection>), LogParseError> {
+	//User-defined formats take precedence, so users can take over any file name
+	for format in custom_formats {
+		if format.matches_file(path) {
+			log::info!("Using log format '{}' for {}", format.name, path.display());
+			return Ok((FileFormat::Custom(format), None));
+		}
+	}
+
+	let mut detection = None;
+	let mut detect_contents = |reader: R| -> Result<Option<detect::LogFormat>, LogParseError> {
+		detection = detect_format(path, reader)?;
+		Ok(detection.map(|detection| detection.format))
+	};
+
 	let extension = path.extension();
-	if let Some(extension) = extension {
+	let log_format = if let Some(extension) = extension {
 		match extension.to_string_lossy().to_lowercase().as_ref() {
 			// ../logfiles/example.glog
-			"glog" => {
-				glog::from_file(&path).map_err(LogParseError::IoError)
-			}
+			"glog" => detect::LogFormat::Glog,
+			// ../logfiles/example.xlog
+			"xlog" => detect::LogFormat::Xlog,
+			// ../logfiles/service.jsonl
+			"jsonl" | "ndjson" => detect::LogFormat::JsonLines,
 			// ../logfiles/logfile1.sfile
-			"sfile" | "lfile" => sfile::from_file(&path).map_err(LogParseError::IoError),
-			"txt" => {
-                let file = std::fs::File::open(path)?;
-                if robot_log::is_robot_log(file) {
-                    robot_log::from_file(&path).map_err(LogParseError::IoError)
-                } else {
-                    Err(LogParseError::UnrecognizedLogFile(
-                        path.clone(),
-                    ))
-                }
-            }
-			"log" => {
-                let file = std::fs::File::open(path)?;
-                if robot_log::is_robot_log(file) {
-                    robot_log::from_file(&path).map_err(LogParseError::IoError)
-                } else {
-                    Err(LogParseError::UnrecognizedLogFile(
-                        path.clone(),
-                    ))
-                }
-            }
-			//TODO: Implement heuristic, more file types
-			_ => Err(LogParseError::UnrecognizedFileExtension(
-				extension.to_os_string(),
-			)),
+			"sfile" | "lfile" => detect::LogFormat::Sfile,
+			// ../logfiles/bundle.tar, also as bundle.tar.gz
+			"tar" => detect::LogFormat::Tar,
+			// ../logfiles/gateway.syslog
+			"syslog" => detect::LogFormat::Syslog,
+			// ../logfiles/output.xml
+			"xml" => {
+				if robot_xml::is_robot_xml(open()?) {
+					detect::LogFormat::RobotXml
+				} else {
+					return Err(LogParseError::UnrecognizedLogFile(path.to_path_buf()));
+				}
+			}
+			"txt" | "log" => {
+				if robot_log::is_robot_log(open()?) {
+					detect::LogFormat::Robot
+				} else {
+					detect_contents(open()?)?
+						.ok_or_else(|| LogParseError::UnrecognizedLogFile(path.to_path_buf()))?
+				}
+			}
+			_ => detect_contents(open()?)?.ok_or_else(|| {
+				LogParseError::UnrecognizedFileExtension(extension.to_os_string())
+			})?,
+		}
+	} else {
+		detect_contents(open()?)?.ok_or(LogParseError::NoFileExtension)?
+	};
+	Ok((FileFormat::Builtin(log_format), detection))
+}
+
+fn from_file(
+	path: &std::path::PathBuf,
+	custom_formats: &[custom_format::CustomFormat],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	progress.set_current(path.display().to_string());
+	if let Some(compression) = compression::detect(path)? {
+		return from_compressed_file(path, compression, custom_formats, sfile_rules, progress);
+	}
+	let (format, detection) = file_format(path, custom_formats)?;
+	let mut log_source = match format {
+		FileFormat::Custom(format) => format.parse_file(path, progress)?,
+		FileFormat::Builtin(log_format) => match log_format {
+			detect::LogFormat::Sfile => sfile::from_file(path, sfile_rules, progress)?,
+			detect::LogFormat::Tar => tar::from_file(path, sfile_rules, progress)?,
+			detect::LogFormat::Glog => glog::from_file(path, progress)?,
+			detect::LogFormat::Xlog => xlog::from_file(path, progress)?,
+			detect::LogFormat::Rds => rds_log::from_file(path, progress)?,
+			detect::LogFormat::ScanLib => scanlib_log::from_file(path, progress)?,
+			detect::LogFormat::Robot => robot_log::from_file(path, progress)?,
+			detect::LogFormat::RobotXml => robot_xml::from_file(path, progress)?,
+			detect::LogFormat::JsonLines => jsonl_log::from_file(path, progress)?,
+			detect::LogFormat::Syslog => syslog::from_file(path, progress)?,
+			detect::LogFormat::Slog2 => slog2_log::from_file(path, progress)?,
+		},
+	};
+	report_detection(&mut log_source, detection);
+	Ok(log_source)
+}
+
+// Compressed files are parsed while they are decompressed. Their format is recognized
+// by the name without the compression extension (debug.txt.gz is a debug.txt) or by
+// the decompressed contents.
+fn from_compressed_file(
+	path: &std::path::PathBuf,
+	compression: compression::Compression,
+	custom_formats: &[custom_format::CustomFormat],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let mut sample = Vec::new();
+	compression::decoder(compression, std::fs::File::open(path)?)?
+		.take(detect::SAMPLE_SIZE)
+		.read_to_end(&mut sample)?;
+	let inner_path = compression::inner_path(path);
+	let (format, detection) = format_of(&inner_path, custom_formats, || {
+		Ok(std::io::Cursor::new(sample.as_slice()))
+	})?;
+
+	let file = std::fs::File::open(path)?;
+	let last_year = datetime_utils::modified_year(&file);
+	let mut reader = compression::decoder(compression, progress.reader(file))?;
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	let mut log_source = match format {
+		FileFormat::Custom(format) => format.to_log_entries(reader, root),
+		FileFormat::Builtin(log_format) => match log_format {
+			detect::LogFormat::Sfile => {
+				//Archives need to seek, which compressed streams cannot
+				let mut bytes = Vec::new();
+				reader.read_to_end(&mut bytes)?;
+				sfile::from_bytes(&bytes, path, sfile_rules, progress)?
+			}
+			detect::LogFormat::Tar => tar::to_log_source(reader, root.name, sfile_rules, progress)?,
+			detect::LogFormat::Glog => {
+				let mut log_source = glog::to_log_entries(reader, root);
+				glog::adjust_sensor_timestamps(&mut log_source);
+				log_source
+			}
+			detect::LogFormat::Xlog => xlog::to_log_entries(reader, root),
+			detect::LogFormat::Rds => rds_log::to_log_entries(reader, root),
+			detect::LogFormat::ScanLib => scanlib_log::to_log_entries(reader, root),
+			detect::LogFormat::Robot => robot_log::to_log_entries(reader, root.name)?,
+			detect::LogFormat::RobotXml => robot_xml::to_log_entries(reader, root),
+			detect::LogFormat::JsonLines => {
+				jsonl_log::to_log_entries(reader, root, &jsonl_log::FieldMapping::load())
+			}
+			detect::LogFormat::Syslog => syslog::to_log_entries(reader, root, last_year),
+			detect::LogFormat::Slog2 => slog2_log::to_log_entries(reader, root, last_year),
+		},
+	};
+	report_detection(&mut log_source, detection);
+	Ok(log_source)
+}
+
+// Tells the user which parser was picked by looking at the contents of the file,
+// as a diagnostic of the file's log source
+fn report_detection(log_source: &mut model::LogSource, detection: Option<detect::Detection>) {
+	if let Some(detection) = detection {
+		log_source.diagnostics.push(model::Diagnostic {
+			source_name: log_source.name.clone(),
+			location: model::DiagnosticLocation::ByteOffset(0),
+			kind: model::DiagnosticKind::DetectedFormat,
+			snippet: format!(
+				"{:?} (confidence {:.0}%)",
+				detection.format,
+				detection.confidence * 100.0
+			),
+		});
+	}
+}
+
+// Parses a file and accounts for all of its bytes in the progress, also if it was
+// skipped or not read to the end
+fn from_file_with_progress(
+	path: &std::path::PathBuf,
+	custom_formats: &[custom_format::CustomFormat],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let bytes_done = progress.bytes_done() + file_size(path);
+	let result = from_file(path, custom_formats, sfile_rules, progress);
+	progress.set_bytes_done(bytes_done);
+	result
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+	std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+// Sum of the file sizes below a path, used as the total of the parse progress
+fn total_size(path: &std::path::Path) -> u64 {
+	//Symbolic links to directories are not followed, same as in `from_dir_entry`
+	if std::fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+		std::fs::read_dir(path).map_or(0, |dir| {
+			dir.filter_map(|dir_entry| dir_entry.ok())
+				.map(|dir_entry| total_size(&dir_entry.path()))
+				.sum()
+		})
+	} else {
+		file_size(path)
+	}
+}
+
+// Opens one or more files and directories as a single log source tree.
+// A single file is parsed on its own. Directories are searched recursively,
+// their tree of log sources mirrors the folder structure. Files that are not
+// recognized as logs are skipped, which is only an error if nothing is left at all.
+// Returns LogParseError::Cancelled if the progress was cancelled meanwhile.
+// The caller adds the size of the paths to the progress total.
+fn from_paths(
+	paths: &[std::path::PathBuf],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let custom_formats = custom_format::load_formats();
+	let result = match paths {
+		[] => Err(LogParseError::NoLogFilesFound(std::path::PathBuf::new())),
+		[path] if !path.is_dir() => {
+			from_file_with_progress(path, &custom_formats, sfile_rules, progress)
+		}
+		[path] => from_dir(path, &custom_formats, sfile_rules, progress).and_then(|log_source| {
+			log_source.ok_or_else(|| LogParseError::NoLogFilesFound(path.clone()))
+		}),
+		paths => {
+			let mut v = Vec::<model::LogSource>::new();
+			let mut diagnostics = Vec::<model::Diagnostic>::new();
+			for path in paths {
+				if progress.is_cancelled() {
+					break;
+				}
+				if let Some(log_source) = from_dir_entry(
+					path,
+					&custom_formats,
+					sfile_rules,
+					progress,
+					&mut diagnostics,
+				) {
+					v.push(log_source);
+				}
+			}
+			//All paths come from the same folder when opened via file dialog or file manager
+			let parent = paths[0].parent().unwrap_or(&paths[0]);
+			if v.is_empty() {
+				Err(LogParseError::NoLogFilesFound(parent.to_path_buf()))
+			} else {
+				//Case insensitive sort by log source name
+				v.sort_by_key(|source| source.name.to_lowercase());
+				Ok(model::LogSource {
+					name: parent
+						.file_name()
+						.unwrap_or(parent.as_os_str())
+						.to_string_lossy()
+						.to_string(),
+					children: { model::LogSourceContents::Sources(v) },
+					diagnostics,
+				})
+			}
 		}
+	};
+	if progress.is_cancelled() {
+		return Err(LogParseError::Cancelled);
+	}
+	result
+}
+
+// Same as `from_paths`, but if a single text log file is opened, it is kept open
+// so that entries appended later on can be read in follow mode.
+pub fn from_paths_follow(
+	paths: &[std::path::PathBuf],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<(model::LogSource, Option<follow::Follower>), LogParseError> {
+	progress.add_total(paths.iter().map(|path| total_size(path)).sum());
+	if let [path] = paths {
+		//Compressed files are not followed, they are not appended to in place
+		if path.is_file() && compression::detect(path)?.is_none() {
+			let custom_formats = custom_format::load_formats();
+			let (format, detection) = file_format(path, &custom_formats)?;
+			progress.set_current(path.display().to_string());
+			if let Some((follower, mut log_source)) = follow::Follower::new(path, format, progress)?
+			{
+				if progress.is_cancelled() {
+					return Err(LogParseError::Cancelled);
+				}
+				report_detection(&mut log_source, detection);
+				return Ok((log_source, Some(follower)));
+			}
+		}
+	}
+	from_paths(paths, sfile_rules, progress).map(|log_source| (log_source, None))
+}
+
+// Returns Ok(None) if the directory does not contain any recognized log files
+fn from_dir(
+	path: &std::path::Path,
+	custom_formats: &[custom_format::CustomFormat],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+) -> Result<Option<model::LogSource>, LogParseError> {
+	let mut v = Vec::<model::LogSource>::new();
+	let mut diagnostics = Vec::<model::Diagnostic>::new();
+	for dir_entry in std::fs::read_dir(path)? {
+		if progress.is_cancelled() {
+			break;
+		}
+		if let Some(log_source) = from_dir_entry(
+			&dir_entry?.path(),
+			custom_formats,
+			sfile_rules,
+			progress,
+			&mut diagnostics,
+		) {
+			v.push(log_source);
+		}
+	}
+	if v.is_empty() {
+		return Ok(None);
+	}
+	//Case insensitive sort by log source name
+	v.sort_by_key(|source| source.name.to_lowercase());
+	Ok(Some(model::LogSource {
+		name: path
+			.file_name()
+			.unwrap_or(path.as_os_str())
+			.to_string_lossy()
+			.to_string(),
+		children: { model::LogSourceContents::Sources(v) },
+		diagnostics,
+	}))
+}
+
+// Parses a file or directory inside a directory. Failures are skipped, so a single
+// broken file does not prevent the rest of the folder from opening. Unreadable files
+// are added to `diagnostics`, which belong to the log source of the directory.
+fn from_dir_entry(
+	path: &std::path::PathBuf,
+	custom_formats: &[custom_format::CustomFormat],
+	sfile_rules: &SfileRules,
+	progress: &Progress,
+	diagnostics: &mut Vec<model::Diagnostic>,
+) -> Option<model::LogSource> {
+	//Symbolic links to directories are not followed, they may form cycles
+	let is_dir = std::fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+	let result = if is_dir {
+		from_dir(path, custom_formats, sfile_rules, progress)
+	} else if path.is_file() {
+		from_file_with_progress(path, custom_formats, sfile_rules, progress).map(Some)
 	} else {
-		//TODO: Implement heuristic
-		Err(LogParseError::NoFileExtension)
+		Ok(None)
+	};
+	match result {
+		Ok(log_source) => log_source,
+		Err(err @ (LogParseError::IoError(_) | LogParseError::InvalidPassword(_))) => {
+			log::warn!("Skipping {}: {}", path.display(), err);
+			diagnostics.push(model::Diagnostic {
+				source_name: path
+					.file_name()
+					.unwrap_or(path.as_os_str())
+					.to_string_lossy()
+					.to_string(),
+				location: model::DiagnosticLocation::ByteOffset(0),
+				kind: model::DiagnosticKind::UnparsableFile,
+				snippet: err.to_string(),
+			});
+			None
+		}
+		Err(err) => {
+			log::info!("Skipping {}: {}", path.display(), err);
+			None
+		}
 	}
 }
+
+// Picks the parser by looking at the file contents instead of the file extension.
+// Returns Ok(None) if no parser is confident enough to handle the file.
+fn detect_format(
+	path: &std::path::Path,
+	reader: impl std::io::Read,
+) -> Result<Option<detect::Detection>, LogParseError> {
+	let detection = if let Some(detection) = detect::detect(reader)? {
+		detection
+	} else {
+		log::warn!("Could not detect log format of {}", path.display());
+		return Ok(None);
+	};
+	log::info!(
+		"Detected log format of {}: {:?} (confidence {:.0}%)",
+		path.display(),
+		detection.format,
+		detection.confidence * 100.0
+	);
+	Ok(Some(detection))
+}
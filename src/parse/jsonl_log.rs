@@ -0,0 +1,379 @@
+use super::super::model;
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use std::io::BufRead;
+use std::io::BufReader;
+
+// JSON Lines parser -----------------------------------------------------------------
+// One JSON object per line (.jsonl / .ndjson). Example:
+// {"ts":"2023-05-04T10:00:00.123Z","level":"warn","logger":"api.auth","msg":"Slow login","user":42}
+//
+// The keys used for timestamp, severity, message and log source can be changed in
+// `jsonl.toml` in any Sherlog configuration directory (see `config::config_dirs`):
+//
+// timestamp_keys = ["ts", "time", "timestamp", "@timestamp"]
+// severity_keys = ["level", "severity"]
+// message_keys = ["msg", "message"]
+// source_key = "logger"
+// source_separator = "."
+//
+// For each kind, the first key present in the object is used. All remaining keys
+// become custom fields. Lines that are not JSON objects are kept as plain messages.
+// ------------------------------------------------------------------------------------
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct FieldMapping {
+	pub timestamp_keys: Vec<String>,
+	pub severity_keys: Vec<String>,
+	pub message_keys: Vec<String>,
+	pub source_key: Option<String>,
+	pub source_separator: Option<String>,
+}
+
+impl Default for FieldMapping {
+	fn default() -> FieldMapping {
+		FieldMapping {
+			timestamp_keys: vec![
+				"ts".to_string(),
+				"time".to_string(),
+				"timestamp".to_string(),
+				"@timestamp".to_string(),
+			],
+			severity_keys: vec!["level".to_string(), "severity".to_string()],
+			message_keys: vec!["msg".to_string(), "message".to_string()],
+			source_key: Some("logger".to_string()),
+			source_separator: Some(".".to_string()),
+		}
+	}
+}
+
+impl FieldMapping {
+	// Loads the field mapping with the highest precedence, or the defaults if there is none
+	pub fn load() -> FieldMapping {
+		if let Some(path) = crate::config::config_files("jsonl.toml").pop() {
+			match std::fs::read_to_string(&path)
+				.map_err(|e| e.to_string())
+				.and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()))
+			{
+				Ok(mapping) => {
+					log::info!("Loaded JSON Lines field mapping from {}", path.display());
+					return mapping;
+				}
+				Err(e) => log::warn!("Ignoring {}: {}", path.display(), e),
+			}
+		}
+		FieldMapping::default()
+	}
+}
+
+pub fn to_log_entries(
+	reader: impl std::io::Read,
+	mut root: model::LogSource,
+	mapping: &FieldMapping,
+) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	JsonLinesParser::new(mapping.clone()).parse(reader, &mut root, &mut builder);
+	builder.build(root)
+}
+
+// State of the parser between the lines of a log. Follow mode keeps it between the
+// chunks that are appended to the file.
+pub struct JsonLinesParser {
+	mapping: FieldMapping,
+	last_timestamp: Option<DateTime<Utc>>,
+	entry_diagnostics: EntryDiagnostics,
+	offset: u64, //Of the next line
+}
+
+impl JsonLinesParser {
+	pub fn new(mapping: FieldMapping) -> JsonLinesParser {
+		JsonLinesParser {
+			mapping,
+			last_timestamp: None,
+			entry_diagnostics: EntryDiagnostics::default(),
+			offset: 0,
+		}
+	}
+
+	// Parses the lines of `reader` into `builder`. Every line is an entry of its own.
+	pub fn parse(
+		&mut self,
+		reader: impl std::io::Read,
+		root: &mut model::LogSource,
+		builder: &mut SourceTreeBuilder,
+	) {
+		let mut bufreader = BufReader::new(reader);
+		let mut buf = Vec::<u8>::with_capacity(512);
+
+		loop {
+			let line_offset = self.offset;
+			buf.clear();
+			match bufreader.read_until(b'\n', &mut buf) {
+				Ok(0) => break,
+				Ok(bytes_read) => self.offset += bytes_read as u64,
+				Err(e) => {
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::ReadError,
+						&e.to_string(),
+					);
+					break;
+				}
+			}
+			let line = String::from_utf8_lossy(&buf);
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+
+			let (log_entry, path) = match serde_json::from_str::<serde_json::Value>(line) {
+				Ok(serde_json::Value::Object(object)) => {
+					to_log_entry(object, &self.mapping, &mut self.entry_diagnostics)
+				}
+				_ => {
+					//Not a JSON object. Keep the line, with the timestamp of its predecessor
+					//so it stays close to its context when sorted.
+					self.entry_diagnostics
+						.report(DiagnosticKind::UnrecognizedLine, line);
+					let log_entry = model::LogEntry {
+						timestamp: self.last_timestamp.unwrap_or_default(),
+						severity: model::LogLevel::Info,
+						message: line.to_string(),
+						..Default::default()
+					};
+					(log_entry, Vec::new())
+				}
+			};
+			self.last_timestamp = Some(log_entry.timestamp);
+			builder.push(&path, log_entry, &mut self.entry_diagnostics);
+		}
+	}
+}
+
+fn to_log_entry(
+	mut object: serde_json::Map<String, serde_json::Value>,
+	mapping: &FieldMapping,
+	diagnostics: &mut EntryDiagnostics,
+) -> (model::LogEntry, Vec<String>) {
+	let mut log_entry = model::LogEntry {
+		severity: model::LogLevel::Info,
+		..Default::default()
+	};
+
+	if let Some(value) = take_first(&mut object, &mapping.timestamp_keys) {
+		if let Some(timestamp) = parse_timestamp(&value) {
+			log_entry.timestamp = timestamp;
+		} else {
+			diagnostics.report(DiagnosticKind::MalformedTimestamp, &value.to_string());
+		}
+	}
+
+	if let Some(value) = take_first(&mut object, &mapping.severity_keys) {
+		if let Some(severity) = parse_severity(&value) {
+			log_entry.severity = severity;
+		} else {
+			diagnostics.report(DiagnosticKind::InvalidSeverity, &value.to_string());
+		}
+	}
+
+	if let Some(value) = take_first(&mut object, &mapping.message_keys) {
+		log_entry.message = match value {
+			serde_json::Value::String(message) => message,
+			other => other.to_string(),
+		};
+	}
+
+	let mut path = Vec::new();
+	if let Some(source_key) = &mapping.source_key {
+		if let Some(value) = object.remove(source_key) {
+			let source = match value {
+				serde_json::Value::String(source) => source,
+				other => other.to_string(),
+			};
+			if let Some(separator) = mapping.source_separator.as_deref().filter(|s| !s.is_empty()) {
+				path.extend(
+					source
+						.split(separator)
+						.filter(|name| !name.is_empty())
+						.map(|name| name.to_string()),
+				);
+			} else if !source.is_empty() {
+				path.push(source);
+			}
+		}
+	}
+
+	for (key, value) in object {
+		if let Some(field) = to_custom_field(value) {
+			log_entry
+				.custom_fields
+				.insert(std::borrow::Cow::Owned(key), field);
+		}
+	}
+
+	(log_entry, path)
+}
+
+fn take_first(
+	object: &mut serde_json::Map<String, serde_json::Value>,
+	keys: &[String],
+) -> Option<serde_json::Value> {
+	keys.iter().find_map(|key| object.remove(key))
+}
+
+fn parse_timestamp(value: &serde_json::Value) -> Option<chrono::DateTime<Utc>> {
+	match value {
+		serde_json::Value::String(s) => {
+			if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+				return Some(dt.with_timezone(&Utc));
+			}
+			//No offset in the log, assume UTC
+			["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+				.iter()
+				.find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+				.map(|ndt| DateTime::<Utc>::from_utc(ndt, Utc))
+		}
+		serde_json::Value::Number(n) => {
+			//Unix epoch. Guess the unit from the magnitude: seconds, ms, us or ns.
+			let epoch = n.as_f64()?;
+			let nanos = if epoch.abs() < 1e11 {
+				epoch * 1e9
+			} else if epoch.abs() < 1e14 {
+				epoch * 1e6
+			} else if epoch.abs() < 1e17 {
+				epoch * 1e3
+			} else {
+				epoch
+			};
+			let secs = (nanos / 1e9).floor();
+			let subsec_nanos = (nanos - secs * 1e9) as u32;
+			NaiveDateTime::from_timestamp_opt(secs as i64, subsec_nanos)
+				.map(|ndt| DateTime::<Utc>::from_utc(ndt, Utc))
+		}
+		_ => None,
+	}
+}
+
+fn parse_severity(value: &serde_json::Value) -> Option<model::LogLevel> {
+	match value {
+		serde_json::Value::String(s) => model::LogLevel::from_name(s),
+		//Numeric levels as used by pino and bunyan
+		serde_json::Value::Number(n) => match n.as_u64()? {
+			0..=10 => Some(model::LogLevel::Trace),
+			11..=20 => Some(model::LogLevel::Debug),
+			21..=30 => Some(model::LogLevel::Info),
+			31..=40 => Some(model::LogLevel::Warning),
+			41..=50 => Some(model::LogLevel::Error),
+			_ => Some(model::LogLevel::Critical),
+		},
+		_ => None,
+	}
+}
+
+fn to_custom_field(value: serde_json::Value) -> Option<model::CustomField> {
+	match value {
+		serde_json::Value::Null => None,
+		serde_json::Value::Bool(b) => Some(model::CustomField::String(b.to_string())),
+		serde_json::Value::Number(n) => {
+			if let Some(i) = n.as_i64() {
+				Some(model::CustomField::Int64(i))
+			} else if let Some(u) = n.as_u64() {
+				Some(model::CustomField::UInt64(u))
+			} else {
+				n.as_f64().map(model::CustomField::Float64)
+			}
+		}
+		serde_json::Value::String(s) => Some(model::CustomField::String(s)),
+		other => Some(model::CustomField::String(other.to_string())),
+	}
+}
+
+// from_file -------------------------------------------------------------------------
+// Reads a JSON Lines file from disk and returns the parsed log source, using the
+// field mapping from the configuration directories.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = progress.reader(std::fs::File::open(path)?);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(file, root, &FieldMapping::load()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_jsonl_log() {
+		let log = concat!(
+			r#"{"ts":"2023-05-04T10:00:00.123Z","level":"warn","logger":"api.auth","msg":"Slow","user":42}"#,
+			"\n",
+			"not json at all\n",
+			r#"{"time":1683194400,"severity":"ERROR","message":"Down","ok":false}"#,
+			"\n",
+		);
+		let root = model::LogSource {
+			name: "service.jsonl".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(log.as_bytes(), root, &FieldMapping::default());
+
+		let sources = match root.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(sources.len(), 2);
+		assert_eq!(sources[0].name, "api");
+		assert_eq!(sources[1].name, "Unknown (None)");
+
+		let unknown = match &sources[1].children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(unknown.len(), 2);
+		assert_eq!(unknown[0].message, "not json at all");
+		assert_eq!(
+			unknown[0].timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			"2023-05-04T10:00:00.123Z"
+		);
+		assert_eq!(unknown[1].severity, model::LogLevel::Error);
+		assert_eq!(
+			unknown[1].timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+			"2023-05-04T10:00:00Z"
+		);
+
+		let auth = match &sources[0].children {
+			model::LogSourceContents::Sources(v) => &v[0],
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(auth.name, "auth");
+		let entries = match &auth.children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(entries[0].severity, model::LogLevel::Warning);
+		assert_eq!(entries[0].message, "Slow");
+		assert!(matches!(
+			entries[0].custom_fields.get("user"),
+			Some(model::CustomField::Int64(42))
+		));
+		assert!(!entries[0].custom_fields.contains_key("logger"));
+	}
+}
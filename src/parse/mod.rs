@@ -1,11 +1,24 @@
+pub mod compression; //Transparent decompression of single files
+pub mod custom_format; //User-defined text formats
+pub mod follow; //Follow mode for growing files
 pub mod glog;
 pub mod io; //Central hub for log parser io
+pub mod jsonl_log;
 pub mod rds_log;
 pub mod robot_log;
+pub mod robot_xml; //Robot Framework output.xml
 pub mod scanlib_log;
 pub mod sfile;
+pub mod sfile_rules; //Configurable categorisation of sfile members
+pub mod slog2_log; //QNX slog2info output
+pub mod syslog;
+pub mod tar; //Tar archives with the same layout as sfiles
 pub mod xlog;
 
 pub mod datetime_utils;
+pub mod diagnostics; //Problems found in the parsed input
+pub mod detect;
+pub mod progress; //Progress reporting and cancellation of running parsers
+pub mod source_tree;
 
-pub use self::io::from_file;
+pub use self::io::from_paths_follow;
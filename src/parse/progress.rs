@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Parse progress --------------------------------------------------------------------
+// Shared between the thread that parses and the GUI that shows a progress dialog.
+// Parsers read through `Progress::reader`, which counts the bytes consumed and ends
+// the input early once parsing was cancelled. Every parser handles the end of its
+// input, so a cancelled parse simply returns what it has read so far and the
+// caller checks `is_cancelled` to throw it away.
+// ------------------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct Progress {
+	bytes_total: AtomicU64,
+	bytes_done: AtomicU64,
+	current: Mutex<String>, //File or archive member being parsed
+	cancelled: AtomicBool,
+}
+
+impl Progress {
+	pub fn new() -> Progress {
+		Default::default()
+	}
+
+	pub fn add_total(&self, bytes: u64) {
+		self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+	}
+
+	pub fn bytes_done(&self) -> u64 {
+		self.bytes_done.load(Ordering::Relaxed)
+	}
+
+	// Used to correct the count after a file, e.g. if it was not read to the end
+	pub fn set_bytes_done(&self, bytes: u64) {
+		self.bytes_done.store(bytes, Ordering::Relaxed);
+	}
+
+	// Between 0.0 and 1.0
+	pub fn fraction(&self) -> f64 {
+		let total = self.bytes_total.load(Ordering::Relaxed);
+		if total == 0 {
+			return 0.0;
+		}
+		(self.bytes_done() as f64 / total as f64).min(1.0)
+	}
+
+	pub fn current(&self) -> String {
+		self.current.lock().unwrap().clone()
+	}
+
+	pub fn set_current(&self, name: String) {
+		*self.current.lock().unwrap() = name;
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+	}
+
+	// Counts the bytes read and reports the end of input once cancelled
+	pub fn reader<R>(&self, inner: R) -> ProgressReader<'_, R> {
+		ProgressReader {
+			inner,
+			progress: self,
+			cancellable: true,
+			counting: Arc::new(AtomicBool::new(true)),
+		}
+	}
+
+	// Only counts the bytes read. For archives, where a premature end of input
+	// would be reported as a corrupt archive. Check `is_cancelled` between members.
+	pub fn archive_reader<R>(&self, inner: R) -> ProgressReader<'_, R> {
+		ProgressReader {
+			inner,
+			progress: self,
+			cancellable: false,
+			counting: Arc::new(AtomicBool::new(true)),
+		}
+	}
+
+	// Like `archive_reader`, but the bytes are only counted once the returned flag is
+	// set. For archives that are opened again, e.g. by another thread, so that their
+	// directory is not counted twice.
+	pub fn reopened_archive_reader<R>(&self, inner: R) -> (ProgressReader<'_, R>, Arc<AtomicBool>) {
+		let counting = Arc::new(AtomicBool::new(false));
+		let reader = ProgressReader {
+			inner,
+			progress: self,
+			cancellable: false,
+			counting: counting.clone(),
+		};
+		(reader, counting)
+	}
+
+	// Only reports the end of input once cancelled. For archive members, so that a large
+	// member can be cancelled while it is parsed. The archive reader counts the bytes.
+	pub fn member_reader<R>(&self, inner: R) -> ProgressReader<'_, R> {
+		ProgressReader {
+			inner,
+			progress: self,
+			cancellable: true,
+			counting: Arc::new(AtomicBool::new(false)),
+		}
+	}
+}
+
+pub struct ProgressReader<'a, R> {
+	inner: R,
+	progress: &'a Progress,
+	cancellable: bool,
+	counting: Arc<AtomicBool>,
+}
+
+impl<'a, R: std::io::Read> std::io::Read for ProgressReader<'a, R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if self.cancellable && self.progress.is_cancelled() {
+			return Ok(0);
+		}
+		let bytes_read = self.inner.read(buf)?;
+		if self.counting.load(Ordering::Relaxed) {
+			self.progress
+				.bytes_done
+				.fetch_add(bytes_read as u64, Ordering::Relaxed);
+		}
+		Ok(bytes_read)
+	}
+}
+
+impl<'a, R: std::io::Seek> std::io::Seek for ProgressReader<'a, R> {
+	fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+		self.inner.seek(pos)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::Read;
+
+	#[test]
+	fn test_progress_reader() {
+		let progress = Progress::new();
+		progress.add_total(8);
+		let mut reader = progress.reader(&b"abcdefgh"[..]);
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 4);
+		assert_eq!(progress.fraction(), 0.5);
+
+		let mut member = progress.member_reader(&b"ijkl"[..]);
+		assert_eq!(member.read(&mut buf).unwrap(), 4);
+		assert_eq!(progress.fraction(), 0.5);
+
+		progress.cancel();
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+		assert_eq!(member.read(&mut buf).unwrap(), 0);
+		assert_eq!(progress.fraction(), 0.5);
+
+		let (mut reopened, counting) = progress.reopened_archive_reader(&b"mnop"[..]);
+		assert_eq!(reopened.read(&mut buf[..2]).unwrap(), 2);
+		assert_eq!(progress.fraction(), 0.5);
+		counting.store(true, Ordering::Relaxed);
+		assert_eq!(reopened.read(&mut buf[..2]).unwrap(), 2);
+		assert_eq!(progress.fraction(), 0.75);
+	}
+}
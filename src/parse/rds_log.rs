@@ -1,4 +1,8 @@
 use super::super::model;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
 
 extern crate chrono;
 use chrono::{DateTime, NaiveDateTime, Utc};
@@ -20,300 +24,319 @@ pub fn parse_rds_datetime(dt_string: &str) -> Option<chrono::DateTime<Utc>> {
 	}
 }
 
-fn read_until_pipe_or_newline(mut reader: impl std::io::Read, buf: &mut Vec<u8>) -> Result<usize> {
+// Like `BufRead::read_until`, but stops at whichever of b'|' and b'\n' comes first
+fn read_until_pipe_or_newline(reader: &mut impl BufRead, buf: &mut Vec<u8>) -> Result<usize> {
 	let mut bytes_read = 0;
-	let mut byte = [0; 1];
 	loop {
-		match reader.read(&mut byte) {
-			Ok(0) => return Ok(bytes_read),
+		let available = match reader.fill_buf() {
+			Ok(available) => available,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
 			Err(e) => return Err(e),
-			Ok(_) => {
-				bytes_read += 1;
-				let byte = byte[0];
-				buf.push(byte);
-				if byte == b'|' || byte == b'\n' {
-					return Ok(bytes_read);
-				}
-			}
+		};
+		let (found, used) = match memchr::memchr2(b'|', b'\n', available) {
+			Some(offset) => (true, offset + 1),
+			None => (false, available.len()),
+		};
+		buf.extend_from_slice(&available[..used]);
+		reader.consume(used);
+		bytes_read += used;
+		if found || used == 0 {
+			return Ok(bytes_read);
 		}
 	}
 }
 
-pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) -> model::LogSource {
-	let mut log_entries = Vec::<model::LogEntry>::new();
-	let mut bufreader = BufReader::new(reader);
-	let mut buf = Vec::<u8>::with_capacity(512);
-	let mut parser_state = RdsLogParserState::ExpectDatetime;
+pub fn to_log_entries(reader: impl std::io::Read, root: model::LogSource) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	let mut parser = RdsParser::new();
+	parser.parse(reader, &mut builder);
+	parser.flush_entry(&mut builder);
+	builder.build(root)
+}
 
-	let mut log_entry = model::LogEntry {
-		..Default::default()
-	};
+// State of the parser between the fields of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the fields and the message of the last
+// entry may still be continued by the next chunk.
+pub struct RdsParser {
+	state: RdsLogParserState,
+	buf: Vec<u8>,
+	line_start: usize, //In `buf`, of the line that is read with ExpectDatetimeTentative
+	log_entry: model::LogEntry,
+	entry_diagnostics: EntryDiagnostics,
+}
 
-	loop {
-		match parser_state {
-			RdsLogParserState::ExpectDatetime => {
-				if let Ok(bytes_read) = bufreader.read_until(b'|', &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'|') {
-							buf.pop();
-							let buf_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &buf_str {
-								log::warn!("MALFORMED UTF-8 in datetime: {}", owned_str);
-							} else {
-								if let Some(timestamp) = parse_rds_datetime(&buf_str) {
-									log_entry.timestamp = timestamp;
+impl Default for RdsParser {
+	fn default() -> RdsParser {
+		RdsParser::new()
+	}
+}
+
+impl RdsParser {
+	pub fn new() -> RdsParser {
+		RdsParser {
+			state: RdsLogParserState::ExpectDatetime,
+			buf: Vec::<u8>::with_capacity(512),
+			line_start: 0,
+			log_entry: model::LogEntry {
+				..Default::default()
+			},
+			entry_diagnostics: EntryDiagnostics::default(),
+		}
+	}
+
+	// Parses `reader` into `builder`. Whatever is left at its end is kept, as the next
+	// chunk may continue it. This includes the last entry (see `flush_entry`).
+	pub fn parse(&mut self, reader: impl std::io::Read, builder: &mut SourceTreeBuilder) {
+		let mut bufreader = BufReader::new(reader);
+		let buf = &mut self.buf;
+
+		loop {
+			match self.state {
+				RdsLogParserState::ExpectDatetime => {
+					if let Ok(bytes_read) = bufreader.read_until(b'|', buf) {
+						if bytes_read != 0 {
+							if buf.last() == Some(&b'|') {
+								buf.pop();
+								let buf_str = String::from_utf8_lossy(buf);
+								if let std::borrow::Cow::Owned(owned_str) = &buf_str {
+									self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
 								} else {
-									//TODO: Notify of invalid datetime?
-									log::warn!("MALFORMED Log datetime: {}", buf_str);
+									if let Some(timestamp) = parse_rds_datetime(&buf_str) {
+										self.log_entry.timestamp = timestamp;
+									} else {
+										self.entry_diagnostics.report(DiagnosticKind::MalformedTimestamp, &buf_str);
+									}
 								}
+								buf.clear();
+								self.state = RdsLogParserState::ExpectErrcodeOrSeverity;
+							} else {
+								//TODO: Error, if the log file ended before delimiter was found.
+								break;
 							}
-							buf.clear();
-							parser_state = RdsLogParserState::ExpectErrcodeOrSeverity;
 						} else {
-							//TODO: Error, log file ended before delimiter was found.
+							//Log file is empty. Nothing to do.
 							break;
 						}
 					} else {
-						//Log file is empty. Nothing to do.
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
-			}
-			RdsLogParserState::ExpectErrcodeOrSeverity => {
-				if let Ok(bytes_read) = bufreader.read_until(b'|', &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'|') {
-							buf.pop();
-							let buf_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &buf_str {
-								log::warn!(
-									"MALFORMED UTF-8 in error code / severity: {}",
-									owned_str
-								);
-								parser_state = RdsLogParserState::ExpectSeverity;
-							} else if let Ok(_error_code) = buf_str.trim_start().parse::<u32>() {
-								//Successfully read error code; discard it for now
-								parser_state = RdsLogParserState::ExpectSeverity;
-							} else if let Some(rds_log_sev) = RdsLogSeverity::from_str(&buf_str) {
-								log_entry.severity = normalize_rds_log_sev(rds_log_sev);
-								parser_state = RdsLogParserState::ExpectLogSource;
+				RdsLogParserState::ExpectErrcodeOrSeverity => {
+					if let Ok(bytes_read) = bufreader.read_until(b'|', buf) {
+						if bytes_read != 0 {
+							if buf.last() == Some(&b'|') {
+								buf.pop();
+								let buf_str = String::from_utf8_lossy(buf);
+								if let std::borrow::Cow::Owned(owned_str) = &buf_str {
+									self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
+									self.state = RdsLogParserState::ExpectSeverity;
+								} else if let Ok(error_code) = buf_str.trim_start().parse::<u32>() {
+									self.log_entry.custom_fields.insert(
+										std::borrow::Cow::Borrowed("ErrorCode"),
+										model::CustomField::UInt32(error_code),
+									);
+									self.state = RdsLogParserState::ExpectSeverity;
+								} else if let Some(rds_log_sev) = RdsLogSeverity::from_str(&buf_str) {
+									self.log_entry.severity = normalize_rds_log_sev(rds_log_sev);
+									self.state = RdsLogParserState::ExpectLogSource;
+								} else {
+									self.entry_diagnostics.report(DiagnosticKind::InvalidSeverity, &buf_str);
+									self.state = RdsLogParserState::ExpectSeverity;
+								}
+								buf.clear();
 							} else {
-								//TODO: Notify of invalid error code / severity?
-								log::warn!("MALFORMED error code / severity: {}", buf_str);
-								parser_state = RdsLogParserState::ExpectSeverity;
+								//TODO: Error, if the log file ended before delimiter was found.
+								break;
 							}
-							buf.clear();
 						} else {
-							//TODO: Error, log file ended before delimiter was found.
+							//TODO: Log file cut off... Error.
 							break;
 						}
 					} else {
-						//TODO: Log file cut off... Error.
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
-			}
-			RdsLogParserState::ExpectSeverity => {
-				if let Ok(bytes_read) = bufreader.read_until(b'|', &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'|') {
-							buf.pop();
-							let buf_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &buf_str {
-								log::warn!("MALFORMED UTF-8 in severity: {}", owned_str);
-							} else {
-								if let Some(rds_log_sev) = RdsLogSeverity::from_str(&buf_str) {
-									log_entry.severity = normalize_rds_log_sev(rds_log_sev);
+				RdsLogParserState::ExpectSeverity => {
+					if let Ok(bytes_read) = bufreader.read_until(b'|', buf) {
+						if bytes_read != 0 {
+							if buf.last() == Some(&b'|') {
+								buf.pop();
+								let buf_str = String::from_utf8_lossy(buf);
+								if let std::borrow::Cow::Owned(owned_str) = &buf_str {
+									self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
 								} else {
-									//TODO: Notify of invalid severity?
-									log::warn!("MALFORMED Log severity: {}", buf_str);
+									if let Some(rds_log_sev) = RdsLogSeverity::from_str(&buf_str) {
+										self.log_entry.severity = normalize_rds_log_sev(rds_log_sev);
+									} else {
+										self.entry_diagnostics.report(DiagnosticKind::InvalidSeverity, &buf_str);
+									}
 								}
+								buf.clear();
+								self.state = RdsLogParserState::ExpectLogSource;
+							} else {
+								//TODO: Error, if the log file ended before delimiter was found.
+								break;
 							}
-							buf.clear();
-							parser_state = RdsLogParserState::ExpectLogSource;
 						} else {
-							//TODO: Error, log file ended before delimiter was found.
+							//TODO: Log file cut off... Error.
 							break;
 						}
 					} else {
-						//TODO: Log file cut off... Error.
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
-			}
-			RdsLogParserState::ExpectLogSource => {
-				if let Ok(bytes_read) = bufreader.read_until(b'|', &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'|') {
-							buf.pop();
-							let buf_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &buf_str {
-								log::warn!("MALFORMED UTF-8 in log source: {}", owned_str);
+				RdsLogParserState::ExpectLogSource => {
+					if let Ok(bytes_read) = bufreader.read_until(b'|', buf) {
+						if bytes_read != 0 {
+							if buf.last() == Some(&b'|') {
+								buf.pop();
+								let buf_str = String::from_utf8_lossy(buf);
+								if let std::borrow::Cow::Owned(owned_str) = &buf_str {
+									self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
+								} else {
+									//Successfully read log source; discard it for now
+								}
+								buf.clear();
+								self.state = RdsLogParserState::ExpectMessage;
 							} else {
-								//Successfully read log source; discard it for now
+								//TODO: Error, if the log file ended before delimiter was found.
+								break;
 							}
-							buf.clear();
-							parser_state = RdsLogParserState::ExpectMessage;
 						} else {
-							//TODO: Error, log file ended before delimiter was found.
+							//TODO: Log file cut off... Error.
 							break;
 						}
 					} else {
-						//TODO: Log file cut off... Error.
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
-			}
-			RdsLogParserState::ExpectMessage => {
-				if let Ok(bytes_read) = bufreader.read_until(b'\n', &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'\n') {
-							parser_state = RdsLogParserState::ExpectDatetimeTentative;
+				RdsLogParserState::ExpectMessage => {
+					if let Ok(bytes_read) = bufreader.read_until(b'\n', buf) {
+						if bytes_read != 0 && buf.last() == Some(&b'\n') {
+							self.line_start = buf.len();
+							self.state = RdsLogParserState::ExpectDatetimeTentative;
 						} else {
-							//Log file ended before delimiter was found.
-							let message_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &message_str {
-								log::warn!("MALFORMED UTF-8 in Message: {}", owned_str);
-							}
-							log_entry.message = message_str.to_string();
-							let finalized_log_entry = std::mem::replace(
-								&mut log_entry,
-								model::LogEntry {
-									..Default::default()
-								},
-							);
-							log_entries.push(finalized_log_entry);
+							//End of the data, the message may go on in the next chunk
 							break;
 						}
 					} else {
-						//End of file. Empty message?
-						log_entry.message = "".to_string();
-						let finalized_log_entry = std::mem::replace(
-							&mut log_entry,
-							model::LogEntry {
-								..Default::default()
-							},
-						);
-						log_entries.push(finalized_log_entry);
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
-			}
-			RdsLogParserState::ExpectDatetimeTentative => {
-				let mut prev_size = buf.len();
-				if let Ok(bytes_read) = read_until_pipe_or_newline(&mut bufreader, &mut buf) {
-					if bytes_read != 0 {
-						if buf.last() == Some(&b'\n') {
-							//We are in a multiline message.
-							//Just continue reading with ExpectDatetimeTentative.
-						} else if buf.last() == Some(&b'|') {
-							let mut prev_last_idx = std::cmp::max(prev_size, 1) - 1;
-							#[allow(clippy::len_zero)]
-							{
-								assert!(buf.len() != 0); //We matched Some for the last element
-							}
-							let last_idx = buf.len() - 1;
+				RdsLogParserState::ExpectDatetimeTentative => {
+					let mut prev_size = self.line_start;
+					if let Ok(bytes_read) = read_until_pipe_or_newline(&mut bufreader, buf) {
+						if bytes_read != 0 {
+							if buf.last() == Some(&b'\n') {
+								//We are in a multiline message.
+								//Just continue reading with ExpectDatetimeTentative.
+								self.line_start = buf.len();
+							} else if buf.last() == Some(&b'|') {
+								let mut prev_last_idx = std::cmp::max(prev_size, 1) - 1;
+								#[allow(clippy::len_zero)]
+								{
+									assert!(buf.len() != 0); //We matched Some for the last element
+								}
+								let last_idx = buf.len() - 1;
 
-							//Skip last byte, as it is the pipe symbol b'|'
-							if let Ok(dt_string) =
-								std::str::from_utf8(&buf[prev_last_idx..last_idx])
-							{
-								if let Some(timestamp) = parse_rds_datetime(dt_string) {
-									//Trim line ending from the end of the message
-									if let Some(b'\n') = buf.get(prev_last_idx) {
-										prev_size = prev_last_idx;
-										prev_last_idx = std::cmp::max(prev_size, 1) - 1;
-									}
-									if let Some(b'\r') = buf.get(prev_last_idx) {
-										prev_size = prev_last_idx;
-									}
-									//Emit message
-									let message_str = String::from_utf8_lossy(&buf[..prev_size]);
-									if let std::borrow::Cow::Owned(owned_str) = &message_str {
-										log::warn!("MALFORMED UTF-8 in Message: {}", owned_str);
+								//Skip last byte, as it is the pipe symbol b'|'
+								if let Ok(dt_string) =
+									std::str::from_utf8(&buf[prev_last_idx..last_idx])
+								{
+									if let Some(timestamp) = parse_rds_datetime(dt_string) {
+										//Trim line ending from the end of the message
+										if let Some(b'\n') = buf.get(prev_last_idx) {
+											prev_size = prev_last_idx;
+											prev_last_idx = std::cmp::max(prev_size, 1) - 1;
+										}
+										if let Some(b'\r') = buf.get(prev_last_idx) {
+											prev_size = prev_last_idx;
+										}
+										//Emit message
+										let message_str = String::from_utf8_lossy(&buf[..prev_size]);
+										if let std::borrow::Cow::Owned(owned_str) = &message_str {
+											self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
+										}
+										self.log_entry.message = message_str.to_string();
+										let finalized_log_entry = std::mem::replace(
+											&mut self.log_entry,
+											model::LogEntry {
+												..Default::default()
+											},
+										);
+										builder.push::<&str>(&[], finalized_log_entry, &mut self.entry_diagnostics);
+
+										self.log_entry.timestamp = timestamp;
+										self.state = RdsLogParserState::ExpectErrcodeOrSeverity;
+										buf.clear();
+									} else {
+										//Cannot parse datetime, so it must be the continuation
+										//of a log message...
+										//Just continue reading with ExpectDatetimeTentative.
+										self.line_start = buf.len();
 									}
-									log_entry.message = message_str.to_string();
-									let finalized_log_entry = std::mem::replace(
-										&mut log_entry,
-										model::LogEntry {
-											..Default::default()
-										},
+								} else {
+									self.entry_diagnostics.report(
+										DiagnosticKind::MalformedUtf8,
+										&String::from_utf8_lossy(buf),
 									);
-									log_entries.push(finalized_log_entry);
-
-									log_entry.timestamp = timestamp;
-									parser_state = RdsLogParserState::ExpectErrcodeOrSeverity;
+									self.state = RdsLogParserState::ExpectErrcodeOrSeverity;
 									buf.clear();
-								} else {
-									//Cannot parse datetime, so it must be the continuation
-									//of a log message...
-									//Just continue reading with ExpectDatetimeTentative.
 								}
 							} else {
-								//TODO: Notify of malformed UTF-8?
-								log::warn!(
-									"MALFORMED UTF-8 in datetime string: {}",
-									&String::from_utf8_lossy(&buf)
-								);
-								parser_state = RdsLogParserState::ExpectErrcodeOrSeverity;
-								buf.clear();
+								//End of the data, the line may go on in the next chunk
+								break;
 							}
 						} else {
-							//Log file ended before delimiter was found.
-							let message_str = String::from_utf8_lossy(&buf);
-							if let std::borrow::Cow::Owned(owned_str) = &message_str {
-								log::warn!("MALFORMED UTF-8 in Message: {}", owned_str);
-							}
-							log_entry.message = message_str.to_string();
-							let finalized_log_entry = std::mem::replace(
-								&mut log_entry,
-								model::LogEntry {
-									..Default::default()
-								},
-							);
-							log_entries.push(finalized_log_entry);
+							//End of the data, the message may go on in the next chunk
 							break;
 						}
 					} else {
-						//End of log file.
-						let message_str = String::from_utf8_lossy(&buf);
-						if let std::borrow::Cow::Owned(owned_str) = &message_str {
-							log::warn!("MALFORMED UTF-8 in Message: {}", owned_str);
-						}
-						log_entry.message = message_str.to_string();
-						let finalized_log_entry = std::mem::replace(
-							&mut log_entry,
-							model::LogEntry {
-								..Default::default()
-							},
-						);
-						log_entries.push(finalized_log_entry);
+						//TODO: Read error
 						break;
 					}
-				} else {
-					//TODO: Read error
-					break;
 				}
 			}
 		}
 	}
-	root.children = model::LogSourceContents::Entries(log_entries);
-	root
+
+	// Pushes the last entry, its message does not get any more lines
+	pub fn flush_entry(&mut self, builder: &mut SourceTreeBuilder) {
+		let mut message_end = self.buf.len();
+		match self.state {
+			RdsLogParserState::ExpectMessage => (),
+			RdsLogParserState::ExpectDatetimeTentative => {
+				//Trim line ending from the end of the message
+				if self.buf[..message_end].last() == Some(&b'\n') {
+					message_end -= 1;
+				}
+				if self.buf[..message_end].last() == Some(&b'\r') {
+					message_end -= 1;
+				}
+			}
+			//TODO: Error, log file ended before the message
+			_ => return,
+		}
+		let message_str = String::from_utf8_lossy(&self.buf[..message_end]);
+		if let std::borrow::Cow::Owned(owned_str) = &message_str {
+			self.entry_diagnostics.report(DiagnosticKind::MalformedUtf8, owned_str);
+		}
+		self.log_entry.message = message_str.to_string();
+		let finalized_log_entry = std::mem::replace(
+			&mut self.log_entry,
+			model::LogEntry {
+				..Default::default()
+			},
+		);
+		builder.push::<&str>(&[], finalized_log_entry, &mut self.entry_diagnostics);
+		self.buf.clear();
+		self.state = RdsLogParserState::ExpectDatetime;
+	}
 }
 
 enum RdsLogParserState {
@@ -367,3 +390,17 @@ impl RdsLogSeverity {
 		}
 	}
 }
+
+// from_file -------------------------------------------------------------------------
+// Reads a single RDS log file from disk and returns the parsed log source.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(path: &std::path::PathBuf, progress: &Progress) -> Result<model::LogSource> {
+	let file = progress.reader(std::fs::File::open(path)?);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(file, root))
+}
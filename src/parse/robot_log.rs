@@ -1,12 +1,17 @@
 // c:\work\git\Sherlog\src\parse\robot_log.rs
 
-use crate::model::{LogEntry, LogLevel, LogSource, LogSourceContents};
+use crate::model::{CustomField, DiagnosticKind, LogEntry, LogLevel, LogSource, LogSourceContents};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use regex::Regex;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+
 /// Check if a file is a Robot Framework debug.txt log by validating first 3 timestamp lines
 pub fn is_robot_log(mut reader: impl std::io::Read + std::io::Seek) -> bool {
     let re = Regex::new(r"^(\d{4}-\d{2}-\d{2}) (\d{2}:\d{2}:\d{2}\.\d{6}) - (\w+) - (.*)$")
@@ -48,122 +53,289 @@ pub fn is_robot_log(mut reader: impl std::io::Read + std::io::Seek) -> bool {
     timestamp_lines >= 3
 }
 
+/// Test case that is currently parsed. Its entries are kept until its END TEST line
+/// (or the next suite or test marker), as they all get the result of the test as a field.
+struct TestCase {
+    path: Vec<String>, // Suite names and the test name
+    start: DateTime<Utc>,
+    entries: Vec<(LogEntry, EntryDiagnostics)>,
+    status: &'static str,
+}
+
+/// Where the entries of a robot log go while it is parsed
+#[derive(Default)]
+struct Context {
+    suites: Vec<(String, String)>, // Long name (e.g. "Device.Login") and name of the open suites
+    test: Option<TestCase>,
+}
+
+impl Context {
+    fn push(&mut self, builder: &mut SourceTreeBuilder, entry: LogEntry, entry_diagnostics: &mut EntryDiagnostics) {
+        if let Some(test) = &mut self.test {
+            test.entries.push((entry, std::mem::take(entry_diagnostics)));
+        } else {
+            let path: Vec<&str> = self.suites.iter().map(|(_, name)| name.as_str()).collect();
+            builder.push(&path, entry, entry_diagnostics);
+        }
+    }
+
+    fn start_suite(&mut self, builder: &mut SourceTreeBuilder, long_name: &str, timestamp: DateTime<Utc>) {
+        self.end_test(builder, timestamp);
+        // Robot logs the long name of a suite, which starts with the long name of its parent
+        let name = match self.suites.last() {
+            Some((parent, _)) => long_name
+                .strip_prefix(parent.as_str())
+                .and_then(|name| name.strip_prefix('.'))
+                .unwrap_or(long_name),
+            None => long_name,
+        };
+        self.suites.push((long_name.to_string(), name.to_string()));
+    }
+
+    fn end_suite(&mut self, builder: &mut SourceTreeBuilder, timestamp: DateTime<Utc>) {
+        self.end_test(builder, timestamp);
+        self.suites.pop();
+    }
+
+    fn start_test(&mut self, builder: &mut SourceTreeBuilder, name: &str, timestamp: DateTime<Utc>) {
+        self.end_test(builder, timestamp);
+        let mut path: Vec<String> = self.suites.iter().map(|(_, name)| name.clone()).collect();
+        path.push(name.to_string());
+        self.test = Some(TestCase {
+            path,
+            start: timestamp,
+            entries: Vec::new(),
+            status: "PASS",
+        });
+    }
+
+    /// Pushes the entries of the open test, followed by an entry with its result
+    fn end_test(&mut self, builder: &mut SourceTreeBuilder, timestamp: DateTime<Utc>) {
+        let test = match self.test.take() {
+            Some(test) => test,
+            None => return,
+        };
+        let duration = (timestamp - test.start).num_milliseconds() as f64 / 1000.0;
+        let result_entry = LogEntry {
+            timestamp,
+            severity: match test.status {
+                "FAIL" => LogLevel::Error,
+                "SKIP" => LogLevel::Warning,
+                _ => LogLevel::Info,
+            },
+            message: format!(
+                "Test '{}' {} ({:.3} s)",
+                test.path.last().unwrap(), // Contains at least the test name
+                test.status,
+                duration
+            ),
+            ..Default::default()
+        };
+        let entries = test
+            .entries
+            .into_iter()
+            .chain(std::iter::once((result_entry, EntryDiagnostics::default())));
+        for (mut entry, mut entry_diagnostics) in entries {
+            entry.custom_fields.insert(
+                std::borrow::Cow::Borrowed("TestStatus"),
+                CustomField::String(test.status.to_string()),
+            );
+            builder.push(&test.path, entry, &mut entry_diagnostics);
+        }
+    }
+}
+
 /// Parse Robot Framework debug.txt log files
 /// 
 /// Format: YYYY-MM-DD HH:MM:SS.microseconds - LEVEL - message
 /// Example: 2025-12-18 22:50:36.585690 - INFO - Selecting tracker 10.62.33.92
+///
+/// Robot marks the start and end of suites, tests and keywords:
+/// 2025-12-18 22:50:36.585690 - INFO - + START SUITE: Device
+/// 2025-12-18 22:50:36.585691 - INFO - +- START TEST: Login
+/// 2025-12-18 22:50:36.585692 - INFO - +-- START KEYWORD: SSHLibrary.Open Connection [ host ]
+/// 2025-12-18 22:50:37.001000 - INFO - +-- END KEYWORD: SSHLibrary.Open Connection (415ms)
+/// 2025-12-18 22:50:38.561234 - INFO - +- END TEST: Login (1.976s)
+///
+/// Suites and tests become child log sources. The END TEST line is replaced by an
+/// entry with the result of the test, which fails if it logged a FAIL line. A test
+/// without END TEST (e.g. in a cut off file) ends with the next suite or test marker.
+/// Suite markers and separator lines (===== or -----) are not kept as entries.
 pub fn to_log_entries(reader: impl std::io::Read, name: String) -> Result<LogSource, std::io::Error> {
-    let reader = BufReader::new(reader);
-    
-    // Regex pattern: YYYY-MM-DD HH:MM:SS.microseconds - LEVEL - message
-    let re = Regex::new(r"^(\d{4}-\d{2}-\d{2}) (\d{2}:\d{2}:\d{2}\.\d{6}) - (\w+) - (.*)$")
-        .expect("Invalid regex pattern");
-    
-    // Pattern for separator lines (===== or -----)
-    let separator_re = Regex::new(r"^[=\-~]+$").expect("Invalid separator regex");
-    
-    let mut entries = Vec::new();
-    let mut current_entry: Option<LogEntry> = None;
-    let mut last_timestamp: Option<DateTime<Utc>> = None;
-    let mut pending_separators: Vec<String> = Vec::new();
-    
-    for line_result in reader.lines() {
-        let line = line_result?;
+    let mut root = LogSource {
+        name,
+        children: LogSourceContents::Entries(Vec::new()),
+        diagnostics: Vec::new(),
+    };
+    let mut builder = SourceTreeBuilder::new(&root.name);
+    let mut parser = RobotParser::new();
+    parser.parse(reader, &mut root, &mut builder)?;
+    parser.finish(&mut builder);
+    Ok(builder.build(root))
+}
+
+/// State of the parser between the lines of a robot log. Follow mode keeps it between
+/// the chunks that are appended to the file, so the open suites, the open test and the
+/// last entry, which may still get continuation lines, carry over to the next chunk.
+pub struct RobotParser {
+    re: Regex,
+    separator_re: Regex,
+    marker_re: Regex,
+    context: Context,
+    current_entry: Option<LogEntry>,
+    entry_diagnostics: EntryDiagnostics, // Of current_entry
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl Default for RobotParser {
+    fn default() -> RobotParser {
+        RobotParser::new()
+    }
+}
+
+impl RobotParser {
+    pub fn new() -> RobotParser {
+        RobotParser {
+            // Regex pattern: YYYY-MM-DD HH:MM:SS.microseconds - LEVEL - message
+            re: Regex::new(r"^(\d{4}-\d{2}-\d{2}) (\d{2}:\d{2}:\d{2}\.\d{6}) - (\w+) - (.*)$")
+                .expect("Invalid regex pattern"),
+            // Pattern for separator lines (===== or -----)
+            separator_re: Regex::new(r"^[=\-~]+$").expect("Invalid separator regex"),
+            // Pattern for suite and test markers, arguments and elapsed time are cut off
+            marker_re: Regex::new(r"^\+-* (START|END) (SUITE|TEST): (.*?)(?: \[[^\[\]]*\]| \([^()]*\))?$")
+                .expect("Invalid marker regex"),
+            context: Context::default(),
+            current_entry: None,
+            entry_diagnostics: EntryDiagnostics::default(),
+            last_timestamp: None,
+        }
+    }
+
+    /// Parses the lines of `reader` into `builder`. The last entry and the open test
+    /// are kept, as the next lines may still belong to them (see `finish`).
+    pub fn parse(
+        &mut self,
+        reader: impl std::io::Read,
+        root: &mut LogSource,
+        builder: &mut SourceTreeBuilder,
+    ) -> Result<(), std::io::Error> {
+        let reader = BufReader::new(reader);
+        let mut line_offset: u64 = 0;
         
-        if let Some(caps) = re.captures(&line) {
-            // Save previous entry if it exists
-            if let Some(entry) = current_entry.take() {
-                entries.push(entry);
+        for line_result in reader.split(b'\n') {
+            let line_bytes = line_result?;
+            let offset = line_offset;
+            line_offset += line_bytes.len() as u64 + 1;
+            let mut line = String::from_utf8(line_bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if line.ends_with('\r') {
+                line.pop();
             }
             
-            // Parse new entry
-            let date = &caps[1];
-            let time = &caps[2];
-            let level_str = &caps[3];
-            let message = &caps[4];
-            
-            // Parse timestamp
-            let datetime_str = format!("{} {}", date, time);
-            let timestamp = match NaiveDateTime::parse_from_str(&datetime_str, "%Y-%m-%d %H:%M:%S%.6f") {
-                Ok(naive_dt) => DateTime::<Utc>::from_utc(naive_dt, Utc),
-                Err(_) => {
-                    log::warn!("Failed to parse timestamp: {}", datetime_str);
+            if let Some(caps) = self.re.captures(&line) {
+                // Save previous entry if it exists
+                self.flush_entry(builder);
+                
+                // Parse new entry
+                let date = &caps[1];
+                let time = &caps[2];
+                let level_str = &caps[3];
+                let message = &caps[4];
+                
+                // Parse timestamp
+                let datetime_str = format!("{} {}", date, time);
+                let timestamp = match NaiveDateTime::parse_from_str(&datetime_str, "%Y-%m-%d %H:%M:%S%.6f") {
+                    Ok(naive_dt) => DateTime::<Utc>::from_utc(naive_dt, Utc),
+                    Err(_) => {
+                        diagnostics::report_at(root, offset, DiagnosticKind::MalformedTimestamp, &line);
+                        continue;
+                    }
+                };
+                self.last_timestamp = Some(timestamp);
+                
+                if let Some(marker) = self.marker_re.captures(message) {
+                    let name = &marker[3];
+                    match (&marker[1], &marker[2]) {
+                        ("START", "SUITE") => self.context.start_suite(builder, name, timestamp),
+                        ("END", "SUITE") => self.context.end_suite(builder, timestamp),
+                        ("START", _) => {
+                            self.context.start_test(builder, name, timestamp);
+                            // Keep the start of the test, the end is replaced by its result
+                            self.current_entry = Some(LogEntry {
+                                timestamp,
+                                severity: LogLevel::Info,
+                                message: message.to_string(),
+                                ..Default::default()
+                            });
+                        }
+                        _ => self.context.end_test(builder, timestamp),
+                    }
                     continue;
                 }
-            };
-            
-            // Add any pending separators with the appropriate timestamp
-            if !pending_separators.is_empty() {
-                let sep_timestamp = last_timestamp.unwrap_or(timestamp);
-                for sep in pending_separators.drain(..) {
-                    entries.push(LogEntry {
-                        timestamp: sep_timestamp,
-                        severity: LogLevel::Info,
-                        message: sep,
-                        ..Default::default()
-                    });
-                }
+                
+                // Parse log level
+                let severity = match level_str.to_uppercase().as_str() {
+                    "TRACE" => LogLevel::Trace,
+                    "DEBUG" => LogLevel::Debug,
+                    "INFO" => LogLevel::Info,
+                    "WARN" => LogLevel::Warning,
+                    "ERROR" => LogLevel::Error,
+                    "FAIL" => {
+                        if let Some(test) = &mut self.context.test {
+                            test.status = "FAIL";
+                        }
+                        LogLevel::Error
+                    }
+                    "SKIP" => {
+                        if let Some(test) = self.context.test.as_mut().filter(|test| test.status != "FAIL") {
+                            test.status = "SKIP";
+                        }
+                        LogLevel::Info
+                    }
+                    _ => {
+                        self.entry_diagnostics.report(DiagnosticKind::InvalidSeverity, level_str);
+                        LogLevel::Info
+                    }
+                };
+                
+                self.current_entry = Some(LogEntry {
+                    timestamp,
+                    severity,
+                    message: message.to_string(),
+                    ..Default::default()
+                });
+            } else if self.separator_re.is_match(&line) {
+                // Separators end the current entry. Robot writes them around the START and END
+                // lines of tests as well, so only the markers end a test.
+                self.flush_entry(builder);
+            } else if let Some(ref mut entry) = self.current_entry {
+                // Multi-line message continuation
+                entry.message.push('\n');
+                entry.message.push_str(&line);
             }
-            
-            last_timestamp = Some(timestamp);
-            
-            // Parse log level
-            let severity = match level_str.to_uppercase().as_str() {
-                "TRACE" => LogLevel::Trace,
-                "DEBUG" => LogLevel::Debug,
-                "INFO" => LogLevel::Info,
-                "WARN" => LogLevel::Warning,
-                "ERROR" | "FAIL" => LogLevel::Error,
-                _ => {
-                    log::warn!("Unknown log level: {}", level_str);
-                    LogLevel::Info
-                }
-            };
-            
-            current_entry = Some(LogEntry {
-                timestamp,
-                severity,
-                message: message.to_string(),
-                ..Default::default()
-            });
-        } else if separator_re.is_match(&line) {
-            // Always add separators to pending list to be processed before next log entry
-            pending_separators.push(line);
-        } else if let Some(ref mut entry) = current_entry {
-            // Multi-line message continuation
-            entry.message.push('\n');
-            entry.message.push_str(&line);
+            // else: skip other lines before first timestamp
         }
-        // else: skip other lines before first timestamp
+        Ok(())
     }
-    
-    // Don't forget the last entry
-    if let Some(entry) = current_entry {
-        entries.push(entry);
+
+    /// Pushes the last entry, it does not get any more continuation lines
+    pub fn flush_entry(&mut self, builder: &mut SourceTreeBuilder) {
+        if let Some(entry) = self.current_entry.take() {
+            self.context.push(builder, entry, &mut self.entry_diagnostics);
+        }
     }
-    
-    // Add any remaining separators at the end with last timestamp
-    if !pending_separators.is_empty() {
-        if let Some(ts) = last_timestamp {
-            for sep in pending_separators.drain(..) {
-                entries.push(LogEntry {
-                    timestamp: ts,
-                    severity: LogLevel::Info,
-                    message: sep,
-                    ..Default::default()
-                });
-            }
+
+    /// Pushes the last entry and ends the open test at the end of the log
+    pub fn finish(&mut self, builder: &mut SourceTreeBuilder) {
+        self.flush_entry(builder);
+        if let Some(timestamp) = self.last_timestamp {
+            self.context.end_test(builder, timestamp);
         }
     }
-    
-    Ok(LogSource {
-        name,
-        children: LogSourceContents::Entries(entries),
-    })
 }
 
-pub fn from_file(path: &PathBuf) -> Result<LogSource, std::io::Error> {
-    let file = File::open(path)?;
+pub fn from_file(path: &PathBuf, progress: &Progress) -> Result<LogSource, std::io::Error> {
+    let file = progress.reader(File::open(path)?);
     let name = path
         .file_name()
         .unwrap_or(path.as_os_str())
@@ -187,4 +359,106 @@ mod tests {
         assert_eq!(&caps[3], "INFO");
         assert_eq!(&caps[4], "Selecting tracker 10.62.33.92");
     }
-}
\ No newline at end of file
+    
+    fn sources(log_source: &LogSource) -> &Vec<LogSource> {
+        match &log_source.children {
+            LogSourceContents::Sources(v) => v,
+            _ => panic!("Expected sub-sources of {}", log_source.name),
+        }
+    }
+    
+    fn entries(log_source: &LogSource) -> &Vec<LogEntry> {
+        match &log_source.children {
+            LogSourceContents::Entries(v) => v,
+            _ => panic!("Expected entries of {}", log_source.name),
+        }
+    }
+    
+    #[test]
+    fn test_parse_robot_test_cases() {
+        // Layout of `robot --debugfile`: separators around suite and test markers, where
+        // consecutive separators are written only once
+        let log = "\
+==============================================================================
+2025-12-18 22:50:36.000000 - INFO - + START SUITE: Device
+==============================================================================
+2025-12-18 22:50:36.000001 - INFO - +- START SUITE: Device.Login
+==============================================================================
+2025-12-18 22:50:36.100000 - INFO - +-- START TEST: Valid login
+------------------------------------------------------------------------------
+2025-12-18 22:50:36.200000 - INFO - +--- START KEYWORD: SSHLibrary.Open Connection [ host ]
+2025-12-18 22:50:36.300000 - FAIL - Connection refused
+  by host
+2025-12-18 22:50:36.400000 - INFO - +--- END KEYWORD: SSHLibrary.Open Connection (00:00:00.200)
+------------------------------------------------------------------------------
+2025-12-18 22:50:37.600000 - INFO - +-- END TEST: Valid login (00:00:01.500)
+------------------------------------------------------------------------------
+2025-12-18 22:50:37.700000 - INFO - +-- START TEST: Logout
+------------------------------------------------------------------------------
+2025-12-18 22:50:37.800000 - DEBUG - Logged out
+------------------------------------------------------------------------------
+2025-12-18 22:50:37.850000 - INFO - +-- END TEST: Logout (00:00:00.150)
+------------------------------------------------------------------------------
+2025-12-18 22:50:37.900000 - INFO - +-- START TEARDOWN: Close All Connections
+2025-12-18 22:50:37.950000 - INFO - Suite teardown
+2025-12-18 22:50:37.990000 - INFO - +-- END TEARDOWN: Close All Connections (00:00:00.090)
+==============================================================================
+2025-12-18 22:50:38.000000 - INFO - +- END SUITE: Device.Login (00:00:02.000)
+==============================================================================
+2025-12-18 22:50:38.100000 - INFO - + END SUITE: Device (00:00:02.100)
+==============================================================================
+
+";
+        let root = to_log_entries(log.as_bytes(), "debug.txt".to_string()).unwrap();
+        let device = &sources(&root)[0];
+        assert_eq!(device.name, "Device");
+        let login = &sources(device)[0];
+        assert_eq!(login.name, "Login");
+        let login_sources = sources(login);
+        assert_eq!(login_sources.len(), 3);
+        
+        assert_eq!(login_sources[0].name, "Login"); // Suite teardown
+        assert_eq!(entries(&login_sources[0])[1].message, "Suite teardown");
+        
+        assert_eq!(login_sources[1].name, "Logout");
+        let logout = entries(&login_sources[1]);
+        assert_eq!(logout.len(), 3);
+        assert_eq!(logout[1].message, "Logged out");
+        assert_eq!(logout[2].message, "Test 'Logout' PASS (0.150 s)");
+        assert_eq!(logout[2].severity, LogLevel::Info);
+        
+        assert_eq!(login_sources[2].name, "Valid login");
+        let valid_login = entries(&login_sources[2]);
+        assert_eq!(valid_login.len(), 5);
+        assert_eq!(valid_login[0].message, "+-- START TEST: Valid login");
+        assert_eq!(valid_login[2].message, "Connection refused\n  by host");
+        assert_eq!(valid_login[2].severity, LogLevel::Error);
+        assert_eq!(valid_login[4].message, "Test 'Valid login' FAIL (1.500 s)");
+        assert_eq!(valid_login[4].severity, LogLevel::Error);
+        assert!(matches!(
+            valid_login[1].custom_fields.get("TestStatus"),
+            Some(CustomField::String(status)) if status == "FAIL"
+        ));
+    }
+    
+    #[test]
+    fn test_parse_robot_cut_off_test() {
+        // The run was aborted, so the test has no END TEST line
+        let log = "\
+2025-12-18 22:50:36.000000 - INFO - + START SUITE: Device
+==============================================================================
+2025-12-18 22:50:36.100000 - INFO - +- START TEST: Reboot
+------------------------------------------------------------------------------
+2025-12-18 22:50:36.200000 - WARN - Device does not answer
+------------------------------------------------------------------------------
+2025-12-18 22:50:36.400000 - INFO - Still waiting
+";
+        let root = to_log_entries(log.as_bytes(), "debug.txt".to_string()).unwrap();
+        let reboot = &sources(&sources(&root)[0])[0];
+        assert_eq!(reboot.name, "Reboot");
+        let reboot = entries(reboot);
+        assert_eq!(reboot.len(), 4);
+        assert_eq!(reboot[2].message, "Still waiting");
+        assert_eq!(reboot[3].message, "Test 'Reboot' PASS (0.300 s)");
+    }
+}
@@ -0,0 +1,558 @@
+use super::super::model;
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use std::io::Read;
+
+// Robot Framework output.xml ---------------------------------------------------------
+// Robot writes the whole run as a tree of suites, tests and keywords:
+//
+// <robot generator="Robot 6.1 (Python 3.11.4 on linux)" generated="20230101 12:00:00.000">
+// <suite id="s1" name="Device" source="/tests/device">
+//   <test id="s1-t1" name="Login">
+//     <kw name="Open Connection" library="SSHLibrary">
+//       <msg timestamp="20230101 12:00:00.123" level="INFO">Connected</msg>
+//       <status status="PASS" starttime="20230101 12:00:00.100" endtime="20230101 12:00:00.200"/>
+//     </kw>
+//     <tag>smoke</tag>
+//     <status status="FAIL" starttime="..." endtime="...">Login failed</status>
+//   </test>
+// </suite>
+// <errors><msg timestamp="..." level="WARN">...</msg></errors>
+// </robot>
+//
+// Robot 7 writes `time` instead of `timestamp` and `start`/`elapsed` instead of
+// `starttime`/`endtime`, both are understood, as are the <tags> around the tags of
+// Robot < 4. Suites and tests become log sources, the keyword messages their entries.
+// Every test gets a closing entry with its result. All entries of a test carry its
+// status and tags as custom fields. Messages outside of tests (suite setup and
+// teardown) belong to their suite, the ones in <errors> to the log source
+// "Execution errors". The file is read as a stream, it can be hundreds of MB.
+// ------------------------------------------------------------------------------------
+
+const ERRORS_SOURCE_NAME: &str = "Execution errors";
+
+// Checks whether the first element of a file is <robot>, only reads its beginning
+pub fn is_robot_xml(reader: impl std::io::Read) -> bool {
+	let mut sample = Vec::new();
+	if reader.take(4 * 1024).read_to_end(&mut sample).is_err() {
+		return false;
+	}
+	let mut xml = Reader::from_reader(sample.as_slice());
+	let mut buf = Vec::new();
+	loop {
+		match xml.read_event_into(&mut buf) {
+			Ok(Event::Start(start) | Event::Empty(start)) => {
+				return start.name().as_ref() == b"robot"
+			}
+			Ok(Event::Decl(_) | Event::Comment(_) | Event::PI(_) | Event::DocType(_)) => (),
+			Ok(Event::Text(text)) if text.iter().all(u8::is_ascii_whitespace) => (),
+			_ => return false,
+		}
+		buf.clear();
+	}
+}
+
+struct Message {
+	timestamp: String,
+	level: String,
+}
+
+struct Test {
+	path: Vec<String>, //Suite names and the test name
+	entries: Vec<(model::LogEntry, EntryDiagnostics)>,
+	tags: Vec<String>,
+	status: Option<String>,
+	start: Option<DateTime<Utc>>,
+	end: Option<DateTime<Utc>>,
+	status_message: String,
+}
+
+pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	let mut xml = Reader::from_reader(std::io::BufReader::new(reader));
+	//<status/> is reported as a start followed by an end, same as <status></status>
+	xml.config_mut().expand_empty_elements = true;
+	let mut buf = Vec::new();
+	let mut elements: Vec<String> = Vec::new(); //Open elements, innermost last
+	let mut suites: Vec<String> = Vec::new();
+	let mut keywords: Vec<String> = Vec::new();
+	let mut test: Option<Test> = None;
+	let mut message: Option<Message> = None;
+	let mut content = String::new(); //Text of the current <msg>, <tag> or <status>
+	let mut last_timestamp = None;
+	let mut entry_diagnostics = EntryDiagnostics::default();
+
+	loop {
+		buf.clear();
+		let event = match xml.read_event_into(&mut buf) {
+			Ok(Event::Eof) => break,
+			Ok(event) => event,
+			Err(e) => {
+				let kind = match e {
+					quick_xml::Error::Io(_) => DiagnosticKind::ReadError,
+					_ => DiagnosticKind::UnparsableFile,
+				};
+				diagnostics::report_at(&mut root, xml.error_position(), kind, &e.to_string());
+				break;
+			}
+		};
+		match event {
+			Event::Start(start) => {
+				let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+				let attributes = attributes(&start);
+				let attribute = |key: &str| {
+					attributes
+						.iter()
+						.find(|(k, _)| k == key)
+						.map(|(_, v)| v.clone())
+				};
+				match name.as_str() {
+					"suite" => suites
+						.push(attribute("name").unwrap_or_else(|| "Unnamed suite".to_string())),
+					"test" => {
+						let mut path = suites.clone();
+						path.push(attribute("name").unwrap_or_else(|| "Unnamed test".to_string()));
+						test = Some(Test {
+							path,
+							entries: Vec::new(),
+							tags: Vec::new(),
+							status: None,
+							start: None,
+							end: None,
+							status_message: String::new(),
+						});
+					}
+					"kw" => keywords.push(attribute("name").unwrap_or_default()),
+					"msg" => {
+						message = Some(Message {
+							timestamp: attribute("timestamp")
+								.or_else(|| attribute("time"))
+								.unwrap_or_default(),
+							level: attribute("level").unwrap_or_default(),
+						});
+						content.clear();
+					}
+					"tag" | "status" => content.clear(),
+					_ => (),
+				}
+				let parent = elements.last().map(String::as_str);
+				if let (Some(test), "status", Some("test")) = (&mut test, name.as_str(), parent) {
+					test.status = attribute("status");
+					let start = attribute("starttime")
+						.or_else(|| attribute("start"))
+						.and_then(|s| parse_timestamp(&s));
+					test.start = start;
+					test.end = attribute("endtime")
+						.and_then(|s| parse_timestamp(&s))
+						.or_else(|| {
+							let elapsed = attribute("elapsed")?.parse::<f64>().ok()?;
+							Some(start? + chrono::Duration::microseconds((elapsed * 1e6) as i64))
+						});
+				}
+				elements.push(name);
+			}
+			Event::Text(text) if has_content(&elements) => {
+				content += &text
+					.unescape()
+					.unwrap_or_else(|_| String::from_utf8_lossy(&text));
+			}
+			Event::CData(cdata) if has_content(&elements) => {
+				content += &String::from_utf8_lossy(&cdata);
+			}
+			Event::End(end) => {
+				elements.pop();
+				match end.name().as_ref() {
+					b"suite" => {
+						suites.pop();
+					}
+					b"kw" => {
+						keywords.pop();
+					}
+					b"msg" => {
+						if let Some(message) = message.take() {
+							let log_entry = to_log_entry(
+								message,
+								&content,
+								keywords.last(),
+								&mut last_timestamp,
+								&mut entry_diagnostics,
+							);
+							if elements.iter().any(|element| element == "errors") {
+								builder.push(
+									&[ERRORS_SOURCE_NAME],
+									log_entry,
+									&mut entry_diagnostics,
+								);
+							} else if let Some(test) = &mut test {
+								test.entries
+									.push((log_entry, std::mem::take(&mut entry_diagnostics)));
+							} else {
+								builder.push(&suites, log_entry, &mut entry_diagnostics);
+							}
+						}
+					}
+					b"tag" => match &mut test {
+						Some(test) if is_test_tag(&elements) => {
+							test.tags.push(content.trim().to_string())
+						}
+						_ => (),
+					},
+					b"status" => {
+						if let (Some(test), Some("test")) =
+							(&mut test, elements.last().map(String::as_str))
+						{
+							test.status_message = content.trim().to_string();
+						}
+					}
+					b"test" => {
+						if let Some(test) = test.take() {
+							push_test(test, &mut builder, &mut last_timestamp);
+						}
+					}
+					_ => (),
+				}
+			}
+			_ => (),
+		}
+	}
+
+	//Cut off file, keep what was read of the last test
+	if let Some(test) = test.take() {
+		push_test(test, &mut builder, &mut last_timestamp);
+	}
+
+	builder.build(root)
+}
+
+fn to_log_entry(
+	message: Message,
+	text: &str,
+	keyword: Option<&String>,
+	last_timestamp: &mut Option<DateTime<Utc>>,
+	entry_diagnostics: &mut EntryDiagnostics,
+) -> model::LogEntry {
+	let timestamp = match parse_timestamp(&message.timestamp) {
+		Some(timestamp) => timestamp,
+		None => {
+			entry_diagnostics.report(DiagnosticKind::MalformedTimestamp, &message.timestamp);
+			last_timestamp.unwrap_or_default()
+		}
+	};
+	*last_timestamp = Some(timestamp);
+
+	let severity = match message.level.as_str() {
+		"FAIL" => model::LogLevel::Error,
+		"SKIP" | "HTML" => model::LogLevel::Info,
+		level => model::LogLevel::from_name(level).unwrap_or_else(|| {
+			entry_diagnostics.report(DiagnosticKind::InvalidSeverity, level);
+			model::LogLevel::Info
+		}),
+	};
+
+	let mut log_entry = model::LogEntry {
+		timestamp,
+		severity,
+		message: text.to_string(),
+		..Default::default()
+	};
+	if let Some(keyword) = keyword.filter(|keyword| !keyword.is_empty()) {
+		log_entry.custom_fields.insert(
+			std::borrow::Cow::Borrowed("Keyword"),
+			model::CustomField::String(keyword.clone()),
+		);
+	}
+	log_entry
+}
+
+// Pushes the entries of a test, followed by an entry with its result
+fn push_test(
+	test: Test,
+	builder: &mut SourceTreeBuilder,
+	last_timestamp: &mut Option<DateTime<Utc>>,
+) {
+	let status = test.status.unwrap_or_else(|| "NOT RUN".to_string());
+	let test_name = test.path.last().cloned().unwrap_or_default();
+	let mut result = format!("Test '{}' {}", test_name, status);
+	if let (Some(start), Some(end)) = (test.start, test.end) {
+		result += &format!(
+			" ({:.3} s)",
+			(end - start).num_milliseconds() as f64 / 1000.0
+		);
+	}
+	if !test.status_message.is_empty() {
+		result += ": ";
+		result += &test.status_message;
+	}
+	let timestamp = test
+		.end
+		.or(test.start)
+		.or(*last_timestamp)
+		.unwrap_or_default();
+	*last_timestamp = Some(timestamp);
+	let result_entry = model::LogEntry {
+		timestamp,
+		severity: match status.as_str() {
+			"FAIL" => model::LogLevel::Error,
+			"SKIP" => model::LogLevel::Warning,
+			_ => model::LogLevel::Info,
+		},
+		message: result,
+		..Default::default()
+	};
+
+	let tags = test.tags.join(", ");
+	let entries = test
+		.entries
+		.into_iter()
+		.chain(std::iter::once((result_entry, EntryDiagnostics::default())));
+	for (mut log_entry, mut entry_diagnostics) in entries {
+		log_entry.custom_fields.insert(
+			std::borrow::Cow::Borrowed("TestStatus"),
+			model::CustomField::String(status.clone()),
+		);
+		if !tags.is_empty() {
+			log_entry.custom_fields.insert(
+				std::borrow::Cow::Borrowed("Tags"),
+				model::CustomField::String(tags.clone()),
+			);
+		}
+		builder.push(&test.path, log_entry, &mut entry_diagnostics);
+	}
+}
+
+// Robot writes local time without a time zone, which is kept as is (same as debug.txt)
+// Example (Robot < 7): 20230101 12:00:00.123
+// Example (Robot 7): 2023-01-01T12:00:00.123456
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+	NaiveDateTime::parse_from_str(timestamp, "%Y%m%d %H:%M:%S%.f")
+		.or_else(|_| NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f"))
+		.ok()
+		.map(|ndt| DateTime::<Utc>::from_utc(ndt, Utc))
+}
+
+fn attributes(start: &BytesStart) -> Vec<(String, String)> {
+	start
+		.attributes()
+		.filter_map(Result::ok)
+		.map(|attribute| {
+			let value = match attribute.unescape_value() {
+				Ok(value) => value.to_string(),
+				Err(_) => String::from_utf8_lossy(&attribute.value).to_string(),
+			};
+			(
+				String::from_utf8_lossy(attribute.key.as_ref()).to_string(),
+				value,
+			)
+		})
+		.collect()
+}
+
+// Whether the text in the innermost element is collected
+fn has_content(elements: &[String]) -> bool {
+	matches!(
+		elements.last().map(String::as_str),
+		Some("msg") | Some("tag") | Some("status")
+	)
+}
+
+// Tags of a test are <test><tag>, before Robot 4 <test><tags><tag>. Tags of keywords
+// are not the ones of the test. `elements` are the parents of the <tag>.
+fn is_test_tag(elements: &[String]) -> bool {
+	match elements {
+		[.., parent] if parent == "test" => true,
+		[.., grandparent, parent] => parent == "tags" && grandparent == "test",
+		_ => false,
+	}
+}
+
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = progress.reader(std::fs::File::open(path)?);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(file, root))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sources(log_source: &model::LogSource) -> &Vec<model::LogSource> {
+		match &log_source.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources of {}", log_source.name),
+		}
+	}
+
+	fn entries(log_source: &model::LogSource) -> &Vec<model::LogEntry> {
+		match &log_source.children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries of {}", log_source.name),
+		}
+	}
+
+	fn string_field<'a>(log_entry: &'a model::LogEntry, key: &str) -> Option<&'a str> {
+		match log_entry.custom_fields.get(key) {
+			Some(model::CustomField::String(value)) => Some(value),
+			_ => None,
+		}
+	}
+
+	#[test]
+	fn test_parse_robot_xml() {
+		let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Generated by Robot -->
+<robot generator="Robot 6.1" generated="20230101 12:00:00.000">
+<suite id="s1" name="Device">
+<kw name="Power On" type="SETUP">
+<msg timestamp="20230101 12:00:00.050" level="INFO">Powered</msg>
+<status status="PASS" starttime="20230101 12:00:00.000" endtime="20230101 12:00:00.060"/>
+</kw>
+<test id="s1-t1" name="Login">
+<kw name="Open Connection">
+<tag>network</tag>
+<msg timestamp="20230101 12:00:01.000" level="INFO">Host &lt;10.0.0.1&gt; &amp; port&#32;22</msg>
+<msg timestamp="20230101 12:00:01.500" level="FAIL"><![CDATA[Timeout <5 s>]]></msg>
+<status status="FAIL" starttime="20230101 12:00:00.900" endtime="20230101 12:00:01.500"/>
+</kw>
+<tag>smoke</tag>
+<tag>ssh</tag>
+<status status="FAIL" starttime="20230101 12:00:00.900" endtime="20230101 12:00:02.134">Timeout &lt;5 s&gt;</status>
+</test>
+<test id="s1-t2" name="Reboot">
+<kw name="Reboot"><msg time="2023-01-01T12:00:03.000123" level="bogus">Rebooting</msg></kw>
+<status status="PASS" start="2023-01-01T12:00:02.500000" elapsed="1.5"/>
+</test>
+<status status="FAIL" starttime="20230101 12:00:00.000" endtime="20230101 12:00:04.000"/>
+</suite>
+<errors>
+<msg timestamp="20230101 12:00:00.010" level="WARN">Deprecated keyword</msg>
+</errors>
+</robot>"#;
+		assert!(is_robot_xml(xml.as_bytes()));
+		assert!(!is_robot_xml("<html><body/></html>".as_bytes()));
+
+		let root = model::LogSource {
+			name: "output.xml".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(xml.as_bytes(), root);
+		let top = sources(&root);
+		assert_eq!(top.len(), 2);
+		assert_eq!(top[0].name, "Device");
+		assert_eq!(top[1].name, ERRORS_SOURCE_NAME);
+		assert_eq!(entries(&top[1])[0].severity, model::LogLevel::Warning);
+
+		let device = sources(&top[0]);
+		assert_eq!(device.len(), 3);
+		assert_eq!(device[0].name, "Device"); //Suite setup
+		assert_eq!(entries(&device[0])[0].message, "Powered");
+		assert!(string_field(&entries(&device[0])[0], "TestStatus").is_none());
+
+		assert_eq!(device[1].name, "Login");
+		let login = entries(&device[1]);
+		assert_eq!(login.len(), 3);
+		assert_eq!(login[0].message, "Host <10.0.0.1> & port 22");
+		assert_eq!(string_field(&login[0], "Keyword"), Some("Open Connection"));
+		assert_eq!(string_field(&login[0], "TestStatus"), Some("FAIL"));
+		assert_eq!(string_field(&login[0], "Tags"), Some("smoke, ssh"));
+		assert_eq!(login[1].severity, model::LogLevel::Error);
+		assert_eq!(login[1].message, "Timeout <5 s>");
+		assert_eq!(login[2].severity, model::LogLevel::Error);
+		assert_eq!(
+			login[2].message,
+			"Test 'Login' FAIL (1.234 s): Timeout <5 s>"
+		);
+		assert_eq!(
+			login[2]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			"2023-01-01T12:00:02.134Z"
+		);
+
+		assert_eq!(device[2].name, "Reboot");
+		let reboot = entries(&device[2]);
+		assert_eq!(
+			reboot[0]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+			"2023-01-01T12:00:03.000123Z"
+		);
+		assert!(string_field(&reboot[0], "Tags").is_none());
+		assert_eq!(reboot[1].message, "Test 'Reboot' PASS (1.500 s)");
+		assert_eq!(reboot[1].severity, model::LogLevel::Info);
+		assert_eq!(device[2].diagnostics.len(), 1);
+		assert_eq!(
+			device[2].diagnostics[0].kind,
+			DiagnosticKind::InvalidSeverity
+		);
+	}
+
+	#[test]
+	fn test_parse_robot_xml_old_tags() {
+		//Robot < 4 wraps tags in <tags>, also the ones of keywords
+		let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<robot generator="Robot 3.2.2 (Python 3.8.10 on linux)" generated="20230101 12:00:00.000">
+<suite id="s1" name="Device">
+<test id="s1-t1" name="Login">
+<kw name="Open Connection">
+<tags>
+<tag>keyword tag</tag>
+</tags>
+<msg timestamp="20230101 12:00:01.000" level="INFO">Connected</msg>
+<status status="PASS" starttime="20230101 12:00:00.900" endtime="20230101 12:00:01.500"></status>
+</kw>
+<tags>
+<tag>smoke</tag>
+<tag>ssh</tag>
+</tags>
+<status status="PASS" starttime="20230101 12:00:00.900" endtime="20230101 12:00:01.500" critical="yes"></status>
+</test>
+</suite>
+</robot>"#;
+		let root = model::LogSource {
+			name: "output.xml".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(xml.as_bytes(), root);
+		assert!(root.diagnostics.is_empty());
+		let login = &sources(&sources(&root)[0])[0];
+		assert_eq!(login.name, "Login");
+		let login = entries(login);
+		assert_eq!(login.len(), 2);
+		assert_eq!(string_field(&login[0], "Tags"), Some("smoke, ssh"));
+		assert_eq!(login[1].message, "Test 'Login' PASS (0.600 s)");
+	}
+
+	#[test]
+	fn test_parse_robot_xml_cut_off() {
+		let xml = "<robot><suite name=\"S\"><test name=\"T\"><kw name=\"K\">\
+			<msg timestamp=\"20230101 12:00:00.000\" level=\"INFO\">Started</msg><status status=";
+		let root = model::LogSource {
+			name: "output.xml".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(xml.as_bytes(), root);
+		assert_eq!(root.diagnostics.len(), 1);
+		assert_eq!(root.diagnostics[0].kind, DiagnosticKind::UnparsableFile);
+		let test = &sources(&sources(&root)[0])[0];
+		assert_eq!(test.name, "T");
+		assert_eq!(entries(test)[1].message, "Test 'T' NOT RUN");
+	}
+}
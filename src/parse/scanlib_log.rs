@@ -1,8 +1,17 @@
 use super::super::model;
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
 
-pub fn to_log_entries(_reader: impl std::io::Read, root: model::LogSource) -> model::LogSource {
-	root
-}
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+
+use std::io::BufRead;
+use std::io::BufReader;
+
+// ScanLib parser --------------------------------------------------------------------
 
 //2020-12-01 15:46:19.122013 (warning) <0x00000001> [] : Foo
 
@@ -14,3 +23,242 @@ pub fn to_log_entries(_reader: impl std::io::Read, root: model::LogSource) -> mo
 //(info)
 //(debug)
 //(trace)
+
+//Lines that do not start with a timestamp continue the message of the previous entry.
+
+pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	let mut parser = ScanLibParser::new();
+	parser.parse(reader, &mut root, &mut builder);
+	parser.flush_entry(&mut builder);
+	builder.build(root)
+}
+
+// State of the parser between the lines of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the last entry may still get continuation
+// lines from the next chunk.
+pub struct ScanLibParser {
+	re: Regex,
+	current: Option<(model::LogEntry, String)>,
+	entry_diagnostics: EntryDiagnostics, //Of `current`
+	offset: u64,                         //Of the next line
+}
+
+impl Default for ScanLibParser {
+	fn default() -> ScanLibParser {
+		ScanLibParser::new()
+	}
+}
+
+impl ScanLibParser {
+	pub fn new() -> ScanLibParser {
+		ScanLibParser {
+			re: Regex::new(
+				r"^(\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(?:\.\d+)?) \((\w+)\) <([^>]*)> \[([^\]]*)\] :(?: (.*))?$",
+			)
+			.expect("Invalid regex pattern"),
+			current: None,
+			entry_diagnostics: EntryDiagnostics::default(),
+			offset: 0,
+		}
+	}
+
+	// Parses the lines of `reader` into `builder`. The last entry is kept, as the next
+	// lines may still continue it (see `flush_entry`).
+	pub fn parse(
+		&mut self,
+		reader: impl std::io::Read,
+		root: &mut model::LogSource,
+		builder: &mut SourceTreeBuilder,
+	) {
+		let mut bufreader = BufReader::new(reader);
+		let mut buf = Vec::<u8>::with_capacity(512);
+
+		loop {
+			let line_offset = self.offset;
+			buf.clear();
+			match bufreader.read_until(b'\n', &mut buf) {
+				Ok(0) => break,
+				Ok(bytes_read) => self.offset += bytes_read as u64,
+				Err(e) => {
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::ReadError,
+						&e.to_string(),
+					);
+					break;
+				}
+			}
+			let mut line_end = buf.len();
+			if buf[..line_end].last() == Some(&b'\n') {
+				line_end -= 1;
+			}
+			if buf[..line_end].last() == Some(&b'\r') {
+				line_end -= 1;
+			}
+			let line = String::from_utf8_lossy(&buf[..line_end]);
+			let malformed_utf8 = matches!(line, std::borrow::Cow::Owned(_));
+
+			if let Some(caps) = self.re.captures(&line) {
+				self.flush_entry(builder);
+				if malformed_utf8 {
+					self.entry_diagnostics
+						.report(DiagnosticKind::MalformedUtf8, &line);
+				}
+
+				let mut log_entry = model::LogEntry {
+					message: caps.get(5).map_or("", |m| m.as_str()).to_string(),
+					..Default::default()
+				};
+				if let Some(timestamp) = parse_scanlib_datetime(&caps[1]) {
+					log_entry.timestamp = timestamp;
+				} else {
+					self.entry_diagnostics
+						.report(DiagnosticKind::MalformedTimestamp, &caps[1]);
+				}
+				if let Some(scanlib_sev) = ScanLibSeverity::from_str(&caps[2]) {
+					log_entry.severity = normalize_scanlib_sev(scanlib_sev);
+				} else {
+					self.entry_diagnostics
+						.report(DiagnosticKind::InvalidSeverity, &caps[2]);
+				}
+				if !caps[3].is_empty() {
+					log_entry.custom_fields.insert(
+						std::borrow::Cow::Borrowed("Address"),
+						model::CustomField::String(caps[3].to_string()),
+					);
+				}
+				self.current = Some((log_entry, caps[4].trim().to_string()));
+			} else if let Some((log_entry, _)) = &mut self.current {
+				//Multi-line message continuation
+				if malformed_utf8 {
+					self.entry_diagnostics
+						.report(DiagnosticKind::MalformedUtf8, &line);
+				}
+				log_entry.message.push('\n');
+				log_entry.message.push_str(&line);
+			} else if !line.trim().is_empty() {
+				let kind = if malformed_utf8 {
+					DiagnosticKind::MalformedUtf8
+				} else {
+					DiagnosticKind::UnrecognizedLine
+				};
+				diagnostics::report_at(root, line_offset, kind, &line);
+			}
+		}
+	}
+
+	// Pushes the last entry, it does not get any more continuation lines
+	pub fn flush_entry(&mut self, builder: &mut SourceTreeBuilder) {
+		if let Some((log_entry, source_name)) = self.current.take() {
+			//Entries that do not specify a log sub-source go to the root
+			let path: &[String] = if source_name.is_empty() {
+				&[]
+			} else {
+				std::slice::from_ref(&source_name)
+			};
+			builder.push(path, log_entry, &mut self.entry_diagnostics);
+		}
+	}
+}
+
+fn parse_scanlib_datetime(dt_string: &str) -> Option<chrono::DateTime<Utc>> {
+	// Same caveat as in RDS logs: there is no time zone information, assume UTC.
+	if let Ok(ndt) = NaiveDateTime::parse_from_str(dt_string, "%Y-%m-%d %H:%M:%S%.f") {
+		Some(DateTime::<Utc>::from_utc(ndt, Utc))
+	} else {
+		None
+	}
+}
+
+fn normalize_scanlib_sev(scanlib_sev: ScanLibSeverity) -> model::LogLevel {
+	match scanlib_sev {
+		ScanLibSeverity::Fatal => model::LogLevel::Critical,
+		ScanLibSeverity::Error => model::LogLevel::Error,
+		ScanLibSeverity::Warning => model::LogLevel::Warning,
+		ScanLibSeverity::Info => model::LogLevel::Info,
+		ScanLibSeverity::Debug => model::LogLevel::Debug,
+		ScanLibSeverity::Trace => model::LogLevel::Trace,
+	}
+}
+
+enum ScanLibSeverity {
+	Fatal,
+	Error,
+	Warning,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl ScanLibSeverity {
+	#[rustfmt::skip]
+	fn from_str(value: &str) -> Option<ScanLibSeverity> {
+		match value {
+			"fatal"   => Some(ScanLibSeverity::Fatal),
+			"error"   => Some(ScanLibSeverity::Error),
+			"warning" => Some(ScanLibSeverity::Warning),
+			"info"    => Some(ScanLibSeverity::Info),
+			"debug"   => Some(ScanLibSeverity::Debug),
+			"trace"   => Some(ScanLibSeverity::Trace),
+			_ => None,
+		}
+	}
+}
+
+// from_file -------------------------------------------------------------------------
+// Reads a single ScanLib log file from disk and returns the parsed log source.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = progress.reader(std::fs::File::open(path)?);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(file, root))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_scanlib_log() {
+		let log = "2020-12-01 15:46:19.122013 (warning) <0x00000001> [] : Foo\n\
+			2020-12-01 15:46:19.122014 (error) <0x00000002> [Scanner] : Bar\n\
+			second line\r\n\
+			2020-12-01 15:46:19.122015 (info) <0x00000002> [Scanner] : Baz";
+		let root = model::LogSource {
+			name: "ScanLib_1".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(log.as_bytes(), root);
+
+		let sources = match root.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(sources.len(), 2);
+		assert_eq!(sources[0].name, "Scanner");
+		assert_eq!(sources[1].name, "Unknown (None)");
+
+		let entries = match &sources[0].children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].severity, model::LogLevel::Error);
+		assert_eq!(entries[0].message, "Bar\nsecond line");
+		match entries[0].custom_fields.get("Address") {
+			Some(model::CustomField::String(address)) => assert_eq!(address, "0x00000002"),
+			_ => panic!("Expected address field"),
+		}
+	}
+}
@@ -7,100 +7,297 @@ use chrono::prelude::Utc;
 use std::path::PathBuf;
 
 use super::super::model;
+use super::diagnostics;
 use super::glog;
+use super::io::LogParseError;
+use super::progress::Progress;
+use super::progress::ProgressReader;
 use super::rds_log;
 use super::robot_log;
+use super::robot_xml;
 use super::scanlib_log;
+use super::sfile_rules::SfileRules;
 use super::xlog;
 
 use std::collections::HashMap;
-use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Passwords of encrypted sfiles -----------------------------------------------------
+// Every archive is tried with all known passwords in turn:
+//
+// 1. Passwords added at runtime, from the command line or the password prompt
+// 2. Environment variable SFILE_PASSWORD when Sherlog runs
+// 3. Environment variable SFILE_PASSWORD when Sherlog was compiled
+// ------------------------------------------------------------------------------------
+
+static COMPILED_PASSWORD: Option<&'static str> = option_env!("SFILE_PASSWORD");
+static PASSWORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn add_password(password: &str) {
+	let mut passwords = PASSWORDS.lock().unwrap();
+	if !passwords.iter().any(|known| known == password) {
+		passwords.push(password.to_string());
+	}
+}
+
+fn known_passwords() -> Vec<String> {
+	let mut passwords = PASSWORDS.lock().unwrap().clone();
+	let env_password = std::env::var("SFILE_PASSWORD").ok();
+	for password in env_password.iter().map(String::as_str).chain(COMPILED_PASSWORD) {
+		if !passwords.iter().any(|known| known == password) {
+			passwords.push(password.to_string());
+		}
+	}
+	passwords
+}
+
+// Returns the password that decrypts all encrypted members of the archive,
+// or Ok(None) if nothing is encrypted.
+fn find_password<R: std::io::Read + std::io::Seek>(
+	archive: &mut zip::ZipArchive<R>,
+	path: &std::path::Path,
+) -> Result<Option<String>, LogParseError> {
+	let mut encrypted = Vec::new();
+	for i in 0..archive.len() {
+		match archive.by_index(i) {
+			Ok(_) => (),
+			Err(zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED)) => {
+				encrypted.push(i)
+			}
+			Err(err) => return Err(std::io::Error::from(err).into()),
+		}
+	}
+	if encrypted.is_empty() {
+		return Ok(None);
+	}
+
+	//The password check of a single member has false positives (one in 256),
+	//checking all of them rules out wrong passwords
+	'passwords: for password in known_passwords() {
+		for &i in &encrypted {
+			match archive.by_index_decrypt(i, password.as_bytes()) {
+				Ok(Ok(_)) => (),
+				Ok(Err(zip::result::InvalidPassword)) => continue 'passwords,
+				Err(err) => return Err(std::io::Error::from(err).into()),
+			}
+		}
+		return Ok(Some(password));
+	}
+	Err(LogParseError::InvalidPassword(path.to_path_buf()))
+}
+
+pub fn from_file(
+	path: &std::path::PathBuf,
+	rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let archive_name = path.file_name().unwrap().to_string_lossy().to_string();
+	to_log_source(archive_name, path, rules, progress, |count_directory| {
+		open_archive(path, progress, count_directory)
+	})
+}
+
+// Sfiles that were compressed as a whole cannot seek, so they are decompressed into
+// `bytes` first (see `io::from_compressed_file`). The decompression counted the
+// progress already, so only cancelling applies to the members.
+pub fn from_bytes(
+	bytes: &[u8],
+	path: &std::path::Path,
+	rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let archive_name = path.file_name().unwrap().to_string_lossy().to_string();
+	to_log_source(archive_name, path, rules, progress, |_| {
+		Ok(zip::ZipArchive::new(std::io::Cursor::new(bytes))?)
+	})
+}
 
-static SFILE_PASSWORD: Option<&'static str> = option_env!("SFILE_PASSWORD");
+// `open` opens the archive, its argument is false if it was opened before (see
+// `open_archive`)
+fn to_log_source<R: std::io::Read + std::io::Seek>(
+	archive_name: String,
+	path: &std::path::Path,
+	rules: &SfileRules,
+	progress: &Progress,
+	open: impl Fn(bool) -> Result<zip::ZipArchive<R>, std::io::Error> + Sync,
+) -> Result<model::LogSource, LogParseError> {
+	let mut archive = open(true)?;
+	let password = find_password(&mut archive, path)?;
+	let password = password.as_deref();
+
+	let mut members = Vec::new();
+	for i in 0..archive.len() {
+		//Names and sizes can be read without decrypting the member
+		let file = archive.by_index_raw(i).map_err(std::io::Error::from)?;
+		members.push(ArchiveMember {
+			name: file.name().to_string(),
+			size: file.compressed_size(),
+		});
+	}
+	let jobs = plan_jobs(&members);
+	let log_sources = parse_jobs(
+		&archive_name,
+		&jobs,
+		progress,
+		archive,
+		|| open(false),
+		|archive, job| parse_job(archive, password, job, progress),
+	)?;
+	Ok(build_tree(archive_name, &jobs, log_sources, rules))
+}
+
+// Member of an sfile or of another archive with the same layout (see `tar`)
+pub struct ArchiveMember {
+	pub name: String, //Path in the archive, separated by '/'
+	pub size: u64,    //Size in the archive, used to balance the parser threads
+}
 
-pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io::Error> {
-	let file = std::fs::File::open(&path)?;
-	let mut archive = zip::ZipArchive::new(file)?;
+// Kind of log in an archive member, None if it is not parsed
+pub fn member_kind(name: &str) -> Option<MemberKind> {
+	let outpath = PathBuf::from(name);
+	let stem = outpath.file_stem()?.to_string_lossy();
+	let extension = outpath.extension()?.to_string_lossy();
 
+	// .ZIP specification, Version: 6.3.9, Paragraph 4.4.17 file name: (Variable)
+	// All slashes MUST be forward slashes '/' as opposed to backwards slashes '\' [...]
+	//
+	// Therefore, we can safely match folders with `/`
+	if outpath.starts_with("RDS/") {
+		match extension.as_ref() {
+			"log" if stem.starts_with("ScanLib_") => Some(MemberKind::ScanLib),
+			"log" => Some(MemberKind::Rds),
+			unknown_extension => {
+				log::warn!("Unknown extension in RDS folder: {}", unknown_extension);
+				None
+			}
+		}
+	} else {
+		match extension.as_ref() {
+			"glog" => Some(MemberKind::Glog),
+			"xlog" => Some(MemberKind::Xlog),
+			"log" if name.contains("robot") => Some(MemberKind::Robot),
+			"xml" if stem == "output" || name.contains("robot") => Some(MemberKind::RobotXml),
+			_ => None,
+		}
+	}
+}
+
+// Decides which members are parsed into which log source
+pub fn plan_jobs(members: &[ArchiveMember]) -> Vec<ParseJob> {
 	let mut glog_files = Vec::new();
+	let mut jobs = Vec::new();
+
+	for (i, member) in members.iter().enumerate() {
+		let kind = match member_kind(&member.name) {
+			Some(kind) => kind,
+			None => continue,
+		};
+		//Checked by `member_kind`
+		let stem = PathBuf::from(&member.name).file_stem().unwrap().to_string_lossy().to_string();
+		match kind {
+			MemberKind::Glog => {
+				glog_files.push(ZipEntry {
+					group_name: get_group_name(&stem),
+					name: stem,
+					index: i,
+					size: member.size,
+				});
+				continue;
+			}
+			MemberKind::ScanLib => log::info!("Log file (ScanLib): {}", &stem),
+			MemberKind::Rds => log::info!("Log file (RDS): {}", &stem),
+			MemberKind::Robot => log::info!("Robot Framework log: {}", member.name),
+			MemberKind::RobotXml => log::info!("Robot Framework output: {}", member.name),
+			MemberKind::Xlog => (),
+		}
+		jobs.push(ParseJob {
+			kind,
+			name: stem,
+			indices: std::collections::VecDeque::from([i]),
+			size: member.size,
+		});
+	}
+
+	//Sort glog files by group (a group is the file name with its ring buffer ID / overview suffix removed)
+	//Inside a group, reverse sort by file name (and thus buffer ID) to get chronological ordering of files.
+	//The reason for this is that higher ring buffer ID means the file is older. Older log entries come first.
+	//Note: Overview logs are interspersed with normal logs, therefore no chronological order between
+	//the log entries of normal logs and overview logs can be established without inspecting timestamps.
+	//Note: Sensor has logSync and logAsync, leading to minor violations of chronological order inside a file
+	//and even e.g. between the end of one file and the start of another.
+	//Due to these facts, this ordering is only mostly chronological with regards to individual log entries.
+	//This chronological ordering is required to perform timestamp corrections for devices that do not have
+	//a real-time clock and thus start with 1970 timestamps on boot-up before distributed clock time is set.
+	//It is also required to preserve the order of log entries that were logged with the exact same timestamp.
+	//
+	//Example ordering:
+	//Group name           File name
+	//--------------------------------------
+	//adm_LoggerAdm_p      adm_LoggerAdm_2_p
+	//adm_LoggerAdm_p      adm_LoggerAdm_1_p
+	//adm_LoggerAdm_v      adm_LoggerAdm_1_v
+	//contr_FtcManager     contr_FtcManager_ov
+	//contr_FtcManager     contr_FtcManager
+	//contr_Hwa            contr_Hwa_4
+	//contr_Hwa            contr_Hwa_3
+	//contr_Hwa            contr_Hwa_2
+	//contr_Hwa            contr_Hwa_1
+	//contr_Hwa            contr_Hwa
+	//
+	//Note the sorting subtleties, especially with _p and _v logs, where the ID is not a suffix.
+
+	glog_files.sort_unstable_by(|a, b| {
+		a.group_name
+			.cmp(&b.group_name)
+			.then(a.name.cmp(&b.name).reverse())
+	});
 
+	let mut last_group = "".to_string();
+	for file in glog_files {
+		if last_group != file.group_name {
+			log::info!("--------------------");
+			log::info!("Glog file: {:?}", file);
+			last_group = file.group_name.clone();
+			jobs.push(ParseJob {
+				kind: MemberKind::Glog,
+				name: file.group_name,
+				indices: std::collections::VecDeque::new(),
+				size: 0,
+			});
+		} else {
+			log::info!("Glog file: {:?}", file);
+		}
+		let job = jobs.last_mut().unwrap(); //Pushed above for the first file of the group
+		job.indices.push_back(file.index);
+		job.size += file.size;
+	}
+
+	jobs
+}
+
+// Arranges the parsed log sources of an archive into its log source tree
+pub fn build_tree(
+	archive_name: String,
+	jobs: &[ParseJob],
+	log_sources: Vec<Option<model::LogSource>>,
+	rules: &SfileRules,
+) -> model::LogSource {
 	let mut client_child_sources = Vec::new();
 	let mut rds_child_sources = Vec::new();
 	let mut scanlib_child_sources = Vec::new();
+	let mut robot_child_sources = Vec::new();
+	let mut robot_debug_sources = Vec::new();
 	let mut child_sources = Vec::new();
-	child_sources.reserve(archive.len());
-
-	for i in 0..archive.len() {
-		let file = if let Some(password) = SFILE_PASSWORD {
-			archive.by_index_decrypt(i, password.as_bytes())?.unwrap() //TODO: 21.06.2020: Handle InvalidPassword!
-		} else {
-			archive.by_index(i)?
-		};
-		let outpath = PathBuf::from(file.name());
-		let stem = outpath.file_stem().unwrap();
-		let stem = stem.to_string_lossy();
-		//log::info!("File contained: {}", &stem);
-
-		// .ZIP specification, Version: 6.3.9, Paragraph 4.4.17 file name: (Variable)
-		// All slashes MUST be forward slashes '/' as opposed to backwards slashes '\' [...]
-		//
-		// Therefore, we can safely match folders with `/`
-		if outpath.starts_with("RDS/") {
-			if let Some(extension) = outpath.extension() {
-				match extension.to_string_lossy().as_ref() {
-					"log" => {
-						if stem.starts_with("ScanLib_") {
-							log::info!("Log file (ScanLib): {}", &stem);
-							let root = model::LogSource {
-								name: stem.to_string(),
-								children: {
-									model::LogSourceContents::Entries(Vec::<model::LogEntry>::new())
-								},
-							};
-							scanlib_child_sources.push(scanlib_log::to_log_entries(file, root));
-						} else {
-							log::info!("Log file (RDS): {}", &stem);
-							let root = model::LogSource {
-								name: stem.to_string(),
-								children: {
-									model::LogSourceContents::Entries(Vec::<model::LogEntry>::new())
-								},
-							};
-							rds_child_sources.push(rds_log::to_log_entries(file, root));
-						}
-					}
-					unknown_extension => {
-						log::warn!("Unknown extension in RDS folder: {}", unknown_extension)
-					}
-				}
-			}
-		} else if let Some(extension) = outpath.extension() {
-			match extension.to_string_lossy().as_ref() {
-				"glog" => {
-					glog_files.push(ZipEntry {
-						name: stem.to_string(),
-						group_name: get_group_name(&stem),
-						index: i,
-					});
-				}
-				"xlog" => {
-					//log::info!("XLOG: {}", &stem);
-					let root = model::LogSource {
-						name: stem.to_string(),
-						children: {
-							model::LogSourceContents::Entries(Vec::<model::LogEntry>::new())
-						},
-					};
-					client_child_sources.push(xlog::to_log_entries(file, root));
-				}
-				"log" if file.name().contains("robot") => {
-                    log::info!("Robot Framework log: {}", file.name());
-                    match robot_log::to_log_entries(file, "RobotFramework".to_string()) {
-                        Ok(source) => client_child_sources.push(source),
-                        Err(e) => log::warn!("Failed to parse Robot Framework log {}: {}", stem, e),
-                    }
-                }
-				_ => (),
+	for (job, log_source) in jobs.iter().zip(log_sources) {
+		if let Some(log_source) = log_source {
+			match job.kind {
+				MemberKind::Glog => child_sources.push(log_source),
+				MemberKind::Xlog => client_child_sources.push(log_source),
+				MemberKind::Rds => rds_child_sources.push(log_source),
+				MemberKind::ScanLib => scanlib_child_sources.push(log_source),
+				MemberKind::Robot => robot_debug_sources.push(log_source),
+				MemberKind::RobotXml => robot_child_sources.push(log_source),
 			}
 		}
 	}
@@ -158,6 +355,13 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 			match children {
 				model::LogSourceContents::Entries(v) => {
 					if let model::LogSourceContents::Entries(mut entries) = file_source.children {
+						//Entries of this file come after the ones already in the channel
+						for mut diagnostic in file_source.diagnostics {
+							if let model::DiagnosticLocation::Entry(idx) = &mut diagnostic.location {
+								*idx += v.len();
+							}
+							source.diagnostics.push(diagnostic);
+						}
 						v.append(&mut entries);
 					} else {
 						unreachable!(); //If this panics, there is a bug in the XLOG parser
@@ -178,6 +382,7 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 							unreachable!(); //If this panics, there is a bug in the XLOG parser
 						}
 					},
+					diagnostics: file_source.diagnostics,
 				},
 			);
 		}
@@ -187,231 +392,305 @@ pub fn from_file(path: &std::path::PathBuf) -> Result<model::LogSource, std::io:
 		client_child_sources.push(sub_source);
 	}
 
-	//Sort glog files by group (a group is the file name with its ring buffer ID / overview suffix removed)
-	//Inside a group, reverse sort by file name (and thus buffer ID) to get chronological ordering of files.
-	//The reason for this is that higher ring buffer ID means the file is older. Older log entries come first.
-	//Note: Overview logs are interspersed with normal logs, therefore no chronological order between
-	//the log entries of normal logs and overview logs can be established without inspecting timestamps.
-	//Note: Sensor has logSync and logAsync, leading to minor violations of chronological order inside a file
-	//and even e.g. between the end of one file and the start of another.
-	//Due to these facts, this ordering is only mostly chronological with regards to individual log entries.
-	//This chronological ordering is required to perform timestamp corrections for devices that do not have
-	//a real-time clock and thus start with 1970 timestamps on boot-up before distributed clock time is set.
-	//It is also required to preserve the order of log entries that were logged with the exact same timestamp.
-	//
-	//Example ordering:
-	//Group name           File name
-	//--------------------------------------
-	//adm_LoggerAdm_p      adm_LoggerAdm_2_p
-	//adm_LoggerAdm_p      adm_LoggerAdm_1_p
-	//adm_LoggerAdm_v      adm_LoggerAdm_1_v
-	//contr_FtcManager     contr_FtcManager_ov
-	//contr_FtcManager     contr_FtcManager
-	//contr_Hwa            contr_Hwa_4
-	//contr_Hwa            contr_Hwa_3
-	//contr_Hwa            contr_Hwa_2
-	//contr_Hwa            contr_Hwa_1
-	//contr_Hwa            contr_Hwa
-	//
-	//Note the sorting subtleties, especially with _p and _v logs, where the ID is not a suffix.
-
-	glog_files.sort_unstable_by(|a, b| {
-		a.group_name
-			.cmp(&b.group_name)
-			.then(a.name.cmp(&b.name).reverse())
-	});
-
-	let mut deque = std::collections::VecDeque::new();
-	let mut last_group = "".to_string();
-	for file in glog_files {
-		if last_group != file.group_name {
-			if !deque.is_empty() {
-				let deque = mem::replace(&mut deque, std::collections::VecDeque::new());
-				let reader = ConcatZipReader::new(&mut archive, deque);
-				let root = model::LogSource {
-					name: last_group,
-					children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
-				};
-				child_sources.push(glog::to_log_entries(reader, root));
-			}
-			log::info!("--------------------");
-			log::info!("Glog file: {:?}", file);
-			last_group = file.group_name;
-		} else {
-			log::info!("Glog file: {:?}", file);
+	//Robot debug logs hold a tree of suites and tests, so they are not arranged into
+	//channels. Like a channel, all of them go into one "RobotFramework" log source.
+	let mut robot_debug_sources = robot_debug_sources.into_iter();
+	if let Some(mut robot_source) = robot_debug_sources.next() {
+		robot_source.name = "RobotFramework".to_string();
+		for other in robot_debug_sources {
+			merge_sources(&mut robot_source, other);
 		}
-		deque.push_back(file.index);
-	}
-	if !deque.is_empty() {
-		let deque = mem::replace(&mut deque, std::collections::VecDeque::new());
-		let reader = ConcatZipReader::new(&mut archive, deque);
-		let root = model::LogSource {
-			name: last_group,
-			children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
-		};
-		child_sources.push(glog::to_log_entries(reader, root));
+		client_child_sources.push(robot_source);
 	}
 
-	let mut contr_child_sources = Vec::new();
-	let mut sensor_child_sources: std::vec::Vec<model::LogSource> = Vec::new();
-	let mut cbox_child_sources = Vec::new();
-	let mut probe_child_sources = Vec::new();
+	//Glog files go into the groups of the categorisation rules, the first match wins
+	let mut group_child_sources: Vec<Vec<model::LogSource>> =
+		rules.groups.iter().map(|_| Vec::new()).collect();
 	let mut unknown_child_sources = Vec::new();
-
 	for mut source in child_sources {
-		//Controller logs
-		if source.name.starts_with("contr_") {
-			//Remove "contr_" to make it look nicer
-			source.name = source.name.split_off(6);
-			contr_child_sources.push(source);
-			continue;
-		}
-
-		//Sensor logs
-		let mut iter = source.name.splitn(2, '_');
-		let board_name = iter.next().unwrap(); //First element always exists
-		if let Some(log_name) = iter.next() {
-			if board_name == "axis" ||
-				board_name == "sensorbase" ||
-				board_name == "telescope" ||
-				board_name == "trigger" ||
-				board_name == "adm" || // G
-				board_name == "laseroven" || // G
-				board_name == "wfd"	|| // W
-				board_name == "dynamicadm" || // P
-				board_name == "icbpower" || // P
-				board_name.starts_with("cfm") || // L
-				board_name == "laserctl" || // L
-				board_name == "wlanmodule" || // L
-				false
-			{
-				if sensor_child_sources.is_empty()
-					|| sensor_child_sources.last().unwrap().name != board_name
-				{
-					let board_name_string = board_name.to_string();
-					source.name = log_name.to_string();
-					sensor_child_sources.push(model::LogSource {
-						name: board_name_string,
-						children: { model::LogSourceContents::Sources(vec![source]) },
-					});
-				} else {
-					source.name = log_name.to_string();
-					if let model::LogSourceContents::Sources(sources) =
-						&mut sensor_child_sources.last_mut().unwrap().children
-					{
+		let placement = rules
+			.groups
+			.iter()
+			.enumerate()
+			.find_map(|(idx, group)| Some((idx, group.place(&source.name)?)));
+		let (idx, placement) = match placement {
+			Some(placement) => placement,
+			None => {
+				unknown_child_sources.push(source);
+				continue;
+			}
+		};
+		source.name = placement.name;
+		let group_sources = &mut group_child_sources[idx];
+		match placement.board {
+			Some(board_name) => {
+				if let Some(board) = group_sources.iter_mut().find(|board| board.name == board_name) {
+					if let model::LogSourceContents::Sources(sources) = &mut board.children {
 						sources.push(source)
 					} else {
-						//We only pushed model::LogSourceContents::Sources
+						//We only push model::LogSourceContents::Sources
 						unreachable!();
 					}
+				} else {
+					group_sources.push(model::LogSource {
+						name: board_name,
+						children: { model::LogSourceContents::Sources(vec![source]) },
+						diagnostics: Vec::new(),
+					});
 				}
-				continue;
 			}
+			None => group_sources.push(source),
 		}
+	}
 
-		//Connect Box logs
-		if source.name.starts_with("connectbox_") {
-			//Remove "connectbox_" to make it look nicer
-			source.name = source.name.split_off(11);
-			cbox_child_sources.push(source);
-			continue;
+	let mut sources_vec = Vec::new();
+	let mut push_group = |name: &str, mut child_sources: Vec<model::LogSource>, show_empty: bool| {
+		if show_empty || !child_sources.is_empty() {
+			//Case insensitive sort by log source name
+			child_sources.sort_by_key(|source| source.name.to_lowercase());
+			sources_vec.push(model::LogSource {
+				name: name.to_string(),
+				children: { model::LogSourceContents::Sources(child_sources) },
+				diagnostics: Vec::new(),
+			});
 		}
+	};
 
-		//Probe logs
-		if source.name.starts_with("ap21_") {
-			//Remove "ap21_" to make it look nicer
-			source.name = source.name.split_off(5);
-			probe_child_sources.push(source);
-			continue;
+	push_group(&rules.client, client_child_sources, true);
+	for (group, mut child_sources) in rules.groups.iter().zip(group_child_sources) {
+		if group.adjust_sensor_timestamps {
+			for source in &mut child_sources {
+				glog::adjust_sensor_timestamps(source);
+			}
 		}
-
-		//Unknown logs
-		unknown_child_sources.push(source);
+		push_group(&group.name, child_sources, group.show_empty);
 	}
+	push_group(&rules.rds, rds_child_sources, false);
+	push_group(&rules.scanlib, scanlib_child_sources, false);
+	push_group(&rules.robot, robot_child_sources, false);
+	push_group(&rules.unknown, unknown_child_sources, false);
 
-	//Case insensitive sort by log source name
-	contr_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	sensor_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	client_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	cbox_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	probe_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	rds_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	scanlib_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-	unknown_child_sources.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-
-	for mut source in &mut sensor_child_sources {
-		glog::adjust_sensor_timestamps(&mut source);
+	model::LogSource {
+		name: archive_name,
+		children: { model::LogSourceContents::Sources(sources_vec) },
+		diagnostics: Vec::new(),
 	}
-	for mut source in &mut cbox_child_sources {
-		glog::adjust_sensor_timestamps(&mut source);
+}
+
+// Adds the entries and sub-sources of `source` to `target`. Sub-sources with the same
+// name are merged, entries next to sub-sources go into a sub-source with the name of
+// their parent (as in `SourceTreeBuilder`).
+fn merge_sources(target: &mut model::LogSource, mut source: model::LogSource) {
+	let both_entries = matches!(
+		(&target.children, &source.children),
+		(
+			model::LogSourceContents::Entries(_),
+			model::LogSourceContents::Entries(_)
+		)
+	);
+	if !both_entries {
+		entries_into_child(target);
+		entries_into_child(&mut source);
 	}
-	for mut source in &mut probe_child_sources {
-		glog::adjust_sensor_timestamps(&mut source);
+	match (&mut target.children, source.children) {
+		(model::LogSourceContents::Entries(v), model::LogSourceContents::Entries(mut entries)) => {
+			//Entries of `source` come after the ones already in `target`
+			for mut diagnostic in source.diagnostics {
+				if let model::DiagnosticLocation::Entry(idx) = &mut diagnostic.location {
+					*idx += v.len();
+				}
+				target.diagnostics.push(diagnostic);
+			}
+			v.append(&mut entries);
+		}
+		(model::LogSourceContents::Sources(v), model::LogSourceContents::Sources(sources)) => {
+			target.diagnostics.extend(source.diagnostics);
+			for sub_source in sources {
+				match v.iter_mut().find(|existing| existing.name == sub_source.name) {
+					Some(existing) => merge_sources(existing, sub_source),
+					None => v.push(sub_source),
+				}
+			}
+			v.sort_by_key(|source| source.name.to_lowercase());
+		}
+		_ => unreachable!(), //Both hold sub-sources after `entries_into_child`
 	}
+}
 
-	let contr_logs = model::LogSource {
-		name: "Controller".to_string(),
-		children: { model::LogSourceContents::Sources(contr_child_sources) },
-	};
-	let sensor_logs = model::LogSource {
-		name: "Sensor".to_string(),
-		children: { model::LogSourceContents::Sources(sensor_child_sources) },
-	};
-	let client_logs = model::LogSource {
-		name: "Client".to_string(),
-		children: { model::LogSourceContents::Sources(client_child_sources) },
-	};
-
-	let mut sources_vec = vec![client_logs, contr_logs, sensor_logs];
-
-	if !cbox_child_sources.is_empty() {
-		let cbox_logs = model::LogSource {
-			name: "Connect Box".to_string(),
-			children: { model::LogSourceContents::Sources(cbox_child_sources) },
+fn entries_into_child(source: &mut model::LogSource) {
+	if let model::LogSourceContents::Entries(_) = source.children {
+		let child = model::LogSource {
+			name: source.name.clone(),
+			children: std::mem::replace(
+				&mut source.children,
+				model::LogSourceContents::Sources(Vec::new()),
+			),
+			diagnostics: std::mem::take(&mut source.diagnostics),
 		};
-		sources_vec.push(cbox_logs);
+		source.children = model::LogSourceContents::Sources(vec![child]);
 	}
+}
 
-	if !probe_child_sources.is_empty() {
-		let probe_logs = model::LogSource {
-			name: "Probe".to_string(),
-			children: { model::LogSourceContents::Sources(probe_child_sources) },
-		};
-		sources_vec.push(probe_logs);
+type Archive<'p> = zip::ZipArchive<ProgressReader<'p, std::fs::File>>;
+
+// `count_directory` is false if the archive was opened before, e.g. for another thread.
+// Then only the members read are counted, the directory was counted already.
+fn open_archive<'p>(
+	path: &std::path::Path,
+	progress: &'p Progress,
+	count_directory: bool,
+) -> Result<Archive<'p>, std::io::Error> {
+	let file = std::fs::File::open(path)?;
+	if count_directory {
+		return Ok(zip::ZipArchive::new(progress.archive_reader(file))?);
 	}
+	let (reader, counting) = progress.reopened_archive_reader(file);
+	let archive = zip::ZipArchive::new(reader)?;
+	counting.store(true, Ordering::Relaxed);
+	Ok(archive)
+}
 
-	if !rds_child_sources.is_empty() {
-		let rds_logs = model::LogSource {
-			name: "RDS".to_string(),
-			children: { model::LogSourceContents::Sources(rds_child_sources) },
-		};
-		sources_vec.push(rds_logs);
+// `password` is the one found by `find_password`
+fn by_index<'a, R: std::io::Read + std::io::Seek>(
+	archive: &'a mut zip::ZipArchive<R>,
+	password: Option<&str>,
+	index: usize,
+) -> std::io::Result<zip::read::ZipFile<'a>> {
+	if let Some(password) = password {
+		archive
+			.by_index_decrypt(index, password.as_bytes())?
+			.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+	} else {
+		Ok(archive.by_index(index)?)
 	}
+}
 
-	if !scanlib_child_sources.is_empty() {
-		let scanlib_logs = model::LogSource {
-			name: "ScanLib".to_string(),
-			children: { model::LogSourceContents::Sources(scanlib_child_sources) },
-		};
-		sources_vec.push(scanlib_logs);
-	}
+// Archive members that are parsed into one log source. That is a single member,
+// except for glog files, which are concatenated per group.
+pub struct ParseJob {
+	pub kind: MemberKind,
+	pub name: String,
+	pub indices: std::collections::VecDeque<usize>, //Of the members, in reading order
+	pub size: u64,                                  //Size of the members in the archive
+}
 
-	if !unknown_child_sources.is_empty() {
-		let unknown_logs = model::LogSource {
-			name: "Unknown".to_string(),
-			children: { model::LogSourceContents::Sources(unknown_child_sources) },
-		};
-		sources_vec.push(unknown_logs);
-	}
+#[derive(Copy, Clone)]
+pub enum MemberKind {
+	Glog,
+	Xlog,
+	Robot,
+	RobotXml, //output.xml with the suite and test tree
+	Rds,
+	ScanLib,
+}
 
-	Ok(model::LogSource {
-		name: path.file_name().unwrap().to_string_lossy().to_string(),
-		children: { model::LogSourceContents::Sources(sources_vec) },
+// Members are independent of each other, so they are parsed on several threads.
+// Each thread gets its own `reader` of the archive from `open`, as reading a member
+// of a zip archive needs a mutable archive. The current thread uses `reader`.
+// Returns the parsed log sources in the same order as the jobs, None for the jobs that
+// were not parsed because parsing was cancelled.
+pub fn parse_jobs<A>(
+	archive_name: &str,
+	jobs: &[ParseJob],
+	progress: &Progress,
+	reader: A,
+	open: impl Fn() -> Result<A, std::io::Error> + Sync,
+	parse: impl Fn(&mut A, &ParseJob) -> Result<model::LogSource, std::io::Error> + Sync,
+) -> Result<Vec<Option<model::LogSource>>, std::io::Error> {
+	//Largest jobs first, so that no thread is left with a big one at the end
+	let mut order: Vec<usize> = (0..jobs.len()).collect();
+	order.sort_by_key(|idx| std::cmp::Reverse(jobs[*idx].size));
+	let next = AtomicUsize::new(0);
+	let thread_count = std::thread::available_parallelism()
+		.map_or(1, |n| n.get())
+		.min(jobs.len());
+
+	let worker = |mut reader: A| {
+		let mut parsed = Vec::new();
+		loop {
+			let n = next.fetch_add(1, Ordering::Relaxed);
+			if n >= order.len() || progress.is_cancelled() {
+				return Ok::<_, std::io::Error>(parsed);
+			}
+			let job = &jobs[order[n]];
+			progress.set_current(format!("{}: {}", archive_name, job.name));
+			parsed.push((order[n], parse(&mut reader, job)?));
+		}
+	};
+
+	std::thread::scope(|scope| {
+		let handles: Vec<_> = (1..thread_count)
+			.map(|_| scope.spawn(|| worker(open()?)))
+			.collect();
+		let mut parsed = worker(reader)?;
+		for handle in handles {
+			match handle.join() {
+				Ok(result) => parsed.extend(result?),
+				Err(panic) => std::panic::resume_unwind(panic),
+			}
+		}
+
+		let mut log_sources: Vec<Option<model::LogSource>> = jobs.iter().map(|_| None).collect();
+		for (idx, log_source) in parsed {
+			log_sources[idx] = Some(log_source);
+		}
+		Ok(log_sources)
 	})
 }
 
+// Members are read through `Progress::member_reader`, so that parsing a large member
+// stops once cancelled. The archive itself is not affected by the early end of input.
+fn parse_job<R: std::io::Read + std::io::Seek>(
+	archive: &mut zip::ZipArchive<R>,
+	password: Option<&str>,
+	job: &ParseJob,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	match job.kind {
+		MemberKind::Glog => {
+			let reader = ConcatZipReader::new(archive, password, job.indices.clone());
+			Ok(parse_member(job, progress.member_reader(reader)))
+		}
+		_ => {
+			let reader = by_index(archive, password, job.indices[0])?;
+			Ok(parse_member(job, progress.member_reader(reader)))
+		}
+	}
+}
+
+// `reader` reads the member of the job, or all of them concatenated for glog files
+pub fn parse_member(job: &ParseJob, reader: impl std::io::Read) -> model::LogSource {
+	let root = model::LogSource {
+		name: job.name.clone(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	match job.kind {
+		MemberKind::Glog => glog::to_log_entries(reader, root),
+		MemberKind::Xlog => xlog::to_log_entries(reader, root),
+		MemberKind::Rds => rds_log::to_log_entries(reader, root),
+		MemberKind::ScanLib => scanlib_log::to_log_entries(reader, root),
+		MemberKind::RobotXml => robot_xml::to_log_entries(reader, root),
+		MemberKind::Robot => match robot_log::to_log_entries(reader, job.name.clone()) {
+			Ok(source) => source,
+			Err(e) => {
+				//Keep an empty log source, so the diagnostic shows up in its place
+				let mut source = root;
+				diagnostics::report_at(
+					&mut source,
+					0,
+					model::DiagnosticKind::UnparsableFile,
+					&format!("{}: {}", job.name, e),
+				);
+				source
+			}
+		},
+	}
+}
+
 struct ConcatZipReader<'a, R: std::io::Read + std::io::Seek> {
 	archive: &'a mut zip::ZipArchive<R>,
+	password: Option<&'a str>,
 	file: Option<zip::read::ZipFile<'a>>,
 	indices: std::collections::VecDeque<usize>,
 }
@@ -419,10 +698,12 @@ struct ConcatZipReader<'a, R: std::io::Read + std::io::Seek> {
 impl<'a, R: std::io::Read + std::io::Seek> ConcatZipReader<'a, R> {
 	fn new(
 		archive: &'a mut zip::ZipArchive<R>,
+		password: Option<&'a str>,
 		indices: std::collections::VecDeque<usize>,
 	) -> ConcatZipReader<'a, R> {
 		ConcatZipReader {
 			archive,
+			password,
 			file: None,
 			indices,
 		}
@@ -447,13 +728,7 @@ impl<'a, R: std::io::Read + std::io::Seek> std::io::Read for ConcatZipReader<'a,
 				match self.indices.pop_front() {
 					Some(idx) => {
 						//Need to open new file
-						let f = if let Some(password) = SFILE_PASSWORD {
-							self.archive
-								.by_index_decrypt(idx, password.as_bytes())?
-								.unwrap() //TODO: 21.06.2020: Handle InvalidPassword!
-						} else {
-							self.archive.by_index(idx)?
-						};
+						let f = by_index(self.archive, self.password, idx)?;
 						unsafe {
 							//Due to the fact that file references archive and both are in the same struct,
 						 //this cannot be done in safe Rust
@@ -480,6 +755,7 @@ struct ZipEntry {
 	name: String,
 	group_name: String,
 	index: usize,
+	size: u64, //Compressed size
 }
 
 fn get_group_name(s: &str) -> String {
@@ -512,4 +788,36 @@ fn get_group_name(s: &str) -> String {
 	//Restore storage type suffix
 	s.push_str(storage_type);
 	s
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::Write;
+
+	#[test]
+	fn test_from_bytes() {
+		let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+		for (name, data) in [
+			("contr_Hwa.glog", "[tq|1568208334470]:[s|4]:[i|12]:[m|New.]\n"),
+			("RDS/agent.log", "2020-12-01 15:46:19.122|INFO|RDSAgent|Started.\n"),
+			("core.dump", "not a log"),
+		] {
+			writer.start_file(name, zip::write::FileOptions::default()).unwrap();
+			writer.write_all(data.as_bytes()).unwrap();
+		}
+		let bytes = writer.finish().unwrap().into_inner();
+
+		let progress = Progress::new();
+		let path = std::path::Path::new("run.sfile.gz");
+		let root = from_bytes(&bytes, path, &SfileRules::default(), &progress).unwrap();
+		assert_eq!(root.name, "run.sfile.gz");
+		let names: Vec<&str> = match &root.children {
+			model::LogSourceContents::Sources(v) => {
+				v.iter().map(|source| source.name.as_str()).collect()
+			}
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(names, vec!["Client", "Controller", "Sensor", "RDS"]);
+	}
+}
@@ -0,0 +1,239 @@
+use regex::Regex;
+use serde::Deserialize;
+
+// Sfile categorisation rules ---------------------------------------------------------
+// Decide under which top-level log source the glog files of an sfile (or tar bundle)
+// are shown, e.g. "contr_Hwa" as "Hwa" under "Controller". The defaults ship with
+// Sherlog (see `sfile_rules.toml` next to this file, which documents the format).
+// An `sfile_rules.toml` in a configuration directory replaces them, the one with the
+// highest precedence wins (see `config::config_dirs`). So new hardware variants only
+// need a new rule, not a new release.
+// ------------------------------------------------------------------------------------
+
+const RULES_FILE: &str = "sfile_rules.toml";
+const DEFAULT_RULES: &str = include_str!("sfile_rules.toml");
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct RulesFile {
+	client: String,
+	rds: String,
+	scanlib: String,
+	robot: String,
+	unknown: String,
+	group: Vec<GroupDefinition>,
+}
+
+impl Default for RulesFile {
+	fn default() -> RulesFile {
+		RulesFile {
+			client: "Client".to_string(),
+			rds: "RDS".to_string(),
+			scanlib: "ScanLib".to_string(),
+			robot: "Robot Framework".to_string(),
+			unknown: "Unknown".to_string(),
+			group: Vec::new(),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct GroupDefinition {
+	name: String,
+	prefix: Option<String>,
+	#[serde(default)]
+	strip_prefix: bool,
+	regex: Option<String>,
+	#[serde(default)]
+	adjust_sensor_timestamps: bool,
+	#[serde(default)]
+	show_empty: bool,
+	#[serde(default)]
+	time_shift: bool,
+}
+
+pub struct SfileRules {
+	pub client: String,
+	pub rds: String,
+	pub scanlib: String,
+	pub robot: String,
+	pub unknown: String,
+	pub groups: Vec<SourceGroup>,
+}
+
+pub struct SourceGroup {
+	pub name: String,
+	pub adjust_sensor_timestamps: bool,
+	pub show_empty: bool,
+	pub time_shift: bool, //Shifted by the "Sensor time shift" of its archive
+	matcher: Matcher,
+}
+
+enum Matcher {
+	Prefix { prefix: String, strip: bool },
+	Regex(Regex),
+}
+
+// Where a glog file goes inside its group
+#[derive(Debug, PartialEq)]
+pub struct Placement {
+	pub board: Option<String>, //Log source between the group and the file, if any
+	pub name: String,          //Shown name of the file
+}
+
+impl SourceGroup {
+	// `name` is the name of the glog file without ring buffer index, e.g. "contr_Hwa"
+	pub fn place(&self, name: &str) -> Option<Placement> {
+		match &self.matcher {
+			Matcher::Prefix { prefix, strip } => {
+				let stripped = name.strip_prefix(prefix.as_str())?;
+				Some(Placement {
+					board: None,
+					name: if *strip { stripped } else { name }.to_string(),
+				})
+			}
+			Matcher::Regex(regex) => {
+				let caps = regex.captures(name)?;
+				Some(Placement {
+					board: caps.name("board").map(|board| board.as_str().to_string()),
+					name: caps
+						.name("name")
+						.map_or(name, |name| name.as_str())
+						.to_string(),
+				})
+			}
+		}
+	}
+}
+
+impl SfileRules {
+	// Loads the rules with the highest precedence, or the bundled defaults if there are none
+	pub fn load() -> SfileRules {
+		if let Some(path) = crate::config::config_files(RULES_FILE).pop() {
+			match std::fs::read_to_string(&path)
+				.map_err(|e| e.to_string())
+				.and_then(|s| SfileRules::from_toml(&s))
+			{
+				Ok(rules) => {
+					log::info!("Loaded sfile rules from {}", path.display());
+					return rules;
+				}
+				Err(e) => log::warn!("Ignoring {}: {}", path.display(), e),
+			}
+		}
+		SfileRules::default()
+	}
+
+	pub fn from_toml(definition: &str) -> Result<SfileRules, String> {
+		let file: RulesFile = toml::from_str(definition).map_err(|e| e.to_string())?;
+		let mut groups = Vec::new();
+		for group in file.group {
+			let matcher = match (group.prefix, group.regex) {
+				(Some(prefix), None) => Matcher::Prefix {
+					prefix,
+					strip: group.strip_prefix,
+				},
+				(None, Some(regex)) => Matcher::Regex(
+					Regex::new(&regex).map_err(|e| format!("Group '{}': {}", group.name, e))?,
+				),
+				_ => {
+					return Err(format!(
+						"Group '{}' needs either a prefix or a regex",
+						group.name
+					))
+				}
+			};
+			groups.push(SourceGroup {
+				name: group.name,
+				adjust_sensor_timestamps: group.adjust_sensor_timestamps,
+				show_empty: group.show_empty,
+				time_shift: group.time_shift,
+				matcher,
+			});
+		}
+		Ok(SfileRules {
+			client: file.client,
+			rds: file.rds,
+			scanlib: file.scanlib,
+			robot: file.robot,
+			unknown: file.unknown,
+			groups,
+		})
+	}
+}
+
+impl Default for SfileRules {
+	fn default() -> SfileRules {
+		SfileRules::from_toml(DEFAULT_RULES).expect("Bundled sfile rules are invalid")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn place(rules: &SfileRules, name: &str) -> Option<(String, Placement)> {
+		rules
+			.groups
+			.iter()
+			.find_map(|group| Some((group.name.clone(), group.place(name)?)))
+	}
+
+	#[test]
+	fn test_default_rules() {
+		let rules = SfileRules::default();
+		assert_eq!(rules.client, "Client");
+		assert_eq!(rules.unknown, "Unknown");
+		assert_eq!(
+			place(&rules, "contr_Hwa"),
+			Some((
+				"Controller".to_string(),
+				Placement {
+					board: None,
+					name: "Hwa".to_string()
+				}
+			))
+		);
+		assert_eq!(
+			place(&rules, "cfm2_Motion_p"),
+			Some((
+				"Sensor".to_string(),
+				Placement {
+					board: Some("cfm2".to_string()),
+					name: "Motion_p".to_string()
+				}
+			))
+		);
+		assert_eq!(place(&rules, "ap21_Probe").unwrap().1.name, "Probe");
+		assert!(rules.groups[3].adjust_sensor_timestamps);
+		assert!(rules.groups.iter().all(|group| group.time_shift));
+		assert_eq!(place(&rules, "axis"), None); //Boards need a log name
+		assert_eq!(place(&rules, "cfm_x_y").unwrap().1.name, "x_y");
+		assert_eq!(place(&rules, "mystery_Log"), None);
+	}
+
+	#[test]
+	fn test_custom_rules() {
+		let rules = SfileRules::from_toml(
+			r#"
+			unknown = "Other"
+
+			[[group]]
+			name = "Gateway"
+			prefix = "gw_"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(rules.client, "Client");
+		assert_eq!(rules.unknown, "Other");
+		assert_eq!(place(&rules, "gw_Router").unwrap().1.name, "gw_Router");
+		assert_eq!(place(&rules, "contr_Hwa"), None);
+		assert!(!rules.groups[0].time_shift);
+
+		assert!(
+			SfileRules::from_toml("[[group]]\nname = \"Both\"\nprefix = \"a\"\nregex = \"b\"")
+				.is_err()
+		);
+		assert!(SfileRules::from_toml("[[group]]\nname = \"Bad\"\nregex = \"(\"").is_err());
+	}
+}
@@ -0,0 +1,450 @@
+use super::super::model;
+use super::datetime_utils;
+use super::datetime_utils::{YearRollback, YearlessDateTime};
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
+
+use regex::Regex;
+
+use std::io::BufRead;
+use std::io::BufReader;
+
+// QNX slog2info parser --------------------------------------------------------------
+// Text dump of the QNX slogger2 buffers, as printed by `slog2info`. Example:
+// Jan 01 00:00:04.637             syslogd.16392         slog      0  syslogd starting
+// Jan 01 00:00:05.012         io-pkt-v6-hc.8204       default  WARNING  12  link down
+//
+// Columns: timestamp, buffer set name and process ID, buffer name, severity, code and
+// message. The severity column is only present in verbose output (`slog2info -v`)
+// and is either the slogger2 severity name or its number. A file is either verbose or
+// not, this is decided from its first records.
+//
+// Timestamps lack the year, it is inferred from the file modification time.
+// There is one log source per buffer set, which is one per process.
+// Lines that do not start with a timestamp continue the message of the previous entry.
+// ------------------------------------------------------------------------------------
+
+// Number of records used to tell verbose from non-verbose output
+const LAYOUT_SAMPLE_RECORDS: usize = 20;
+
+pub fn to_log_entries(
+	reader: impl std::io::Read,
+	mut root: model::LogSource,
+	last_year: i32,
+) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	let mut parser = Slog2Parser::new();
+	parser.parse(reader, &mut root);
+	parser.flush_entry();
+	parser.push_records(&mut builder, last_year);
+	builder.build(root)
+}
+
+// State of the parser between the lines of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the layout stays the one decided from the
+// first records, and the last record may still get continuation lines.
+pub struct Slog2Parser {
+	record_start_re: Regex,
+	verbose_re: Regex,
+	plain_re: Regex,
+	//A non-verbose line whose message starts with a number looks like a verbose one,
+	//e.g. "slog 0 12 bytes". So the layout is decided once per file, from its first
+	//records: it is verbose if all of them have a severity column.
+	verbose: Option<bool>,
+	sample: Vec<(u64, Vec<u8>)>, //Lines read before the layout is known
+	sample_records: usize,
+	//Years can only be assigned once all entries are known
+	records: Vec<Slog2Record>,
+	current: Option<Slog2Record>,
+	offset: u64, //Of the next line
+}
+
+struct Slog2Record {
+	log_entry: model::LogEntry,
+	buffer_set: String,
+	yearless: YearlessDateTime,
+	diagnostics: EntryDiagnostics,
+}
+
+impl Default for Slog2Parser {
+	fn default() -> Slog2Parser {
+		Slog2Parser::new()
+	}
+}
+
+impl Slog2Parser {
+	pub fn new() -> Slog2Parser {
+		Slog2Parser {
+			record_start_re: Regex::new(r"^[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}")
+				.expect("Invalid regex pattern"),
+			verbose_re: Regex::new(
+				r"^(?P<time>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}(?:\.\d+)?)\s+(?P<set>\S+)\s+(?P<buffer>\S+)\s+(?P<severity>[A-Z]+[12]?|[0-7])\s+(?P<code>\d+)(?:\s+(?P<message>.*))?$",
+			)
+			.expect("Invalid regex pattern"),
+			plain_re: Regex::new(
+				r"^(?P<time>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}(?:\.\d+)?)\s+(?P<set>\S+)\s+(?P<buffer>\S+)\s+(?P<code>\d+)(?:\s+(?P<message>.*))?$",
+			)
+			.expect("Invalid regex pattern"),
+			verbose: None,
+			sample: Vec::new(),
+			sample_records: 0,
+			records: Vec::new(),
+			current: None,
+			offset: 0,
+		}
+	}
+
+	// Parses the lines of `reader`. The last record is kept open, as the next lines may
+	// still continue it (see `flush_entry`). If there are fewer than
+	// LAYOUT_SAMPLE_RECORDS records so far, the layout is decided from those.
+	pub fn parse(&mut self, reader: impl std::io::Read, root: &mut model::LogSource) {
+		let mut bufreader = BufReader::new(reader);
+		let mut buf = Vec::<u8>::with_capacity(512);
+
+		loop {
+			let line_offset = self.offset;
+			buf.clear();
+			match bufreader.read_until(b'\n', &mut buf) {
+				Ok(0) => break,
+				Ok(bytes_read) => self.offset += bytes_read as u64,
+				Err(e) => {
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::ReadError,
+						&e.to_string(),
+					);
+					break;
+				}
+			}
+
+			if let Some(verbose) = self.verbose {
+				self.parse_line(verbose, &buf, line_offset, root);
+			} else {
+				if self
+					.record_start_re
+					.is_match(&String::from_utf8_lossy(&buf))
+				{
+					self.sample_records += 1;
+				}
+				self.sample.push((line_offset, buf.clone()));
+				if self.sample_records >= LAYOUT_SAMPLE_RECORDS {
+					self.end_sample(root);
+				}
+			}
+		}
+		if self.verbose.is_none() {
+			self.end_sample(root);
+		}
+	}
+
+	// Decides the layout from the sampled lines and parses them. Without any record
+	// among them, the layout is left open, the lines are no records either way.
+	fn end_sample(&mut self, root: &mut model::LogSource) {
+		let verbose = self.sample_records > 0
+			&& self.sample.iter().all(|(_, line)| {
+				let line = String::from_utf8_lossy(line);
+				let line = line.trim_end_matches(['\r', '\n']);
+				!self.record_start_re.is_match(line)
+					|| self
+						.verbose_re
+						.captures(line)
+						.and_then(|caps| to_record(&caps))
+						.is_some()
+			});
+		if self.sample_records > 0 {
+			self.verbose = Some(verbose);
+		}
+		for (offset, line) in std::mem::take(&mut self.sample) {
+			self.parse_line(verbose, &line, offset, root);
+		}
+	}
+
+	fn parse_line(
+		&mut self,
+		verbose: bool,
+		buf: &[u8],
+		line_offset: u64,
+		root: &mut model::LogSource,
+	) {
+		let mut line_end = buf.len();
+		if buf[..line_end].last() == Some(&b'\n') {
+			line_end -= 1;
+		}
+		if buf[..line_end].last() == Some(&b'\r') {
+			line_end -= 1;
+		}
+		let line = String::from_utf8_lossy(&buf[..line_end]);
+		let malformed_utf8 = matches!(line, std::borrow::Cow::Owned(_));
+
+		let re = if verbose {
+			&self.verbose_re
+		} else {
+			&self.plain_re
+		};
+		if let Some((log_entry, buffer_set, yearless)) =
+			re.captures(&line).and_then(|caps| to_record(&caps))
+		{
+			self.flush_entry();
+			self.current = Some(Slog2Record {
+				log_entry,
+				buffer_set,
+				yearless,
+				diagnostics: EntryDiagnostics::default(),
+			});
+		} else if let Some(record) = &mut self.current {
+			//Multi-line message continuation
+			record.log_entry.message.push('\n');
+			record.log_entry.message.push_str(&line);
+		} else if !line.trim().is_empty() {
+			let kind = if malformed_utf8 {
+				DiagnosticKind::MalformedUtf8
+			} else {
+				DiagnosticKind::UnrecognizedLine
+			};
+			diagnostics::report_at(root, line_offset, kind, &line);
+			return;
+		}
+		if malformed_utf8 {
+			if let Some(record) = &mut self.current {
+				record
+					.diagnostics
+					.report(DiagnosticKind::MalformedUtf8, &line);
+			}
+		}
+	}
+
+	// Ends the last record, it does not get any more continuation lines
+	pub fn flush_entry(&mut self) {
+		if let Some(record) = self.current.take() {
+			self.records.push(record);
+		}
+	}
+
+	// Pushes the ended records into `builder`. `last_year` is the year of the newest one.
+	pub fn push_records(&mut self, builder: &mut SourceTreeBuilder, last_year: i32) {
+		let mut rollback = YearRollback::new(last_year);
+		for record in self.records.iter_mut().rev() {
+			if let Some(timestamp) = rollback.resolve(record.yearless) {
+				record.log_entry.timestamp = timestamp;
+			} else {
+				//e.g. Feb 29 in the wrong year
+				record.diagnostics.report(
+					DiagnosticKind::MalformedTimestamp,
+					&format!("{:?} in {}", record.yearless, rollback.year()),
+				);
+			}
+		}
+
+		for mut record in self.records.drain(..) {
+			builder.push(
+				&[record.buffer_set],
+				record.log_entry,
+				&mut record.diagnostics,
+			);
+		}
+	}
+}
+
+fn to_record(caps: &regex::Captures) -> Option<(model::LogEntry, String, YearlessDateTime)> {
+	let yearless = datetime_utils::parse_yearless(&caps["time"])?;
+
+	let mut log_entry = model::LogEntry {
+		severity: model::LogLevel::Info,
+		message: caps.name("message").map_or("", |m| m.as_str()).to_string(),
+		..Default::default()
+	};
+
+	//Buffer set name and process ID, e.g. "syslogd.16392"
+	let buffer_set = match caps["set"].rsplit_once('.') {
+		Some((name, pid)) if !name.is_empty() && pid.parse::<u32>().is_ok() => {
+			log_entry.custom_fields.insert(
+				std::borrow::Cow::Borrowed("ProcessId"),
+				model::CustomField::UInt32(pid.parse::<u32>().unwrap()), //Checked above
+			);
+			name
+		}
+		_ => &caps["set"],
+	};
+
+	log_entry.custom_fields.insert(
+		std::borrow::Cow::Borrowed("Buffer"),
+		model::CustomField::String(caps["buffer"].to_string()),
+	);
+
+	if let Some(severity) = caps.name("severity") {
+		//Not a slogger2 severity, so this line is no slog2info record
+		let qnx_sev = QnxSeverity::from_str(severity.as_str())?;
+		log_entry.severity = normalize_qnx_sev(qnx_sev);
+		//Keep the original severity, LogLevel cannot tell NOTICE from INFO, for example
+		log_entry.custom_fields.insert(
+			std::borrow::Cow::Borrowed("QnxSeverity"),
+			model::CustomField::String(qnx_sev.name().to_string()),
+		);
+	}
+
+	log_entry.custom_fields.insert(
+		std::borrow::Cow::Borrowed("Code"),
+		model::CustomField::UInt32(caps["code"].parse::<u32>().ok()?),
+	);
+
+	Some((log_entry, buffer_set.to_string(), yearless))
+}
+
+fn normalize_qnx_sev(qnx_sev: QnxSeverity) -> model::LogLevel {
+	match qnx_sev {
+		QnxSeverity::Shutdown => model::LogLevel::Critical,
+		QnxSeverity::Critical => model::LogLevel::Critical,
+		QnxSeverity::Error => model::LogLevel::Error,
+		QnxSeverity::Warning => model::LogLevel::Warning,
+		QnxSeverity::Notice => model::LogLevel::Info,
+		QnxSeverity::Info => model::LogLevel::Info,
+		QnxSeverity::Debug1 => model::LogLevel::Debug,
+		QnxSeverity::Debug2 => model::LogLevel::Trace,
+	}
+}
+
+// slogger2 severities (SLOG2_SHUTDOWN to SLOG2_DEBUG2), as used by the controller
+// firmware's Severity enum. Lower values are more severe.
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum QnxSeverity {
+	Shutdown = 0, //Sporadic abnormal event, whole system corrupted
+	Critical = 1, //Sporadic abnormal event, functionality corrupted
+	Error = 2,    //Sporadic abnormal event, functionality immediately affected
+	Warning = 3,  //Sporadic abnormal event, functionality not immediately affected
+	Notice = 4,   //Sporadic data, business content
+	Info = 5,     //Sporadic data, generic content
+	Debug1 = 6,   //Low bandwidth debug data, business content
+	Debug2 = 7,   //High bandwidth debug data, generic content
+}
+
+impl QnxSeverity {
+	#[rustfmt::skip]
+	fn from_str(value: &str) -> Option<QnxSeverity> {
+		match value {
+			"SHUTDOWN" | "0" => Some(QnxSeverity::Shutdown),
+			"CRITICAL" | "1" => Some(QnxSeverity::Critical),
+			"ERROR"    | "2" => Some(QnxSeverity::Error),
+			"WARNING"  | "3" => Some(QnxSeverity::Warning),
+			"NOTICE"   | "4" => Some(QnxSeverity::Notice),
+			"INFO"     | "5" => Some(QnxSeverity::Info),
+			"DEBUG1"   | "6" => Some(QnxSeverity::Debug1),
+			"DEBUG2"   | "7" => Some(QnxSeverity::Debug2),
+			_ => None,
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			QnxSeverity::Shutdown => "SHUTDOWN",
+			QnxSeverity::Critical => "CRITICAL",
+			QnxSeverity::Error => "ERROR",
+			QnxSeverity::Warning => "WARNING",
+			QnxSeverity::Notice => "NOTICE",
+			QnxSeverity::Info => "INFO",
+			QnxSeverity::Debug1 => "DEBUG1",
+			QnxSeverity::Debug2 => "DEBUG2",
+		}
+	}
+}
+
+// from_file -------------------------------------------------------------------------
+// Reads slog2info output from disk and returns the parsed log source.
+// The year of the timestamps is derived from the file modification time.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = std::fs::File::open(path)?;
+	let last_year = datetime_utils::modified_year(&file);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(progress.reader(file), root, last_year))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(log: &str) -> Vec<model::LogSource> {
+		let root = model::LogSource {
+			name: "slog2info.txt".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		match to_log_entries(log.as_bytes(), root, 2021).children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources"),
+		}
+	}
+
+	fn entries(source: &model::LogSource) -> &Vec<model::LogEntry> {
+		match &source.children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		}
+	}
+
+	#[test]
+	fn test_parse_slog2info() {
+		let sources = parse(concat!(
+			"Jan 01 00:00:04.637             syslogd.16392         slog  INFO   0  syslogd starting\n",
+			"Jan 01 00:00:05.012         io-pkt-v6-hc.8204       default  NOTICE  12  link up\n",
+			"  second line\n",
+			"Jan 01 00:00:05.100         io-pkt-v6-hc.8204       default       7   0  rx 64 bytes\n",
+		));
+		assert_eq!(sources.len(), 2);
+		assert_eq!(sources[0].name, "io-pkt-v6-hc");
+		assert_eq!(sources[1].name, "syslogd");
+
+		let entries = entries(&sources[0]);
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].severity, model::LogLevel::Info);
+		assert_eq!(entries[0].message, "link up\n  second line");
+		assert_eq!(
+			entries[0]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			"2021-01-01T00:00:05.012Z"
+		);
+		assert!(matches!(
+			entries[0].custom_fields.get("QnxSeverity"),
+			Some(model::CustomField::String(sev)) if sev == "NOTICE"
+		));
+		assert!(matches!(
+			entries[0].custom_fields.get("ProcessId"),
+			Some(model::CustomField::UInt32(8204))
+		));
+		assert_eq!(entries[1].severity, model::LogLevel::Trace);
+		assert!(matches!(
+			entries[1].custom_fields.get("QnxSeverity"),
+			Some(model::CustomField::String(sev)) if sev == "DEBUG2"
+		));
+	}
+
+	#[test]
+	fn test_parse_slog2info_non_verbose() {
+		//The message of the second line starts with a number, like a verbose line
+		let sources = parse(concat!(
+			"Jan 01 00:00:04.637             syslogd.16392         slog      0  syslogd starting\n",
+			"Jan 01 00:00:04.640             syslogd.16392         slog      0  12 bytes\n",
+		));
+		let entries = entries(&sources[0]);
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[1].severity, model::LogLevel::Info);
+		assert_eq!(entries[1].message, "12 bytes");
+		assert!(!entries[1].custom_fields.contains_key("QnxSeverity"));
+		assert!(matches!(
+			entries[1].custom_fields.get("Code"),
+			Some(model::CustomField::UInt32(0))
+		));
+	}
+}
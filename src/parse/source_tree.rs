@@ -0,0 +1,97 @@
+use super::super::model;
+use super::diagnostics::EntryDiagnostics;
+
+use std::collections::HashMap;
+
+// Log source tree builder -----------------------------------------------------------
+// Collects log entries by their source path (e.g. ["Scanner", "Motor"]) and turns
+// them into a nested `model::LogSource` tree, sorted case insensitively by name.
+//
+// A `model::LogSource` either holds sources or entries, never both. If a node has
+// entries and children at the same time, its own entries go into an extra child
+// with the same name as the node. Entries without any source path end up directly
+// in the root if there are no other sources, otherwise in "Unknown (None)".
+// Diagnostics of an entry go to the log source that ends up holding the entry.
+// ------------------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct SourceTreeBuilder {
+	root: SourceNode,
+	source_name: String, //File that is parsed, for diagnostics
+}
+
+#[derive(Default)]
+struct SourceNode {
+	entries: Vec<model::LogEntry>,
+	diagnostics: Vec<model::Diagnostic>,
+	children: HashMap<String, SourceNode>,
+}
+
+impl SourceTreeBuilder {
+	pub fn new(source_name: &str) -> SourceTreeBuilder {
+		SourceTreeBuilder {
+			source_name: source_name.to_string(),
+			..Default::default()
+		}
+	}
+
+	pub fn push<S: AsRef<str>>(
+		&mut self,
+		path: &[S],
+		log_entry: model::LogEntry,
+		entry_diagnostics: &mut EntryDiagnostics,
+	) {
+		let mut node = &mut self.root;
+		for name in path {
+			node = node.children.entry(name.as_ref().to_string()).or_default();
+		}
+		entry_diagnostics.attach(&self.source_name, &mut node.diagnostics, node.entries.len());
+		node.entries.push(log_entry);
+	}
+
+	pub fn build(self, mut root: model::LogSource) -> model::LogSource {
+		let (children, mut diagnostics) = self.root.into_contents("Unknown (None)");
+		root.children = children;
+		root.diagnostics.append(&mut diagnostics);
+		root
+	}
+}
+
+impl SourceNode {
+	// Returns the diagnostics of the node's own entries if it only has entries
+	fn into_contents(
+		self,
+		own_entries_name: &str,
+	) -> (model::LogSourceContents, Vec<model::Diagnostic>) {
+		if self.children.is_empty() {
+			return (
+				model::LogSourceContents::Entries(self.entries),
+				self.diagnostics,
+			);
+		}
+
+		let mut v: Vec<model::LogSource> = self
+			.children
+			.into_iter()
+			.map(|(name, node)| {
+				let (children, diagnostics) = node.into_contents(&name);
+				model::LogSource {
+					children,
+					name,
+					diagnostics,
+				}
+			})
+			.collect();
+		if !self.entries.is_empty() {
+			v.push(model::LogSource {
+				name: own_entries_name.to_string(),
+				children: { model::LogSourceContents::Entries(self.entries) },
+				diagnostics: self.diagnostics,
+			});
+		}
+
+		//Case insensitive sort by log source name
+		v.sort_by_key(|source| source.name.to_lowercase());
+		(model::LogSourceContents::Sources(v), Vec::new())
+	}
+}
@@ -0,0 +1,481 @@
+use super::super::model;
+use super::datetime_utils;
+use super::datetime_utils::{YearRollback, YearlessDateTime};
+use super::diagnostics;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
+use super::source_tree::SourceTreeBuilder;
+use model::DiagnosticKind;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use std::io::BufRead;
+use std::io::BufReader;
+
+// Syslog parser ---------------------------------------------------------------------
+// Handles both syslog flavours as written to files by syslogd, rsyslog, etc.
+//
+// RFC 3164 (BSD syslog), priority is optional in files:
+// <34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick on /dev/pts/8
+// Oct  1 08:00:01 gateway CRON[1234]: (root) CMD (run-parts /etc/cron.hourly)
+// rsyslog writes the same layout with an RFC 3339 timestamp instead:
+// 2023-10-11T22:14:15.003+02:00 mymachine su[230]: 'su root' failed
+//
+// RFC 5424:
+// <165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application"] An application event
+//
+// RFC 3164 timestamps have no year. We assume that the last entry was logged in
+// `last_year` (taken from the file modification time) and count back one year for
+// every December -> January rollover between an entry and the end of the file.
+//
+// Entries are grouped by hostname and then by app-name (the tag in RFC 3164).
+// Lines that are not syslog records continue the message of the previous entry.
+// ------------------------------------------------------------------------------------
+
+pub fn to_log_entries(
+	reader: impl std::io::Read,
+	mut root: model::LogSource,
+	last_year: i32,
+) -> model::LogSource {
+	let mut builder = SourceTreeBuilder::new(&root.name);
+	let mut parser = SyslogParser::new();
+	parser.parse(reader, &mut root);
+	parser.flush_entry();
+	parser.push_records(&mut builder, last_year);
+	builder.build(root)
+}
+
+// State of the parser between the lines of a log. Follow mode keeps it between the
+// chunks that are appended to the file, so the last record may still get continuation
+// lines from the next chunk.
+pub struct SyslogParser {
+	rfc5424_re: Regex,
+	rfc3164_re: Regex,
+	tag_re: Regex,
+	//RFC 3164 entries need to be post-processed once we know how many years they span
+	records: Vec<SyslogRecord>,
+	current: Option<SyslogRecord>,
+	offset: u64, //Of the next line
+}
+
+impl Default for SyslogParser {
+	fn default() -> SyslogParser {
+		SyslogParser::new()
+	}
+}
+
+impl SyslogParser {
+	pub fn new() -> SyslogParser {
+		SyslogParser {
+			rfc5424_re: Regex::new(r"^(?:<(\d{1,3})>)?1 (\S+) (\S+) (\S+) (\S+) (\S+) (.*)$")
+				.expect("Invalid regex pattern"),
+			rfc3164_re: Regex::new(
+				r"^(?:<(\d{1,3})>)?(?:([A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2})|(\d{4}-\d{2}-\d{2}T\S+)) (\S+) ?(.*)$",
+			)
+			.expect("Invalid regex pattern"),
+			tag_re: Regex::new(r"^([^\s:\[]+)(?:\[([^\]]*)\])?: ?(.*)$")
+				.expect("Invalid regex pattern"),
+			records: Vec::new(),
+			current: None,
+			offset: 0,
+		}
+	}
+
+	// Parses the lines of `reader`. The last record is kept open, as the next lines may
+	// still continue it (see `flush_entry`).
+	pub fn parse(&mut self, reader: impl std::io::Read, root: &mut model::LogSource) {
+		let mut bufreader = BufReader::new(reader);
+		let mut buf = Vec::<u8>::with_capacity(512);
+
+		loop {
+			let line_offset = self.offset;
+			buf.clear();
+			match bufreader.read_until(b'\n', &mut buf) {
+				Ok(0) => break,
+				Ok(bytes_read) => self.offset += bytes_read as u64,
+				Err(e) => {
+					diagnostics::report_at(
+						root,
+						line_offset,
+						DiagnosticKind::ReadError,
+						&e.to_string(),
+					);
+					break;
+				}
+			}
+			let mut line_end = buf.len();
+			if buf[..line_end].last() == Some(&b'\n') {
+				line_end -= 1;
+			}
+			if buf[..line_end].last() == Some(&b'\r') {
+				line_end -= 1;
+			}
+			let line = String::from_utf8_lossy(&buf[..line_end]);
+			let malformed_utf8 = matches!(line, std::borrow::Cow::Owned(_));
+
+			let record = if let Some(caps) = self.rfc5424_re.captures(&line) {
+				parse_rfc5424(&caps)
+			} else if let Some(caps) = self.rfc3164_re.captures(&line) {
+				parse_rfc3164(&caps, &self.tag_re)
+			} else {
+				None
+			};
+
+			if let Some(mut record) = record {
+				if malformed_utf8 {
+					record
+						.diagnostics
+						.report(DiagnosticKind::MalformedUtf8, &line);
+				}
+				self.flush_entry();
+				self.current = Some(record);
+			} else if let Some(record) = &mut self.current {
+				//Multi-line message continuation
+				if malformed_utf8 {
+					record
+						.diagnostics
+						.report(DiagnosticKind::MalformedUtf8, &line);
+				}
+				record.log_entry.message.push('\n');
+				record.log_entry.message.push_str(&line);
+			} else if !line.trim().is_empty() {
+				let kind = if malformed_utf8 {
+					DiagnosticKind::MalformedUtf8
+				} else {
+					DiagnosticKind::UnrecognizedLine
+				};
+				diagnostics::report_at(root, line_offset, kind, &line);
+			}
+		}
+	}
+
+	// Ends the last record, it does not get any more continuation lines
+	pub fn flush_entry(&mut self) {
+		if let Some(record) = self.current.take() {
+			self.records.push(record);
+		}
+	}
+
+	// Pushes the ended records into `builder`. `last_year` is the year of the newest one.
+	pub fn push_records(&mut self, builder: &mut SourceTreeBuilder, last_year: i32) {
+		//Walk backwards from the newest record to infer the year of RFC 3164 timestamps
+		let mut rollback = YearRollback::new(last_year);
+		for record in self.records.iter_mut().rev() {
+			if let Some(yearless) = record.yearless {
+				if let Some(timestamp) = rollback.resolve(yearless) {
+					record.log_entry.timestamp = timestamp;
+				} else {
+					//e.g. Feb 29 in the wrong year
+					record.diagnostics.report(
+						DiagnosticKind::MalformedTimestamp,
+						&format!("{:?} in {}", yearless, rollback.year()),
+					);
+				}
+			}
+		}
+
+		for mut record in self.records.drain(..) {
+			builder.push(&record.path, record.log_entry, &mut record.diagnostics);
+		}
+	}
+}
+
+struct SyslogRecord {
+	log_entry: model::LogEntry,
+	path: Vec<String>,
+	//Set for RFC 3164 timestamps, which lack the year
+	yearless: Option<YearlessDateTime>,
+	diagnostics: EntryDiagnostics,
+}
+
+fn parse_rfc5424(caps: &regex::Captures) -> Option<SyslogRecord> {
+	let mut log_entry = model::LogEntry {
+		severity: model::LogLevel::Info,
+		..Default::default()
+	};
+	if let Some(pri) = caps.get(1) {
+		apply_priority(&mut log_entry, pri.as_str())?;
+	}
+
+	let mut diagnostics = EntryDiagnostics::default();
+	let timestamp = &caps[2];
+	if timestamp != "-" {
+		if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp) {
+			log_entry.timestamp = dt.with_timezone(&Utc);
+		} else {
+			diagnostics.report(DiagnosticKind::MalformedTimestamp, timestamp);
+		}
+	}
+
+	let hostname = nil_to_none(&caps[3]);
+	let app_name = nil_to_none(&caps[4]);
+	if let Some(procid) = nil_to_none(&caps[5]) {
+		insert_string(&mut log_entry, "ProcessId", procid);
+	}
+	if let Some(msgid) = nil_to_none(&caps[6]) {
+		insert_string(&mut log_entry, "MessageId", msgid);
+	}
+
+	let rest = &caps[7];
+	let message = if let Some(rest) = rest.strip_prefix('-') {
+		rest
+	} else if rest.starts_with('[') {
+		let (params, rest) = parse_structured_data(rest)?;
+		for (key, value) in params {
+			log_entry.custom_fields.insert(
+				std::borrow::Cow::Owned(key),
+				model::CustomField::String(value),
+			);
+		}
+		rest
+	} else {
+		return None;
+	};
+	let message = message.strip_prefix(' ').unwrap_or(message);
+	//Messages may start with a UTF-8 byte order mark
+	log_entry.message = message.trim_start_matches('\u{feff}').to_string();
+
+	Some(SyslogRecord {
+		log_entry,
+		path: source_path(hostname, app_name),
+		yearless: None,
+		diagnostics,
+	})
+}
+
+fn parse_rfc3164(caps: &regex::Captures, tag_re: &Regex) -> Option<SyslogRecord> {
+	let mut log_entry = model::LogEntry {
+		severity: model::LogLevel::Info,
+		..Default::default()
+	};
+	if let Some(pri) = caps.get(1) {
+		apply_priority(&mut log_entry, pri.as_str())?;
+	}
+
+	let mut yearless = None;
+	if let Some(timestamp) = caps.get(2) {
+		yearless = Some(datetime_utils::parse_yearless(timestamp.as_str())?);
+	} else if let Some(timestamp) = caps.get(3) {
+		log_entry.timestamp = DateTime::parse_from_rfc3339(timestamp.as_str())
+			.ok()?
+			.with_timezone(&Utc);
+	}
+
+	let hostname = nil_to_none(&caps[4]);
+	let rest = &caps[5];
+	let app_name = if let Some(tag_caps) = tag_re.captures(rest) {
+		if let Some(pid) = tag_caps.get(2) {
+			insert_string(&mut log_entry, "ProcessId", pid.as_str());
+		}
+		log_entry.message = tag_caps[3].to_string();
+		Some(tag_caps.get(1).unwrap().as_str()) //Group 1 is not optional
+	} else {
+		log_entry.message = rest.to_string();
+		None
+	};
+
+	Some(SyslogRecord {
+		log_entry,
+		path: source_path(hostname, app_name),
+		yearless,
+		diagnostics: EntryDiagnostics::default(),
+	})
+}
+
+// Returns None if the priority is out of range, which means the line is no syslog record
+fn apply_priority(log_entry: &mut model::LogEntry, pri: &str) -> Option<()> {
+	let pri = pri.parse::<u8>().ok().filter(|pri| *pri <= 191)?;
+	log_entry.severity = normalize_syslog_sev(pri & 0x7);
+	log_entry.custom_fields.insert(
+		std::borrow::Cow::Borrowed("Facility"),
+		model::CustomField::UInt8(pri >> 3),
+	);
+	Some(())
+}
+
+fn normalize_syslog_sev(syslog_sev: u8) -> model::LogLevel {
+	match syslog_sev {
+		0 => model::LogLevel::Critical, //Emergency
+		1 => model::LogLevel::Critical, //Alert
+		2 => model::LogLevel::Critical, //Critical
+		3 => model::LogLevel::Error,
+		4 => model::LogLevel::Warning,
+		5 => model::LogLevel::Info, //Notice
+		6 => model::LogLevel::Info,
+		_ => model::LogLevel::Debug,
+	}
+}
+
+fn nil_to_none(value: &str) -> Option<&str> {
+	if value == "-" {
+		None
+	} else {
+		Some(value)
+	}
+}
+
+fn insert_string(log_entry: &mut model::LogEntry, key: &'static str, value: &str) {
+	log_entry.custom_fields.insert(
+		std::borrow::Cow::Borrowed(key),
+		model::CustomField::String(value.to_string()),
+	);
+}
+
+fn source_path(hostname: Option<&str>, app_name: Option<&str>) -> Vec<String> {
+	let mut path = vec![hostname.unwrap_or("Unknown host").to_string()];
+	if let Some(app_name) = app_name {
+		path.push(app_name.to_string());
+	}
+	path
+}
+
+// Parses `[id param="value" ...][id2 ...]` and returns the parameters as
+// ("id.param", value) pairs, plus the remaining text after the structured data.
+fn parse_structured_data(s: &str) -> Option<(Vec<(String, String)>, &str)> {
+	let mut params = Vec::new();
+	let mut rest = s;
+	while let Some(element) = rest.strip_prefix('[') {
+		let id_end = element.find([' ', ']'])?;
+		let sd_id = &element[..id_end];
+		rest = &element[id_end..];
+		loop {
+			rest = rest.trim_start_matches(' ');
+			if let Some(after) = rest.strip_prefix(']') {
+				rest = after;
+				break;
+			}
+			let name_end = rest.find('=')?;
+			let name = &rest[..name_end];
+			let value_str = rest[name_end + 1..].strip_prefix('"')?;
+			//Inside values, '"', '\' and ']' are escaped with a backslash
+			let mut value = String::new();
+			let mut escaped = false;
+			let mut value_end = None;
+			for (i, c) in value_str.char_indices() {
+				if escaped {
+					if c != '"' && c != '\\' && c != ']' {
+						value.push('\\');
+					}
+					value.push(c);
+					escaped = false;
+				} else if c == '\\' {
+					escaped = true;
+				} else if c == '"' {
+					value_end = Some(i);
+					break;
+				} else {
+					value.push(c);
+				}
+			}
+			rest = &value_str[value_end? + 1..];
+			params.push((format!("{}.{}", sd_id, name), value));
+		}
+	}
+	Some((params, rest))
+}
+
+// from_file -------------------------------------------------------------------------
+// Reads a syslog file from disk and returns the parsed log source.
+// The year of RFC 3164 timestamps is derived from the file modification time.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = std::fs::File::open(path)?;
+	let last_year = datetime_utils::modified_year(&file);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(progress.reader(file), root, last_year))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entries_of<'a>(source: &'a model::LogSource, path: &[&str]) -> &'a Vec<model::LogEntry> {
+		let mut source = source;
+		for name in path {
+			source = match &source.children {
+				model::LogSourceContents::Sources(v) => v
+					.iter()
+					.find(|s| s.name == *name)
+					.expect("Source not found"),
+				_ => panic!("Expected sub-sources"),
+			};
+		}
+		match &source.children {
+			model::LogSourceContents::Entries(v) => v,
+			_ => panic!("Expected entries"),
+		}
+	}
+
+	fn new_root() -> model::LogSource {
+		model::LogSource {
+			name: "syslog".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn test_parse_rfc3164_year_rollover() {
+		let log = "Dec 31 23:59:58 gateway CRON[1234]: (root) CMD (backup)\n\
+			<34>Jan  1 00:00:01 gateway su: 'su root' failed\n\
+			Jan  1 00:00:02 controller kernel: eth0 up\n";
+		let root = to_log_entries(log.as_bytes(), new_root(), 2021);
+
+		let cron = entries_of(&root, &["gateway", "CRON"]);
+		assert_eq!(
+			cron[0]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+			"2020-12-31T23:59:58Z"
+		);
+		assert!(matches!(
+			cron[0].custom_fields.get("ProcessId"),
+			Some(model::CustomField::String(pid)) if pid == "1234"
+		));
+
+		let su = entries_of(&root, &["gateway", "su"]);
+		assert_eq!(su[0].severity, model::LogLevel::Critical);
+		assert_eq!(su[0].message, "'su root' failed");
+		assert_eq!(
+			su[0]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+			"2021-01-01T00:00:01Z"
+		);
+		assert!(matches!(
+			su[0].custom_fields.get("Facility"),
+			Some(model::CustomField::UInt8(4))
+		));
+	}
+
+	#[test]
+	fn test_parse_rfc5424() {
+		let log = "<165>1 2003-10-11T22:14:15.003+02:00 mymachine evntslog - ID47 \
+			[exampleSDID@32473 iut=\"3\" eventSource=\"App\\]lication\"] An application event\n";
+		let root = to_log_entries(log.as_bytes(), new_root(), 2021);
+
+		let entries = entries_of(&root, &["mymachine", "evntslog"]);
+		assert_eq!(entries[0].severity, model::LogLevel::Info);
+		assert_eq!(entries[0].message, "An application event");
+		assert_eq!(
+			entries[0]
+				.timestamp
+				.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			"2003-10-11T20:14:15.003Z"
+		);
+		assert!(matches!(
+			entries[0].custom_fields.get("exampleSDID@32473.eventSource"),
+			Some(model::CustomField::String(value)) if value == "App]lication"
+		));
+		assert!(!entries[0].custom_fields.contains_key("ProcessId"));
+	}
+}
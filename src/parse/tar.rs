@@ -0,0 +1,411 @@
+use super::super::model;
+use super::io::LogParseError;
+use super::progress::Progress;
+use super::sfile;
+use super::sfile_rules::SfileRules;
+
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+// Tar archives ------------------------------------------------------------------------
+// Some product lines ship their diagnostic bundles as tar files, usually gzip
+// compressed (see `compression`), with the same members as an sfile. The members are
+// grouped and arranged in the same way (see `sfile::plan_jobs` and `sfile::build_tree`),
+// so a bundle gives the same log source tree in both formats.
+//
+// An uncompressed tar file is read twice: first the headers, skipping all data, then
+// the members with logs from their offsets while they are parsed. A compressed tar
+// cannot seek, so it is read in a single pass, keeping the members with logs in memory.
+// ustar, GNU long names and pax path records are understood.
+// ------------------------------------------------------------------------------------
+
+const BLOCK_SIZE: u64 = 512;
+//Long names and pax records are read into memory, larger ones mean a corrupt archive
+const MAX_EXTENSION_SIZE: u64 = 1024 * 1024;
+
+pub fn from_file(
+	path: &std::path::PathBuf,
+	rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let archive_name = path.file_name().unwrap().to_string_lossy().to_string();
+	let mut tar = TarReader::new(progress.archive_reader(std::fs::File::open(path)?));
+	let mut members = Vec::new();
+	let mut offsets = HashMap::<usize, (u64, u64)>::new(); //Of the members with logs
+	while let Some(header) = tar.next_member()? {
+		if progress.is_cancelled() {
+			return Err(LogParseError::Cancelled);
+		}
+		if sfile::member_kind(&header.name).is_some() {
+			offsets.insert(members.len(), (tar.data_offset()?, header.size));
+		}
+		tar.seek_data(header.size)?;
+		members.push(sfile::ArchiveMember {
+			name: header.name,
+			size: header.size,
+		});
+	}
+
+	parse_members(archive_name, &members, rules, progress, |idx| {
+		let (offset, size) = offsets.get(&idx).copied().unwrap_or_default();
+		let mut file = std::fs::File::open(path)?;
+		file.seek(std::io::SeekFrom::Start(offset))?;
+		Ok(Box::new(progress.archive_reader(file).take(size)))
+	})
+}
+
+// `reader` reads the (decompressed) tar archive
+pub fn to_log_source(
+	reader: impl Read,
+	archive_name: String,
+	rules: &SfileRules,
+	progress: &Progress,
+) -> Result<model::LogSource, LogParseError> {
+	let mut tar = TarReader::new(reader);
+	let mut members = Vec::new();
+	let mut contents = HashMap::<usize, Vec<u8>>::new(); //Of the members with logs
+	while let Some(header) = tar.next_member()? {
+		if progress.is_cancelled() {
+			return Err(LogParseError::Cancelled);
+		}
+		if sfile::member_kind(&header.name).is_some() {
+			contents.insert(members.len(), tar.read_data(header.size)?);
+		} else {
+			tar.skip_data(header.size)?;
+		}
+		members.push(sfile::ArchiveMember {
+			name: header.name,
+			size: header.size,
+		});
+	}
+
+	parse_members(archive_name, &members, rules, progress, |idx| {
+		Ok(Box::new(contents.get(&idx).map_or(&[][..], Vec::as_slice)))
+	})
+}
+
+// `member` reads the data of the member with the given index
+fn parse_members<'a>(
+	archive_name: String,
+	members: &[sfile::ArchiveMember],
+	rules: &SfileRules,
+	progress: &Progress,
+	member: impl Fn(usize) -> Result<Box<dyn Read + 'a>, std::io::Error> + Sync,
+) -> Result<model::LogSource, LogParseError> {
+	let jobs = sfile::plan_jobs(members);
+	let log_sources = sfile::parse_jobs(
+		&archive_name,
+		&jobs,
+		progress,
+		(),
+		|| Ok(()),
+		|_, job| {
+			//Glog files of a group are concatenated
+			let mut reader = Box::new(std::io::empty()) as Box<dyn Read>;
+			for idx in &job.indices {
+				reader = Box::new(reader.chain(member(*idx)?));
+			}
+			Ok(sfile::parse_member(job, progress.member_reader(reader)))
+		},
+	)?;
+	Ok(sfile::build_tree(archive_name, &jobs, log_sources, rules))
+}
+
+struct MemberHeader {
+	name: String,
+	size: u64,
+}
+
+struct TarReader<R: Read> {
+	reader: R,
+}
+
+impl<R: Read> TarReader<R> {
+	fn new(reader: R) -> TarReader<R> {
+		TarReader { reader }
+	}
+
+	// Header of the next file, skipping directories, links and other special members.
+	// Returns Ok(None) at the end of the archive.
+	fn next_member(&mut self) -> Result<Option<MemberHeader>, std::io::Error> {
+		let mut long_name = None; //Of the next member, from a GNU or pax extension header
+		loop {
+			let mut header = [0u8; BLOCK_SIZE as usize];
+			match self.reader.read_exact(&mut header) {
+				Ok(()) => (),
+				//Some writers leave out the two zero blocks at the end
+				Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+				Err(e) => return Err(e),
+			}
+			if header.iter().all(|b| *b == 0) {
+				return Ok(None);
+			}
+
+			let size = parse_size(&header[124..136])?;
+			match header[156] {
+				//Regular file
+				b'0' | b'\0' | b'7' => {
+					let name = long_name.take().unwrap_or_else(|| header_name(&header));
+					//Bundles made with `tar -C dir .` have "./" in front of every name
+					let name = name.trim_start_matches("./").to_string();
+					return Ok(Some(MemberHeader { name, size }));
+				}
+				//GNU long name of the next member
+				b'L' => {
+					let data = self.read_extension(size)?;
+					long_name = Some(c_string(&data));
+				}
+				//pax extended header of the next member
+				b'x' => {
+					let data = self.read_extension(size)?;
+					long_name = pax_path(&data).or(long_name);
+				}
+				_ => self.skip_data(size)?,
+			}
+		}
+	}
+
+	fn read_extension(&mut self, size: u64) -> Result<Vec<u8>, std::io::Error> {
+		if size > MAX_EXTENSION_SIZE {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("Extension header of {} bytes in tar archive", size),
+			));
+		}
+		self.read_data(size)
+	}
+
+	// The size comes from the header and may be corrupt, so the buffer only grows with
+	// the data that is actually there
+	fn read_data(&mut self, size: u64) -> Result<Vec<u8>, std::io::Error> {
+		let mut data = Vec::new();
+		(&mut self.reader).take(size).read_to_end(&mut data)?;
+		if (data.len() as u64) < size {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		self.skip_padding(size)?;
+		Ok(data)
+	}
+
+	fn skip_data(&mut self, size: u64) -> Result<(), std::io::Error> {
+		let skipped = std::io::copy(&mut (&mut self.reader).take(size), &mut std::io::sink())?;
+		if skipped < size {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		self.skip_padding(size)
+	}
+
+	//Data is padded to full blocks
+	fn skip_padding(&mut self, size: u64) -> Result<(), std::io::Error> {
+		let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+		std::io::copy(&mut (&mut self.reader).take(padding), &mut std::io::sink())?;
+		Ok(())
+	}
+}
+
+impl<R: Read + Seek> TarReader<R> {
+	// Offset of the data of the member whose header was read last
+	fn data_offset(&mut self) -> Result<u64, std::io::Error> {
+		self.reader.stream_position()
+	}
+
+	// Same as `skip_data`, without reading the data
+	fn seek_data(&mut self, size: u64) -> Result<(), std::io::Error> {
+		let padded_size = size
+			.checked_add((BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE)
+			.and_then(|size| i64::try_from(size).ok())
+			.ok_or_else(|| {
+				std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					format!("Malformed size in tar header: {}", size),
+				)
+			})?;
+		self.reader.seek(std::io::SeekFrom::Current(padded_size))?;
+		Ok(())
+	}
+}
+
+fn c_string(bytes: &[u8]) -> String {
+	let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+	String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+// ustar splits long names into a prefix and a name
+fn header_name(header: &[u8]) -> String {
+	let name = c_string(&header[0..100]);
+	let prefix = if header[257..262] == *b"ustar" {
+		c_string(&header[345..500])
+	} else {
+		String::new()
+	};
+	if prefix.is_empty() {
+		name
+	} else {
+		format!("{}/{}", prefix, name)
+	}
+}
+
+// Octal number, or big-endian binary if the high bit of the first byte is set (GNU)
+fn parse_size(field: &[u8]) -> Result<u64, std::io::Error> {
+	if field[0] & 0x80 != 0 {
+		return Ok(field[1..]
+			.iter()
+			.fold(0u64, |size, b| (size << 8) | *b as u64));
+	}
+	let octal = c_string(field);
+	let octal = octal.trim_matches(|c: char| c == ' ' || c == '\0');
+	if octal.is_empty() {
+		return Ok(0);
+	}
+	u64::from_str_radix(octal, 8).map_err(|_| {
+		std::io::Error::new(
+			std::io::ErrorKind::InvalidData,
+			format!("Malformed size in tar header: {}", octal),
+		)
+	})
+}
+
+// pax records look like "30 path=some/long/file/name\n", the number is the record length
+fn pax_path(data: &[u8]) -> Option<String> {
+	let records = String::from_utf8_lossy(data);
+	records.lines().find_map(|record| {
+		let (_, key_value) = record.split_once(' ')?;
+		key_value.strip_prefix("path=").map(str::to_string)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+		let mut header = vec![0u8; BLOCK_SIZE as usize];
+		header[..name.len()].copy_from_slice(name.as_bytes());
+		header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+		header[156] = kind;
+		header[257..263].copy_from_slice(b"ustar\0");
+		header
+	}
+
+	fn append_member(tar: &mut Vec<u8>, name: &str, data: &[u8], kind: u8) {
+		tar.extend(header(name, data.len(), kind));
+		tar.extend(data);
+		tar.resize(tar.len() + (512 - data.len() % 512) % 512, 0);
+	}
+
+	fn assert_bundle(root: &model::LogSource) {
+		assert_eq!(root.name, "bundle.tar");
+		let sources = match &root.children {
+			model::LogSourceContents::Sources(v) => v,
+			_ => panic!("Expected sub-sources"),
+		};
+		let names: Vec<&str> = sources.iter().map(|source| source.name.as_str()).collect();
+		assert_eq!(names, vec!["Client", "Controller", "Sensor", "RDS"]);
+		//Both ring buffer files of Hwa end up in one log source, the older one first
+		let hwa = match &sources[1].children {
+			model::LogSourceContents::Sources(v) => &v[0],
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(hwa.name, "Hwa");
+		let entries = match &hwa.children {
+			//Sub-source of log source ID 12
+			model::LogSourceContents::Sources(v) => match &v[0].children {
+				model::LogSourceContents::Entries(v) => v,
+				_ => panic!("Expected entries"),
+			},
+			_ => panic!("Expected sub-sources"),
+		};
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].message, "Old.");
+	}
+
+	#[test]
+	fn test_read_tar() {
+		let long_name = format!("{}/contr_Hwa.glog", "nested".repeat(20));
+		let pax = "24 path=./RDS/agent.log\n";
+		let mut tar = Vec::new();
+		append_member(&mut tar, "./", b"", b'5');
+		append_member(
+			&mut tar,
+			"./contr_Hwa_1.glog",
+			b"[tq|1568208334469]:[s|4]:[i|12]:[m|Old.]\n",
+			b'0',
+		);
+		append_member(&mut tar, "././@LongLink", long_name.as_bytes(), b'L');
+		append_member(&mut tar, "ignored", b"", b'0');
+		append_member(
+			&mut tar,
+			"./contr_Hwa.glog",
+			b"[tq|1568208334470]:[s|4]:[i|12]:[m|New.]\n",
+			b'0',
+		);
+		append_member(&mut tar, "PaxHeaders/agent.log", pax.as_bytes(), b'x');
+		append_member(
+			&mut tar,
+			"./RDS/truncated_name.log",
+			b"2020-12-01 15:46:19.122|INFO|RDSAgent|Started.\n",
+			b'0',
+		);
+		append_member(&mut tar, "./core.dump", &[0xab; 700], b'0');
+		tar.extend([0u8; 1024]);
+
+		let mut reader = TarReader::new(tar.as_slice());
+		let mut names = Vec::new();
+		while let Some(member) = reader.next_member().unwrap() {
+			reader.skip_data(member.size).unwrap();
+			names.push(member.name);
+		}
+		assert_eq!(
+			names,
+			vec![
+				"contr_Hwa_1.glog".to_string(),
+				long_name.clone(),
+				"contr_Hwa.glog".to_string(),
+				"RDS/agent.log".to_string(),
+				"core.dump".to_string(),
+			]
+		);
+
+		let progress = Progress::new();
+		let root = to_log_source(
+			tar.as_slice(),
+			"bundle.tar".to_string(),
+			&SfileRules::default(),
+			&progress,
+		)
+		.unwrap();
+		assert_bundle(&root);
+
+		//Seeks to the members instead of keeping them in memory
+		let dir = std::env::temp_dir().join(format!("sherlog_tar_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("bundle.tar");
+		std::fs::write(&path, &tar).unwrap();
+		let root = from_file(&path, &SfileRules::default(), &progress);
+		std::fs::remove_dir_all(&dir).unwrap();
+		assert_bundle(&root.unwrap());
+	}
+
+	#[test]
+	fn test_malformed_tar() {
+		let mut tar = header("contr_Hwa.glog", 100, b'0');
+		tar[124..128].copy_from_slice(b"9x9x");
+		assert!(TarReader::new(tar.as_slice()).next_member().is_err());
+
+		let tar = header("contr_Hwa.glog", 100, b'0'); //Data missing
+		let mut reader = TarReader::new(tar.as_slice());
+		let member = reader.next_member().unwrap().unwrap();
+		assert!(reader.read_data(member.size).is_err());
+
+		//GNU base-256 size, far larger than the archive
+		let mut tar = header("././@LongLink", 0, b'L');
+		tar[124..128].copy_from_slice(&[0x80, 0, 0, 0]);
+		tar[128..136].copy_from_slice(&[0xff; 8]);
+		assert!(TarReader::new(tar.as_slice()).next_member().is_err());
+		tar[156] = b'0';
+		let mut reader = TarReader::new(tar.as_slice());
+		let member = reader.next_member().unwrap().unwrap();
+		assert_eq!(member.size, u64::MAX);
+		assert!(reader.read_data(member.size).is_err());
+	}
+}
@@ -1,15 +1,19 @@
 use super::super::model;
 
 use super::datetime_utils;
+use super::diagnostics::EntryDiagnostics;
+use super::progress::Progress;
 
 use std::io::BufRead;
 use std::io::BufReader;
+use model::DiagnosticKind;
 
 // XLOG parser ----------------------------------------------------------------------
 
 pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) -> model::LogSource {
 	let bufreader = BufReader::new(reader);
 	let mut log_entries = Vec::<model::LogEntry>::new();
+	let mut entry_diagnostics = EntryDiagnostics::default();
 	for line in bufreader.lines() {
 		let mut log_entry = model::LogEntry {
 			..Default::default()
@@ -27,37 +31,75 @@ pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) ->
 							if let Some(datetime) = datetime_utils::from_100ns(gcom_datetime) {
 								log_entry.timestamp = datetime;
 							} else {
-								//TODO: Notify of invalid datetime?
-								log::warn!("MALFORMED Log 100ns datetime: {}", gcom_datetime);
+								entry_diagnostics.report(DiagnosticKind::MalformedTimestamp, unit_value);
 							}
 						} else {
-							//TODO: Notify of invalid time?
-							log::warn!("MALFORMED Log 100ns value: {}", unit_value);
+							entry_diagnostics.report(DiagnosticKind::MalformedTimestamp, unit_value);
 						}
 					}
 					"<L>" => {
 						if let Some(xlog_sev) = XlogSeverity::from_str(unit_value) {
 							log_entry.severity = normalize_xlog_sev(xlog_sev);
+							log_entry.source_severity = xlog_source_sev(xlog_sev);
 						} else {
-							//TODO: Notify of invalid severity?
-							log::warn!("INVALID Log severity: {}", unit_value);
+							entry_diagnostics.report(DiagnosticKind::InvalidSeverity, unit_value);
 						}
 					}
 					"<M>" => {
 						log_entry.message = unit_value.to_string();
 					}
+					"<E>" => insert_string(&mut log_entry, "Exception", decode_newlines(unit_value)),
+					"<PIE>" => insert_string(
+						&mut log_entry,
+						"PrivateInnerException",
+						decode_newlines(unit_value),
+					),
+					"<A>" => insert_string(&mut log_entry, "Application", unit_value.to_string()),
+					"<C>" => insert_string(&mut log_entry, "Channel", unit_value.to_string()),
+					"<I>" => {
+						if let Ok(pid) = unit_value.parse::<u32>() {
+							log_entry.custom_fields.insert(
+								std::borrow::Cow::Borrowed("ProcessId"),
+								model::CustomField::UInt32(pid),
+							);
+						} else {
+							entry_diagnostics.report(DiagnosticKind::MalformedField, unit);
+						}
+					}
+					"<S>" => {
+						//Used as session ID in the GUI, see `LogEntryExt::from_entry`
+						if let Ok(session_id) = unit_value.parse::<u32>() {
+							log_entry.custom_fields.insert(
+								std::borrow::Cow::Borrowed("SessionId"),
+								model::CustomField::UInt32(session_id),
+							);
+						} else {
+							entry_diagnostics.report(DiagnosticKind::MalformedField, unit);
+						}
+					}
+					"<EN>" => {
+						//.NET error numbers (HRESULT) are signed, e.g. -2147467259
+						if let Ok(error_number) = unit_value.parse::<i32>() {
+							log_entry.custom_fields.insert(
+								std::borrow::Cow::Borrowed("ErrorNumber"),
+								model::CustomField::Int32(error_number),
+							);
+						} else {
+							entry_diagnostics.report(DiagnosticKind::MalformedField, unit);
+						}
+					}
 					_ => {
-						//TODO: Notify of invalid kind?
-						//log::warn!("UNRECOGNIZED kind: {}", &unit_header);
+						entry_diagnostics.report(DiagnosticKind::UnrecognizedKind, unit);
 					}
 				}
 
 			//log::info!("Header: [{}] Value: [{}]", unit_header, unit_value);
 			} else {
-				log::warn!("ERROR: NO DELMIMTER FOUND IN {}", unit);
+				entry_diagnostics.report(DiagnosticKind::MalformedField, unit);
 			}
 		}
 
+		entry_diagnostics.attach(&root.name, &mut root.diagnostics, log_entries.len());
 		log_entries.push(log_entry);
 	}
 
@@ -65,6 +107,18 @@ pub fn to_log_entries(reader: impl std::io::Read, mut root: model::LogSource) ->
 	root
 }
 
+fn insert_string(log_entry: &mut model::LogEntry, name: &'static str, value: String) {
+	log_entry.custom_fields.insert(
+		std::borrow::Cow::Borrowed(name),
+		model::CustomField::String(value),
+	);
+}
+
+//Multi-line values have their newlines encoded as '˪'
+fn decode_newlines(value: &str) -> String {
+	value.replace('˪', "\n")
+}
+
 #[rustfmt::skip]
 fn normalize_xlog_sev(xlog_sev: XlogSeverity) -> model::LogLevel {
 	match xlog_sev {
@@ -78,6 +132,17 @@ fn normalize_xlog_sev(xlog_sev: XlogSeverity) -> model::LogLevel {
 	}
 }
 
+// Keeps the severities that get lost in `normalize_xlog_sev`
+fn xlog_source_sev(xlog_sev: XlogSeverity) -> Option<model::SourceSeverity> {
+	match xlog_sev {
+		XlogSeverity::AppStart => Some(model::SourceSeverity::AppStart),
+		XlogSeverity::AppStop => Some(model::SourceSeverity::AppStop),
+		XlogSeverity::Exception => Some(model::SourceSeverity::Exception),
+		_ => None,
+	}
+}
+
+#[derive(Copy, Clone)]
 enum XlogSeverity {
 	AppStart,
 	AppStop,
@@ -138,3 +203,74 @@ impl XlogSeverity {
 //1. Files with same Application_PID_channel - concatenate /group in temporal order.
 //2. Group the collections formed in (1) by channel
 //3. Parse & merge sort together
+
+// from_file -------------------------------------------------------------------------
+// Reads a single XLOG file from disk and returns the parsed log source.
+// Returns std::io::Error on file read errors.
+// ------------------------------------------------------------------------------------
+pub fn from_file(
+	path: &std::path::PathBuf,
+	progress: &Progress,
+) -> Result<model::LogSource, std::io::Error> {
+	let file = progress.reader(std::fs::File::open(path)?);
+	let root = model::LogSource {
+		name: path.file_name().unwrap().to_string_lossy().to_string(),
+		children: { model::LogSourceContents::Entries(Vec::<model::LogEntry>::new()) },
+		diagnostics: Vec::new(),
+	};
+	Ok(to_log_entries(file, root))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_xlog() {
+		let input = "<T>˩637055156092730381˫<L>˩Exception˫<M>˩Save failed˫<E>˩System.IO.IOException: Disk full˪   at Foo.Save()˫<A>˩ApplicationX˫<I>˩14016˫<C>˩System˫<S>˩42˫<EN>˩-2147467259\n\
+			<T>˩637055156092730382˫<L>˩Info˫<M>˩Done˫<I>˩PID˫<X>˩?\n";
+		let root = model::LogSource {
+			name: "test.xlog".to_string(),
+			children: model::LogSourceContents::Entries(Vec::new()),
+			diagnostics: Vec::new(),
+		};
+		let root = to_log_entries(input.as_bytes(), root);
+		let entries = match &root.children {
+			model::LogSourceContents::Entries(entries) => entries,
+			_ => panic!("Expected entries"),
+		};
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].message, "Save failed");
+		assert_eq!(entries[0].severity, model::LogLevel::Error);
+		assert_eq!(entries[0].source_severity, Some(model::SourceSeverity::Exception));
+		assert_eq!(entries[1].source_severity, None);
+		match entries[0].custom_fields.get("Exception") {
+			Some(model::CustomField::String(exception)) => {
+				assert_eq!(exception, "System.IO.IOException: Disk full\n   at Foo.Save()")
+			}
+			_ => panic!("Expected exception"),
+		}
+		assert!(matches!(
+			entries[0].custom_fields.get("ProcessId"),
+			Some(model::CustomField::UInt32(14016))
+		));
+		assert!(matches!(
+			entries[0].custom_fields.get("SessionId"),
+			Some(model::CustomField::UInt32(42))
+		));
+		assert!(matches!(
+			entries[0].custom_fields.get("ErrorNumber"),
+			Some(model::CustomField::Int32(-2147467259))
+		));
+		assert!(matches!(
+			entries[0].custom_fields.get("Channel"),
+			Some(model::CustomField::String(channel)) if channel == "System"
+		));
+
+		//Malformed PID and unknown unit
+		assert!(!entries[1].custom_fields.contains_key("ProcessId"));
+		assert_eq!(root.diagnostics.len(), 2);
+		assert_eq!(root.diagnostics[0].location, model::DiagnosticLocation::Entry(1));
+		assert_eq!(root.diagnostics[1].kind, DiagnosticKind::UnrecognizedKind);
+	}
+}
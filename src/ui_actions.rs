@@ -75,23 +75,71 @@ pub fn search_changed(
 pub fn timeshift_changed(
 entry: &gtk::Entry, 
 store: &mut LogStoreLinear,
+shift_idx: usize,
 drawing_area: &gtk::DrawingArea)
 {
     
     // parse time shift from timeshift_entry format "+0D 00:00:00.000"
     let timeshift_text = entry.text().to_string();
-    log::info!("timeshift_changed {}", &timeshift_text);
+    log::info!("timeshift_changed {} ({})", &timeshift_text, store.sensor_shifts[shift_idx].archive_name);
     let time_shift = ui_formatting::parse_duration(&timeshift_text);
-    let actual_shift = time_shift - store.sensor_shift;
-    store.sensor_shift = time_shift;
+    let actual_shift = time_shift - store.sensor_shifts[shift_idx].sensor_shift;
+    store.sensor_shifts[shift_idx].sensor_shift = time_shift;
     // apply time shift
 
-    store.shift_store_times(actual_shift);
+    store.shift_store_times(shift_idx, actual_shift);
     resort_store_preserve_state(store);
     drawing_area.queue_draw();
     entry.set_text(&ui_formatting::format_duration(time_shift));
 }
 
+// show only the entries with an error code, or all entries for None
+pub fn error_code_filter_changed(
+    store: &mut LogStoreLinear,
+    error_code: Option<u32>,
+    drawing_area: &gtk::DrawingArea,
+) {
+    if store.error_code_filter == error_code {
+        return;
+    }
+    store.error_code_filter = error_code;
+    store.filter_store(&|_entry: &LogEntryExt| true, true, crate::model_internal::VISIBLE_OFF_ERROR_CODE);
+    if let Some(error_code) = error_code {
+        store.filter_store(
+            &|entry: &LogEntryExt| entry.error_code != Some(error_code),
+            false,
+            crate::model_internal::VISIBLE_OFF_ERROR_CODE,
+        );
+    }
+    drawing_area.queue_draw();
+}
+
+// jump to the entry of a diagnostic, returns false if the entry is hidden by the filters
+pub fn jump_to_diagnostic(
+    store: &mut LogStoreLinear,
+    diagnostic_id: u32,
+    drawing_area: &gtk::DrawingArea,
+) -> bool {
+    let offset = match store
+        .store
+        .iter()
+        .position(|entry| entry.diagnostic_id == Some(diagnostic_id))
+    {
+        Some(offset) => offset,
+        None => return true,
+    };
+    let visible = store.store[offset].is_visible();
+    store.selected_single.clear();
+    store.selected_range = None;
+    store.excluded_single.clear();
+    store.selected_single.insert(offset);
+    store.selected_single_last = Some(offset);
+    store.anchor_offset = Some(offset);
+    store.filter_store(&|_entry: &LogEntryExt| true, false, crate::model_internal::VISIBLE_ON); //Centers the view on the anchor
+    drawing_area.queue_draw();
+    visible
+}
+
 pub fn sort_changed(
 	sort_combo: &gtk::ComboBoxText,
 	store: &mut LogStoreLinear,
@@ -109,7 +157,7 @@ pub fn sort_changed(
 	}
 }
 
-fn resort_store_preserve_state(store: &mut LogStoreLinear) {
+pub fn resort_store_preserve_state(store: &mut LogStoreLinear) {
     // misuse the entry_id to remember selected
     let mut selection_active = false;
     for &offset in store.selected_single.iter() {
@@ -1,6 +1,8 @@
 // src/formatting.rs
 
 use crate::model::LogLevel;
+use crate::model::SourceSeverity;
+use crate::model_internal::LogEntryExt;
 
 // +0D 00:00:00.000
 pub fn parse_duration(s: &str) -> chrono::Duration {
@@ -60,6 +62,24 @@ pub fn short_severity(sev: &LogLevel) -> &'static str {
     }
 }
 
+pub fn short_source_severity(sev: &SourceSeverity) -> &'static str {
+    match sev {
+        SourceSeverity::AppStart => "STA",
+        SourceSeverity::AppStop => "STP",
+        SourceSeverity::Exception => "EXC",
+        SourceSeverity::Hardware => "HW",
+        SourceSeverity::Unspecified => "NON",
+    }
+}
+
+// Severity column: the severity as logged, if it is more detailed than the normalized one
+pub fn short_entry_severity(entry: &LogEntryExt) -> &'static str {
+    match &entry.source_severity {
+        Some(source_severity) => short_source_severity(source_severity),
+        None => short_severity(&entry.severity),
+    }
+}
+
 // Timezone information structure
 #[derive(Clone)]
 pub struct TimezoneInfo {
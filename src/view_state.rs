@@ -0,0 +1,126 @@
+extern crate chrono;
+
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::log_store::LogStoreLinear;
+use crate::log_store::SortMode;
+use crate::model;
+use crate::model_internal::LogEntryExt;
+
+// View state ------------------------------------------------------------------------
+// Everything the user set up to look at a log: filters, anchor, selection, time shift
+// and sort mode. Captured before a reload and reapplied to the re-parsed log.
+//
+// Log entries get new offsets and log sources get new IDs when a file is parsed
+// again. Therefore, log sources are identified by their path in the tree (as in
+// `LogStoreLinear::log_sources`) and log entries by their timestamp plus message.
+// ------------------------------------------------------------------------------------
+
+pub struct ViewState {
+	pub shown_severities: Vec<(model::LogLevel, bool)>,
+	pub hidden_source_severities: HashSet<model::SourceSeverity>,
+	pub error_code_filter: Option<u32>,
+	pub hidden_sources: HashSet<String>,
+	pub search_text: String,
+	pub search_case_sensitive: bool,
+	pub search_enabled: bool,
+	pub sensor_shifts: HashMap<String, chrono::Duration>, //By archive name
+	pub sort_mode: SortMode,
+	anchor: Option<(DateTime<Utc>, String)>,
+	selection: HashMap<DateTime<Utc>, Vec<String>>,
+}
+
+const SEVERITIES: [model::LogLevel; 6] = [
+	model::LogLevel::Critical,
+	model::LogLevel::Error,
+	model::LogLevel::Warning,
+	model::LogLevel::Info,
+	model::LogLevel::Debug,
+	model::LogLevel::Trace,
+];
+
+impl ViewState {
+	// Captures the state kept in the store. The caller fills in the state that is
+	// only kept in widgets (hidden sources and search).
+	pub fn from_store(store: &LogStoreLinear) -> ViewState {
+		let mut selection = HashMap::<DateTime<Utc>, Vec<String>>::new();
+		for (offset, entry) in store.store.iter().enumerate() {
+			if is_selected(store, offset) {
+				selection
+					.entry(entry.timestamp)
+					.or_default()
+					.push(entry.message.clone());
+			}
+		}
+
+		ViewState {
+			shown_severities: SEVERITIES
+				.iter()
+				.map(|severity| (*severity, store.is_severity_shown(*severity)))
+				.collect(),
+			hidden_source_severities: store.hidden_source_severities.clone(),
+			error_code_filter: store.error_code_filter,
+			hidden_sources: HashSet::new(),
+			search_text: String::new(),
+			search_case_sensitive: false,
+			search_enabled: true,
+			sensor_shifts: store
+				.sensor_shifts
+				.iter()
+				.map(|shift| (shift.archive_name.clone(), shift.sensor_shift))
+				.collect(),
+			sort_mode: store.sort_mode,
+			anchor: store
+				.anchor_offset
+				.and_then(|offset| store.store.get(offset))
+				.map(|entry| (entry.timestamp, entry.message.clone())),
+			selection,
+		}
+	}
+
+	// Restores anchor and selection. Must be called after the time shift has been
+	// reapplied, as the timestamps are compared including the shift.
+	pub fn restore_entries(&self, store: &mut LogStoreLinear) {
+		store.selected_single.clear();
+		store.excluded_single.clear();
+		store.selected_range = None;
+		store.selected_single_last = None;
+		store.anchor_offset = None;
+		for (offset, entry) in store.store.iter().enumerate() {
+			if let Some(messages) = self.selection.get(&entry.timestamp) {
+				if messages.contains(&entry.message) {
+					store.selected_single.insert(offset);
+				}
+			}
+			if store.anchor_offset.is_none() {
+				if let Some((timestamp, message)) = &self.anchor {
+					if entry.timestamp == *timestamp && entry.message == *message {
+						store.anchor_offset = Some(offset);
+					}
+				}
+			}
+		}
+
+		if self.anchor.is_some() && store.anchor_offset.is_none() {
+			log::info!("Anchor entry not found after reload");
+		}
+
+		// combination of active: false and mask: VISIBLE_ON (0) only recalculates the visible
+		// entries and aligns the viewport to the anchor, without changing the filter state
+		store.filter_store(
+			&|_entry: &LogEntryExt| true,
+			false,
+			crate::model_internal::VISIBLE_ON,
+		);
+	}
+}
+
+fn is_selected(store: &LogStoreLinear, offset: usize) -> bool {
+	(store.selected_single.contains(&offset)
+		|| store
+			.selected_range
+			.is_some_and(|(start, end)| start <= offset && offset <= end))
+		&& !store.excluded_single.contains(&offset)
+}